@@ -1,22 +1,37 @@
 use anyhow::Result;
 use matrix_sdk::{
-    Client, Room, SessionMeta, SessionTokens,
+    Client, LoopCtrl, Room, RoomMemberships, SessionMeta, SessionTokens,
     authentication::matrix::MatrixSession,
     config::SyncSettings,
     encryption::{
-        BackupDownloadStrategy, EncryptionSettings,
+        BackupDownloadStrategy, EncryptionSettings, LocalTrust,
         verification::{SasVerification, VerificationRequest, VerificationRequestState},
     },
     media::{MediaFormat, MediaRequestParameters},
-    room::MessagesOptions,
+    room::{MessagesOptions, Receipts},
     ruma::{
-        OwnedEventId, OwnedRoomId, OwnedUserId, UInt, UserId,
-        api::client::receipt::create_receipt,
+        OwnedEventId, OwnedRoomAliasId, OwnedRoomId, OwnedRoomOrAliasId, OwnedServerName,
+        OwnedUserId, UInt,
+        UserId,
+        ClientSecret, OwnedClientSecret, OwnedDeviceId, OwnedSessionId,
+        api::client::{
+            account::request_openid_token,
+            context::get_context,
+            membership::Invite3pidInit,
+            message::get_message_events,
+            presence::{get_presence, set_presence},
+            receipt::create_receipt::v3::ReceiptType as CreateReceiptType,
+            room::Visibility,
+            room::{get_room_visibility, set_room_visibility},
+            uiaa::{AuthData, Password, UserIdentifier},
+        },
         events::{
             AnySyncMessageLikeEvent, AnySyncTimelineEvent, SyncEphemeralRoomEvent,
+            fully_read::FullyReadEventContent,
             key::verification::VerificationMethod,
             reaction::OriginalSyncReactionEvent,
-            receipt::ReceiptThread,
+            presence::PresenceEvent,
+            receipt::{ReceiptEventContent, ReceiptThread, ReceiptType},
             relation::Annotation,
             room::message::{
                 AddMentions, ForwardThread, MessageType, OriginalSyncRoomMessageEvent,
@@ -24,15 +39,24 @@ use matrix_sdk::{
                 RoomMessageEventContentWithoutRelation, SyncRoomMessageEvent,
             },
             room::MediaSource,
+            room::member::{MembershipState, OriginalSyncRoomMemberEvent},
+            room::power_levels::OriginalSyncRoomPowerLevelsEvent,
             typing::TypingEventContent,
+            GlobalAccountDataEventType, TimelineEventType,
         },
+        presence::PresenceState,
+        serde::Raw,
+        thirdparty::Medium,
     },
 };
 use futures_util::StreamExt;
+use std::collections::{HashMap, HashSet};
 use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
 use tokio::sync::mpsc;
 use tokio::task::JoinHandle;
-use tracing::info;
+use tracing::{info, warn};
 
 use crate::config::{SavedAccount, data_dir};
 
@@ -64,6 +88,454 @@ fn strip_reply_fallback(body: &str) -> String {
     }
 }
 
+/// Convert a page of timeline events (newest first) into display messages.
+/// Events that fail to deserialize are most likely undecryptable.
+fn history_to_display(
+    events: impl Iterator<Item = serde_json::Result<AnySyncTimelineEvent>>,
+) -> Vec<crate::app::DisplayMessage> {
+    let mut messages = Vec::new();
+
+    for event in events {
+        match event {
+            Ok(AnySyncTimelineEvent::MessageLike(
+                AnySyncMessageLikeEvent::RoomMessage(SyncRoomMessageEvent::Original(original)),
+            )) => {
+                let reply_to_event_id = match &original.content.relates_to {
+                    Some(Relation::Reply { in_reply_to }) => {
+                        Some(in_reply_to.event_id.to_string())
+                    }
+                    _ => None,
+                };
+                // Handle image messages with full metadata
+                if let MessageType::Image(ref img) = original.content.msgtype {
+                    messages.push(crate::app::DisplayMessage {
+                        sender: original.sender.to_string(),
+                        content: crate::app::MessageContent::Image {
+                            body: img.filename().to_string(),
+                            source: img.source.clone(),
+                            protocol: None,
+                            image: None,
+                            loading: false,
+                        },
+                        timestamp: original.origin_server_ts.as_secs().into(),
+                        event_id: Some(original.event_id.to_string()),
+                        reply_to_sender: None,
+                        reply_to_body: None,
+                        reactions: Vec::new(),
+                        send_error: None,
+                        notice: false,
+                        reply_to_event_id_raw: reply_to_event_id,
+                    });
+                } else if let MessageType::File(ref f) = original.content.msgtype {
+                    messages.push(crate::app::DisplayMessage {
+                        sender: original.sender.to_string(),
+                        content: crate::app::MessageContent::File {
+                            body: f.filename().to_string(),
+                            source: f.source.clone(),
+                            media_type: crate::app::FileKind::File,
+                        },
+                        timestamp: original.origin_server_ts.as_secs().into(),
+                        event_id: Some(original.event_id.to_string()),
+                        reply_to_sender: None,
+                        reply_to_body: None,
+                        reactions: Vec::new(),
+                        send_error: None,
+                        notice: false,
+                        reply_to_event_id_raw: reply_to_event_id,
+                    });
+                } else if let MessageType::Video(ref v) = original.content.msgtype {
+                    messages.push(crate::app::DisplayMessage {
+                        sender: original.sender.to_string(),
+                        content: crate::app::MessageContent::File {
+                            body: v.filename().to_string(),
+                            source: v.source.clone(),
+                            media_type: crate::app::FileKind::Video,
+                        },
+                        timestamp: original.origin_server_ts.as_secs().into(),
+                        event_id: Some(original.event_id.to_string()),
+                        reply_to_sender: None,
+                        reply_to_body: None,
+                        reactions: Vec::new(),
+                        send_error: None,
+                        notice: false,
+                        reply_to_event_id_raw: reply_to_event_id,
+                    });
+                } else if let MessageType::Audio(ref a) = original.content.msgtype {
+                    messages.push(crate::app::DisplayMessage {
+                        sender: original.sender.to_string(),
+                        content: crate::app::MessageContent::File {
+                            body: a.filename().to_string(),
+                            source: a.source.clone(),
+                            media_type: crate::app::FileKind::Audio,
+                        },
+                        timestamp: original.origin_server_ts.as_secs().into(),
+                        event_id: Some(original.event_id.to_string()),
+                        reply_to_sender: None,
+                        reply_to_body: None,
+                        reactions: Vec::new(),
+                        send_error: None,
+                        notice: false,
+                        reply_to_event_id_raw: reply_to_event_id,
+                    });
+                } else {
+                    let body = match &original.content.msgtype {
+                        MessageType::Text(text) => text.body.clone(),
+                        MessageType::Notice(n) => n.body.clone(),
+                        MessageType::Emote(e) => format!("* {}", e.body),
+                        _ => "[unsupported message type]".to_string(),
+                    };
+                    // Strip reply fallback from body if this is a reply
+                    let body = if reply_to_event_id.is_some() {
+                        strip_reply_fallback(&body)
+                    } else {
+                        body
+                    };
+                    messages.push(crate::app::DisplayMessage {
+                        sender: original.sender.to_string(),
+                        content: crate::app::MessageContent::Text(body),
+                        timestamp: original.origin_server_ts.as_secs().into(),
+                        event_id: Some(original.event_id.to_string()),
+                        reply_to_sender: None,
+                        reply_to_body: None,
+                        reactions: Vec::new(),
+                        send_error: None,
+                        notice: matches!(original.content.msgtype, MessageType::Notice(_)),
+                        reply_to_event_id_raw: reply_to_event_id,
+                    });
+                }
+            }
+            Ok(_) => {} // state events, reactions, etc — skip
+            Err(e) => {
+                // Likely an encrypted message that couldn't be decrypted
+                info!("Failed to deserialize event: {}", e);
+                messages.push(crate::app::DisplayMessage {
+                    sender: "".to_string(),
+                    content: crate::app::MessageContent::Text("[encrypted message — unable to decrypt]".to_string()),
+                    timestamp: 0,
+                    event_id: None,
+                    reply_to_sender: None,
+                    reply_to_body: None,
+                    reactions: Vec::new(),
+                    send_error: None,
+                    notice: false,
+                    reply_to_event_id_raw: None,
+                });
+            }
+        }
+    }
+    messages
+}
+
+/// Forward the sync events the UI cares about from `client` to `tx`
+fn register_event_handlers(
+    client: &Client,
+    account_id: String,
+    tx: mpsc::UnboundedSender<MatrixEvent>,
+) {
+    // Register message handler
+    let tx_msg = tx.clone();
+    let aid_msg = account_id.clone();
+    client.add_event_handler(
+        move |event: OriginalSyncRoomMessageEvent, room: Room| {
+            let tx = tx_msg.clone();
+            let account_id = aid_msg.clone();
+            async move {
+                let reply_to_event_id = match &event.content.relates_to {
+                    Some(Relation::Reply { in_reply_to }) => {
+                        Some(in_reply_to.event_id.to_string())
+                    }
+                    _ => None,
+                };
+                if let Some(Relation::Thread(thread)) = &event.content.relates_to {
+                    let _ = tx.send(MatrixEvent::ThreadReply {
+                        account_id,
+                        room_id: room.room_id().to_owned(),
+                        thread_root: thread.event_id.to_string(),
+                        event_id: event.event_id.to_string(),
+                        sender: event.sender.clone(),
+                    });
+                }
+                // Handle image messages separately
+                if let MessageType::Image(ref img) = event.content.msgtype {
+                    let _ = tx.send(MatrixEvent::ImageMessage {
+                        room_id: room.room_id().to_owned(),
+                        sender: event.sender.clone(),
+                        timestamp: event.origin_server_ts.as_secs().into(),
+                        event_id: event.event_id.to_string(),
+                        body: img.filename().to_string(),
+                        source: img.source.clone(),
+                        reply_to_event_id,
+                    });
+                    let _ = tx.send(MatrixEvent::RoomsUpdated);
+                    return;
+                }
+                // Handle file/video/audio messages separately
+                match &event.content.msgtype {
+                    MessageType::File(f) => {
+                        let _ = tx.send(MatrixEvent::FileMessage {
+                            room_id: room.room_id().to_owned(),
+                            sender: event.sender.clone(),
+                            timestamp: event.origin_server_ts.as_secs().into(),
+                            event_id: event.event_id.to_string(),
+                            body: f.filename().to_string(),
+                            source: f.source.clone(),
+                            media_type: crate::app::FileKind::File,
+                            reply_to_event_id,
+                        });
+                        let _ = tx.send(MatrixEvent::RoomsUpdated);
+                        return;
+                    }
+                    MessageType::Video(v) => {
+                        let _ = tx.send(MatrixEvent::FileMessage {
+                            room_id: room.room_id().to_owned(),
+                            sender: event.sender.clone(),
+                            timestamp: event.origin_server_ts.as_secs().into(),
+                            event_id: event.event_id.to_string(),
+                            body: v.filename().to_string(),
+                            source: v.source.clone(),
+                            media_type: crate::app::FileKind::Video,
+                            reply_to_event_id,
+                        });
+                        let _ = tx.send(MatrixEvent::RoomsUpdated);
+                        return;
+                    }
+                    MessageType::Audio(a) => {
+                        let _ = tx.send(MatrixEvent::FileMessage {
+                            room_id: room.room_id().to_owned(),
+                            sender: event.sender.clone(),
+                            timestamp: event.origin_server_ts.as_secs().into(),
+                            event_id: event.event_id.to_string(),
+                            body: a.filename().to_string(),
+                            source: a.source.clone(),
+                            media_type: crate::app::FileKind::Audio,
+                            reply_to_event_id,
+                        });
+                        let _ = tx.send(MatrixEvent::RoomsUpdated);
+                        return;
+                    }
+                    _ => {}
+                }
+                let body = match &event.content.msgtype {
+                    MessageType::Text(text) => text.body.clone(),
+                    MessageType::Notice(n) => n.body.clone(),
+                    MessageType::Emote(e) => format!("* {}", e.body),
+                    _ => "[unsupported message type]".to_string(),
+                };
+                // Strip reply fallback from body if this is a reply
+                let body = if reply_to_event_id.is_some() {
+                    strip_reply_fallback(&body)
+                } else {
+                    body
+                };
+                let _ = tx.send(MatrixEvent::Message {
+                    room_id: room.room_id().to_owned(),
+                    sender: event.sender.clone(),
+                    body,
+                    timestamp: event
+                        .origin_server_ts
+                        .as_secs()
+                        .into(),
+                    event_id: event.event_id.to_string(),
+                    reply_to_event_id,
+                    notice: matches!(event.content.msgtype, MessageType::Notice(_)),
+                });
+                let _ = tx.send(MatrixEvent::RoomsUpdated);
+            }
+        },
+    );
+
+    // Register typing indicator handler
+    let tx_typing = tx.clone();
+    client.add_event_handler(
+        move |event: SyncEphemeralRoomEvent<TypingEventContent>, room: Room| {
+            let tx = tx_typing.clone();
+            async move {
+                let mut users = Vec::new();
+                for uid in event.content.user_ids {
+                    let name = match room.get_member_no_sync(&uid).await {
+                        Ok(Some(member)) => member.display_name().map(|n| n.to_string()),
+                        _ => None,
+                    };
+                    let name = name.unwrap_or_else(|| uid.localpart().to_string());
+                    users.push((uid, name));
+                }
+                let _ = tx.send(MatrixEvent::Typing {
+                    room_id: room.room_id().to_owned(),
+                    users,
+                });
+            }
+        },
+    );
+
+    // Register power levels handler, so badges and redact rights follow changes
+    let tx_power = tx.clone();
+    let aid_power = account_id.clone();
+    client.add_event_handler(
+        move |_event: OriginalSyncRoomPowerLevelsEvent, room: Room| {
+            let tx = tx_power.clone();
+            let account_id = aid_power.clone();
+            async move {
+                let _ = tx.send(MatrixEvent::PowerLevelsChanged {
+                    account_id,
+                    room_id: room.room_id().to_owned(),
+                });
+            }
+        },
+    );
+
+    // Register reaction handler
+    let tx_react = tx.clone();
+    client.add_event_handler(
+        move |event: OriginalSyncReactionEvent, room: Room| {
+            let tx = tx_react.clone();
+            async move {
+                let _ = tx.send(MatrixEvent::Reaction {
+                    room_id: room.room_id().to_owned(),
+                    event_id: event.content.relates_to.event_id.to_string(),
+                    key: event.content.relates_to.key,
+                });
+            }
+        },
+    );
+
+    // Register read receipt handler, keeping only our own receipts
+    let tx_receipt = tx.clone();
+    let aid_receipt = account_id.clone();
+    client.add_event_handler(
+        move |event: SyncEphemeralRoomEvent<ReceiptEventContent>, room: Room| {
+            let tx = tx_receipt.clone();
+            let aid = aid_receipt.clone();
+            async move {
+                for (event_id, receipts) in event.content.iter() {
+                    let ours = [ReceiptType::Read, ReceiptType::ReadPrivate]
+                        .iter()
+                        .filter_map(|t| receipts.get(t))
+                        .find_map(|users| {
+                            users.iter().find(|(u, _)| u.as_str() == aid).map(|(_, r)| r)
+                        });
+                    if let Some(receipt) = ours {
+                        let _ = tx.send(MatrixEvent::OwnReadReceipt {
+                            room_id: room.room_id().to_owned(),
+                            event_id: event_id.to_string(),
+                            thread: receipt.thread.clone(),
+                        });
+                    }
+                }
+            }
+        },
+    );
+
+    // Register membership handler: join requests for moderators as they
+    // arrive, and our own kicks / bans
+    let tx_knock = tx.clone();
+    let aid_member = account_id.clone();
+    client.add_event_handler(
+        move |event: OriginalSyncRoomMemberEvent, room: Room| {
+            let tx = tx_knock.clone();
+            let account_id = aid_member.clone();
+            async move {
+                let own_id = room.own_user_id();
+                let removed = matches!(
+                    event.content.membership,
+                    MembershipState::Leave | MembershipState::Ban
+                );
+                if removed && *event.state_key == *own_id && *event.sender != *own_id {
+                    let _ = tx.send(MatrixEvent::RemovedFromRoom {
+                        account_id,
+                        room_id: room.room_id().to_owned(),
+                        by: event.sender.to_string(),
+                        reason: event.content.reason.clone(),
+                        banned: event.content.membership == MembershipState::Ban,
+                    });
+                    return;
+                }
+                if event.content.membership != MembershipState::Knock {
+                    return;
+                }
+                if !room.can_user_invite(room.own_user_id()).await.unwrap_or(false) {
+                    return;
+                }
+                let _ = tx.send(MatrixEvent::Knock {
+                    room_id: room.room_id().to_owned(),
+                    user_id: event.state_key.to_string(),
+                    reason: event.content.reason.clone(),
+                });
+            }
+        },
+    );
+
+    // Register presence handler for status messages
+    let tx_presence = tx.clone();
+    client.add_event_handler(move |event: PresenceEvent| {
+        let tx = tx_presence.clone();
+        async move {
+            let _ = tx.send(MatrixEvent::Presence {
+                user_id: event.sender.to_string(),
+                status_msg: event.content.status_msg.filter(|s| !s.is_empty()),
+            });
+        }
+    });
+
+    // Register incoming verification request handler
+    let tx_verify = tx.clone();
+    let aid_verify = account_id.clone();
+    client.add_event_handler(
+        move |event: matrix_sdk::ruma::events::key::verification::request::ToDeviceKeyVerificationRequestEvent| {
+            let tx = tx_verify.clone();
+            let aid = aid_verify.clone();
+            async move {
+                let _ = tx.send(MatrixEvent::VerificationIncoming {
+                    account_id: aid,
+                    user_id: event.sender.to_string(),
+                    flow_id: event.content.transaction_id.to_string(),
+                });
+            }
+        },
+    );
+}
+
+/// Seconds since the Unix epoch
+fn unix_now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+/// Report devices that newly appear on our own account after the initial sync,
+/// so an unexpected login is noticed quickly
+async fn watch_new_devices(client: Client, account_id: String, tx: mpsc::UnboundedSender<MatrixEvent>) {
+    let Some(own_id) = client.user_id().map(|u| u.to_owned()) else {
+        return;
+    };
+    let encryption = client.encryption();
+    let mut known: HashSet<String> = match encryption.get_user_devices(&own_id).await {
+        Ok(devices) => devices.devices().map(|d| d.device_id().to_string()).collect(),
+        Err(e) => {
+            info!("Device list unavailable for {}: {}", account_id, e);
+            return;
+        }
+    };
+    let Ok(stream) = encryption.devices_stream().await else {
+        return;
+    };
+    futures_util::pin_mut!(stream);
+    while let Some(updates) = stream.next().await {
+        let Some(devices) = updates.new.get(&own_id) else {
+            continue;
+        };
+        for (device_id, device) in devices {
+            if known.insert(device_id.to_string()) {
+                let _ = tx.send(MatrixEvent::NewDevice {
+                    account_id: account_id.clone(),
+                    device_id: device_id.to_string(),
+                    display_name: device.display_name().map(|n| n.to_string()),
+                });
+            }
+        }
+    }
+}
+
 /// Events pushed from Matrix sync to the UI
 #[derive(Debug, Clone)]
 pub enum MatrixEvent {
@@ -74,10 +546,12 @@ pub enum MatrixEvent {
         timestamp: u64,
         event_id: String,
         reply_to_event_id: Option<String>,
+        notice: bool,
     },
     Typing {
         room_id: OwnedRoomId,
-        user_ids: Vec<OwnedUserId>,
+        /// Everyone typing, with their display name (localpart if unset)
+        users: Vec<(OwnedUserId, String)>,
     },
     Reaction {
         room_id: OwnedRoomId,
@@ -112,6 +586,86 @@ pub enum MatrixEvent {
     SasDone {
         flow_id: String,
     },
+    /// The original of a reply, fetched from the server because it wasn't loaded locally
+    ReplyContext {
+        room_id: OwnedRoomId,
+        event_id: String,
+        sender: String,
+        body: String,
+    },
+    /// A user's presence status message changed (None when cleared)
+    Presence {
+        user_id: String,
+        status_msg: Option<String>,
+    },
+    /// One of our own clients (possibly this one) moved its read receipt in a room
+    OwnReadReceipt {
+        room_id: OwnedRoomId,
+        event_id: String,
+        thread: ReceiptThread,
+    },
+    /// Someone asked to join a room we can invite to
+    Knock {
+        room_id: OwnedRoomId,
+        user_id: String,
+        reason: Option<String>,
+    },
+    /// We were kicked or banned from a room
+    RemovedFromRoom {
+        account_id: String,
+        room_id: OwnedRoomId,
+        by: String,
+        reason: Option<String>,
+        banned: bool,
+    },
+    /// A message was posted in a thread (MSC3440); also delivered as a normal message
+    ThreadReply {
+        account_id: String,
+        room_id: OwnedRoomId,
+        thread_root: String,
+        event_id: String,
+        sender: OwnedUserId,
+    },
+    /// A `/broadcast` send finished; `error` is None if it went out
+    BroadcastSent {
+        room_name: String,
+        error: Option<String>,
+    },
+    /// One of the messages queued by "Delete All by Sender" was tried: `redacted` is its
+    /// event ID if it went, and `done` of `total` are through so far
+    SenderRedaction {
+        room_id: OwnedRoomId,
+        sender: String,
+        redacted: Option<String>,
+        deleted: usize,
+        done: usize,
+        total: usize,
+        last_error: Option<String>,
+    },
+    /// Whether a room is published in the room directory, for Room Info and the editor
+    DirectoryVisibility {
+        room_id: OwnedRoomId,
+        published: bool,
+    },
+    /// A room's power levels changed
+    PowerLevelsChanged {
+        account_id: String,
+        room_id: OwnedRoomId,
+    },
+    /// Results of the Settings > Diagnostics checks
+    Diagnostics(Vec<crate::doctor::Check>),
+    /// Joining the rooms of an imported favorites list finished: IDs of the rooms
+    /// joined, and names of the ones that couldn't be
+    FavoritesJoined {
+        joined: Vec<String>,
+        failed: Vec<String>,
+    },
+    /// A device we haven't seen before appeared on one of our own accounts
+    NewDevice {
+        account_id: String,
+        device_id: String,
+        display_name: Option<String>,
+    },
     SasCancelled {
         flow_id: String,
         reason: String,
@@ -143,6 +697,8 @@ pub struct RoomInfo {
     pub id: OwnedRoomId,
     pub name: String,
     pub is_dm: bool,
+    /// The other person in a DM
+    pub dm_user: Option<String>,
     pub unread: u64,
     pub account_id: String,
 }
@@ -155,6 +711,79 @@ pub struct RoomDetails {
     pub member_count: u64,
     pub encryption: String,
     pub room_id: String,
+    /// Listed in the homeserver's public room directory; None until fetched
+    pub published: Option<bool>,
+}
+
+/// A joined room member for the member list overlay
+#[derive(Debug, Clone)]
+pub struct MemberInfo {
+    pub user_id: String,
+    pub display_name: Option<String>,
+}
+
+/// A pending request to join a room (membership = knock)
+#[derive(Debug, Clone)]
+pub struct KnockRequest {
+    pub user_id: String,
+    pub display_name: Option<String>,
+    pub reason: Option<String>,
+}
+
+/// One of the devices our messages to an encrypted room are encrypted for
+#[derive(Debug, Clone)]
+pub struct RoomDevice {
+    pub user_id: String,
+    pub device_id: String,
+    pub display_name: Option<String>,
+    pub verified: bool,
+}
+
+/// The parts of a room's power levels MatrixTUI acts on
+#[derive(Debug, Clone)]
+pub struct RoomPower {
+    /// Users with a non-default power level
+    pub users: HashMap<String, i64>,
+    pub users_default: i64,
+    /// Level needed to redact other people's events; the [mod] badge starts here
+    pub redact: i64,
+    /// Level needed to change the power levels themselves; the [admin] badge starts here
+    pub admin: i64,
+}
+
+impl Default for RoomPower {
+    /// Spec defaults, used until the room's power levels are known
+    fn default() -> Self {
+        Self {
+            users: HashMap::new(),
+            users_default: 0,
+            redact: 50,
+            admin: 100,
+        }
+    }
+}
+
+impl RoomPower {
+    pub fn level(&self, user_id: &str) -> i64 {
+        self.users
+            .get(user_id)
+            .copied()
+            .unwrap_or(self.users_default)
+    }
+
+    /// Badge for a user whose level is above the room's default
+    pub fn badge(&self, user_id: &str) -> Option<&'static str> {
+        let level = self.level(user_id);
+        if level <= self.users_default {
+            None
+        } else if level >= self.admin {
+            Some("admin")
+        } else if level >= self.redact {
+            Some("mod")
+        } else {
+            None
+        }
+    }
 }
 
 /// A single logged-in Matrix account
@@ -166,14 +795,21 @@ pub struct Account {
     pub syncing: bool,
     pub sync_complete: bool,
     sync_handle: Option<JoinHandle<()>>,
+    /// Unix seconds of the last sync response, for the stalled-sync watchdog
+    last_sync: Arc<AtomicU64>,
+    /// Guest session with an in-memory store: not saved, signed out on quit
+    pub ephemeral: bool,
+    /// Event handlers are on the client already, from an earlier `start_sync`
+    handlers_registered: bool,
 }
 
 impl Account {
-    /// Login with username and password
+    /// Login with username and password; with `memory` the store lives in memory only
     pub async fn login(
         homeserver: &str,
         username: &str,
         password: &str,
+        memory: bool,
     ) -> Result<(Self, SavedAccount)> {
         let url = normalize_homeserver(homeserver);
         // Normalize to @user:server format so db path matches restore()
@@ -182,15 +818,15 @@ impl Account {
         } else {
             format!("@{}:{}", username, homeserver)
         };
-        let db_path = session_db_path(&normalized_id, homeserver);
-        std::fs::create_dir_all(&db_path)?;
-
-        let client = Client::builder()
+        let mut builder = Client::builder()
             .homeserver_url(&url)
-            .sqlite_store(&db_path, None)
-            .with_encryption_settings(e2ee_settings())
-            .build()
-            .await?;
+            .with_encryption_settings(e2ee_settings());
+        if !memory {
+            let db_path = session_db_path(&normalized_id, homeserver);
+            std::fs::create_dir_all(&db_path)?;
+            builder = builder.sqlite_store(&db_path, None);
+        }
+        let client = builder.build().await?;
 
         let response = client
             .matrix_auth()
@@ -204,6 +840,8 @@ impl Account {
             user_id: user_id.clone(),
             access_token: response.access_token,
             device_id: response.device_id.to_string(),
+            disabled: false,
+            identity_server_token: None,
         };
 
         let account = Self {
@@ -214,6 +852,9 @@ impl Account {
             syncing: false,
             sync_complete: false,
             sync_handle: None,
+            handlers_registered: false,
+            last_sync: Arc::new(AtomicU64::new(unix_now())),
+            ephemeral: memory,
         };
 
         Ok((account, saved))
@@ -252,6 +893,9 @@ impl Account {
             syncing: false,
             sync_complete: false,
             sync_handle: None,
+            handlers_registered: false,
+            last_sync: Arc::new(AtomicU64::new(unix_now())),
+            ephemeral: false,
         })
     }
 
@@ -263,156 +907,17 @@ impl Account {
         self.syncing = true;
         let client = self.client.clone();
         let account_id = self.user_id.clone();
+        let last_sync = self.last_sync.clone();
+        last_sync.store(unix_now(), Ordering::Relaxed);
+        // Handlers live on the client, so a restarted sync keeps the first set
+        if !self.handlers_registered {
+            register_event_handlers(&client, account_id.clone(), tx.clone());
+            self.handlers_registered = true;
+        }
 
         let handle = tokio::spawn(async move {
             info!("Starting sync for {}", account_id);
 
-            // Register message handler
-            let tx_msg = tx.clone();
-            client.add_event_handler(
-                move |event: OriginalSyncRoomMessageEvent, room: Room| {
-                    let tx = tx_msg.clone();
-                    async move {
-                        let reply_to_event_id = match &event.content.relates_to {
-                            Some(Relation::Reply { in_reply_to }) => {
-                                Some(in_reply_to.event_id.to_string())
-                            }
-                            _ => None,
-                        };
-                        // Handle image messages separately
-                        if let MessageType::Image(ref img) = event.content.msgtype {
-                            let _ = tx.send(MatrixEvent::ImageMessage {
-                                room_id: room.room_id().to_owned(),
-                                sender: event.sender.clone(),
-                                timestamp: event.origin_server_ts.as_secs().into(),
-                                event_id: event.event_id.to_string(),
-                                body: img.filename().to_string(),
-                                source: img.source.clone(),
-                                reply_to_event_id,
-                            });
-                            let _ = tx.send(MatrixEvent::RoomsUpdated);
-                            return;
-                        }
-                        // Handle file/video/audio messages separately
-                        match &event.content.msgtype {
-                            MessageType::File(f) => {
-                                let _ = tx.send(MatrixEvent::FileMessage {
-                                    room_id: room.room_id().to_owned(),
-                                    sender: event.sender.clone(),
-                                    timestamp: event.origin_server_ts.as_secs().into(),
-                                    event_id: event.event_id.to_string(),
-                                    body: f.filename().to_string(),
-                                    source: f.source.clone(),
-                                    media_type: crate::app::FileKind::File,
-                                    reply_to_event_id,
-                                });
-                                let _ = tx.send(MatrixEvent::RoomsUpdated);
-                                return;
-                            }
-                            MessageType::Video(v) => {
-                                let _ = tx.send(MatrixEvent::FileMessage {
-                                    room_id: room.room_id().to_owned(),
-                                    sender: event.sender.clone(),
-                                    timestamp: event.origin_server_ts.as_secs().into(),
-                                    event_id: event.event_id.to_string(),
-                                    body: v.filename().to_string(),
-                                    source: v.source.clone(),
-                                    media_type: crate::app::FileKind::Video,
-                                    reply_to_event_id,
-                                });
-                                let _ = tx.send(MatrixEvent::RoomsUpdated);
-                                return;
-                            }
-                            MessageType::Audio(a) => {
-                                let _ = tx.send(MatrixEvent::FileMessage {
-                                    room_id: room.room_id().to_owned(),
-                                    sender: event.sender.clone(),
-                                    timestamp: event.origin_server_ts.as_secs().into(),
-                                    event_id: event.event_id.to_string(),
-                                    body: a.filename().to_string(),
-                                    source: a.source.clone(),
-                                    media_type: crate::app::FileKind::Audio,
-                                    reply_to_event_id,
-                                });
-                                let _ = tx.send(MatrixEvent::RoomsUpdated);
-                                return;
-                            }
-                            _ => {}
-                        }
-                        let body = match &event.content.msgtype {
-                            MessageType::Text(text) => text.body.clone(),
-                            MessageType::Notice(n) => n.body.clone(),
-                            MessageType::Emote(e) => format!("* {}", e.body),
-                            _ => "[unsupported message type]".to_string(),
-                        };
-                        // Strip reply fallback from body if this is a reply
-                        let body = if reply_to_event_id.is_some() {
-                            strip_reply_fallback(&body)
-                        } else {
-                            body
-                        };
-                        let _ = tx.send(MatrixEvent::Message {
-                            room_id: room.room_id().to_owned(),
-                            sender: event.sender.clone(),
-                            body,
-                            timestamp: event
-                                .origin_server_ts
-                                .as_secs()
-                                .into(),
-                            event_id: event.event_id.to_string(),
-                            reply_to_event_id,
-                        });
-                        let _ = tx.send(MatrixEvent::RoomsUpdated);
-                    }
-                },
-            );
-
-            // Register typing indicator handler
-            let tx_typing = tx.clone();
-            client.add_event_handler(
-                move |event: SyncEphemeralRoomEvent<TypingEventContent>, room: Room| {
-                    let tx = tx_typing.clone();
-                    async move {
-                        let _ = tx.send(MatrixEvent::Typing {
-                            room_id: room.room_id().to_owned(),
-                            user_ids: event.content.user_ids,
-                        });
-                    }
-                },
-            );
-
-            // Register reaction handler
-            let tx_react = tx.clone();
-            client.add_event_handler(
-                move |event: OriginalSyncReactionEvent, room: Room| {
-                    let tx = tx_react.clone();
-                    async move {
-                        let _ = tx.send(MatrixEvent::Reaction {
-                            room_id: room.room_id().to_owned(),
-                            event_id: event.content.relates_to.event_id.to_string(),
-                            key: event.content.relates_to.key,
-                        });
-                    }
-                },
-            );
-
-            // Register incoming verification request handler
-            let tx_verify = tx.clone();
-            let aid_verify = account_id.clone();
-            client.add_event_handler(
-                move |event: matrix_sdk::ruma::events::key::verification::request::ToDeviceKeyVerificationRequestEvent| {
-                    let tx = tx_verify.clone();
-                    let aid = aid_verify.clone();
-                    async move {
-                        let _ = tx.send(MatrixEvent::VerificationIncoming {
-                            account_id: aid,
-                            user_id: event.sender.to_string(),
-                            flow_id: event.content.transaction_id.to_string(),
-                        });
-                    }
-                },
-            );
-
             // Initial sync
             let settings = SyncSettings::default();
             match client.sync_once(settings.clone()).await {
@@ -430,12 +935,23 @@ impl Account {
                 }
             }
 
-            // Continuous sync
-            let _ = client.sync(settings).await;
+            // Continuous sync, watching our own device list alongside it. Every response
+            // (even an empty long-poll) stamps `last_sync` so a hung sync can be spotted.
+            let watch = watch_new_devices(client.clone(), account_id.clone(), tx.clone());
+            let sync = client.sync_with_callback(settings, move |_| {
+                last_sync.store(unix_now(), Ordering::Relaxed);
+                async { LoopCtrl::Continue }
+            });
+            let _ = tokio::join!(sync, watch);
         });
         self.sync_handle = Some(handle);
     }
 
+    /// Seconds since the sync loop last got a response from the server
+    pub fn secs_since_sync(&self) -> u64 {
+        unix_now().saturating_sub(self.last_sync.load(Ordering::Relaxed))
+    }
+
     /// Stop the background sync task
     pub fn stop_sync(&mut self) {
         if let Some(handle) = self.sync_handle.take() {
@@ -444,6 +960,21 @@ impl Account {
         self.syncing = false;
     }
 
+    /// Sign this session out on the server (revoking its token and device) and
+    /// delete its local store, which is useless without the device
+    pub async fn logout(&mut self) -> Result<()> {
+        self.stop_sync();
+        self.client.matrix_auth().logout().await?;
+        if self.ephemeral {
+            return Ok(());
+        }
+        let db_path = session_db_path(&self.user_id, &self.homeserver);
+        if let Err(e) = std::fs::remove_dir_all(&db_path) {
+            warn!("Failed to delete store for {}: {}", self.user_id, e);
+        }
+        Ok(())
+    }
+
     /// Get joined rooms as RoomInfo
     pub async fn rooms(&self) -> Vec<RoomInfo> {
         let mut result = Vec::new();
@@ -453,10 +984,16 @@ impl Account {
                 .map(|n| n.to_string())
                 .unwrap_or_else(|| room.room_id().to_string());
             let is_dm = room.is_direct().await.unwrap_or(false);
+            let dm_user = if is_dm {
+                room.heroes().first().map(|h| h.user_id.to_string())
+            } else {
+                None
+            };
             result.push(RoomInfo {
                 id: room.room_id().to_owned(),
                 name,
                 is_dm,
+                dm_user,
                 unread: room.num_unread_notifications().into(),
                 account_id: self.user_id.clone(),
             });
@@ -498,123 +1035,8 @@ impl Account {
             response.chunk.len(),
             response.end
         );
-        let mut messages = Vec::new();
-
-        for timeline_event in &response.chunk {
-            match timeline_event.raw().deserialize() {
-                Ok(AnySyncTimelineEvent::MessageLike(
-                    AnySyncMessageLikeEvent::RoomMessage(SyncRoomMessageEvent::Original(original)),
-                )) => {
-                    let reply_to_event_id = match &original.content.relates_to {
-                        Some(Relation::Reply { in_reply_to }) => {
-                            Some(in_reply_to.event_id.to_string())
-                        }
-                        _ => None,
-                    };
-                    // Handle image messages with full metadata
-                    if let MessageType::Image(ref img) = original.content.msgtype {
-                        messages.push(crate::app::DisplayMessage {
-                            sender: original.sender.to_string(),
-                            content: crate::app::MessageContent::Image {
-                                body: img.filename().to_string(),
-                                source: img.source.clone(),
-                                protocol: None,
-                                loading: false,
-                            },
-                            timestamp: original.origin_server_ts.as_secs().into(),
-                            event_id: Some(original.event_id.to_string()),
-                            reply_to_sender: None,
-                            reply_to_body: None,
-                            reactions: Vec::new(),
-                            reply_to_event_id_raw: reply_to_event_id,
-                        });
-                    } else if let MessageType::File(ref f) = original.content.msgtype {
-                        messages.push(crate::app::DisplayMessage {
-                            sender: original.sender.to_string(),
-                            content: crate::app::MessageContent::File {
-                                body: f.filename().to_string(),
-                                source: f.source.clone(),
-                                media_type: crate::app::FileKind::File,
-                            },
-                            timestamp: original.origin_server_ts.as_secs().into(),
-                            event_id: Some(original.event_id.to_string()),
-                            reply_to_sender: None,
-                            reply_to_body: None,
-                            reactions: Vec::new(),
-                            reply_to_event_id_raw: reply_to_event_id,
-                        });
-                    } else if let MessageType::Video(ref v) = original.content.msgtype {
-                        messages.push(crate::app::DisplayMessage {
-                            sender: original.sender.to_string(),
-                            content: crate::app::MessageContent::File {
-                                body: v.filename().to_string(),
-                                source: v.source.clone(),
-                                media_type: crate::app::FileKind::Video,
-                            },
-                            timestamp: original.origin_server_ts.as_secs().into(),
-                            event_id: Some(original.event_id.to_string()),
-                            reply_to_sender: None,
-                            reply_to_body: None,
-                            reactions: Vec::new(),
-                            reply_to_event_id_raw: reply_to_event_id,
-                        });
-                    } else if let MessageType::Audio(ref a) = original.content.msgtype {
-                        messages.push(crate::app::DisplayMessage {
-                            sender: original.sender.to_string(),
-                            content: crate::app::MessageContent::File {
-                                body: a.filename().to_string(),
-                                source: a.source.clone(),
-                                media_type: crate::app::FileKind::Audio,
-                            },
-                            timestamp: original.origin_server_ts.as_secs().into(),
-                            event_id: Some(original.event_id.to_string()),
-                            reply_to_sender: None,
-                            reply_to_body: None,
-                            reactions: Vec::new(),
-                            reply_to_event_id_raw: reply_to_event_id,
-                        });
-                    } else {
-                        let body = match &original.content.msgtype {
-                            MessageType::Text(text) => text.body.clone(),
-                            MessageType::Notice(n) => n.body.clone(),
-                            MessageType::Emote(e) => format!("* {}", e.body),
-                            _ => "[unsupported message type]".to_string(),
-                        };
-                        // Strip reply fallback from body if this is a reply
-                        let body = if reply_to_event_id.is_some() {
-                            strip_reply_fallback(&body)
-                        } else {
-                            body
-                        };
-                        messages.push(crate::app::DisplayMessage {
-                            sender: original.sender.to_string(),
-                            content: crate::app::MessageContent::Text(body),
-                            timestamp: original.origin_server_ts.as_secs().into(),
-                            event_id: Some(original.event_id.to_string()),
-                            reply_to_sender: None,
-                            reply_to_body: None,
-                            reactions: Vec::new(),
-                            reply_to_event_id_raw: reply_to_event_id,
-                        });
-                    }
-                }
-                Ok(_) => {} // state events, reactions, etc — skip
-                Err(e) => {
-                    // Likely an encrypted message that couldn't be decrypted
-                    info!("Failed to deserialize event: {}", e);
-                    messages.push(crate::app::DisplayMessage {
-                        sender: "".to_string(),
-                        content: crate::app::MessageContent::Text("[encrypted message — unable to decrypt]".to_string()),
-                        timestamp: 0,
-                        event_id: None,
-                        reply_to_sender: None,
-                        reply_to_body: None,
-                        reactions: Vec::new(),
-                        reply_to_event_id_raw: None,
-                    });
-                }
-            }
-        }
+        let mut messages =
+            history_to_display(response.chunk.iter().map(|e| e.raw().deserialize()));
 
         // Messages come newest-first from backward pagination, reverse for chronological
         messages.reverse();
@@ -627,6 +1049,39 @@ impl Account {
         Ok((messages, response.end))
     }
 
+    /// Resolve a room to peek at (`#alias` or `!id`) without joining it
+    pub async fn resolve_peek_target(&self, id_or_alias: &str) -> Result<OwnedRoomId> {
+        if id_or_alias.starts_with('#') {
+            let alias: OwnedRoomAliasId = id_or_alias.parse()?;
+            let response = self.client.resolve_room_alias(&alias).await?;
+            Ok(response.room_id)
+        } else {
+            Ok(id_or_alias.parse()?)
+        }
+    }
+
+    /// History of a room we haven't joined; only works for world-readable rooms
+    pub async fn peek_history(
+        &self,
+        room_id: &OwnedRoomId,
+        from: Option<&str>,
+        limit: u32,
+    ) -> Result<(Vec<crate::app::DisplayMessage>, Option<String>)> {
+        let mut request = get_message_events::v3::Request::backward(room_id.clone());
+        request.from = from.map(str::to_owned);
+        request.limit = UInt::from(limit);
+        let response = self.client.send(request).await?;
+        // Peeked events carry a room_id but otherwise match the sync shape
+        let mut messages = history_to_display(
+            response
+                .chunk
+                .iter()
+                .map(|raw| serde_json::from_str::<AnySyncTimelineEvent>(raw.json().get())),
+        );
+        messages.reverse();
+        Ok((messages, response.end))
+    }
+
     /// Fetch recent message history for a room (convenience wrapper)
     pub async fn fetch_history(
         &self,
@@ -638,15 +1093,28 @@ impl Account {
     }
 
     /// Send a text message to a room
-    pub async fn send_message(&self, room_id: &OwnedRoomId, body: &str) -> Result<()> {
+    pub async fn send_message(
+        &self,
+        room_id: &OwnedRoomId,
+        body: &str,
+        markdown: bool,
+    ) -> Result<()> {
+        info!("Sending to {} via {}", room_id, self.user_id);
+        send_text(&self.client, room_id, body, markdown).await?;
+        info!("Send OK");
+        Ok(())
+    }
+
+    /// Send text as a fenced code block, with a `<pre><code>` formatted body so
+    /// clients keep its whitespace
+    pub async fn send_code_block(&self, room_id: &OwnedRoomId, code: &str) -> Result<()> {
         let room = self
             .client
             .get_room(room_id)
             .ok_or_else(|| anyhow::anyhow!("Room not found for {}", self.user_id))?;
-        info!("Sending to {} via {}", room_id, self.user_id);
-        let content = RoomMessageEventContent::text_plain(body);
+        let content =
+            RoomMessageEventContent::text_html(code_block_body(code), code_block_html(code));
         room.send(content).await?;
-        info!("Send OK");
         Ok(())
     }
 
@@ -764,6 +1232,292 @@ impl Account {
         Ok(())
     }
 
+    /// Invite someone by email through the account's identity server
+    pub async fn invite_by_email(
+        &self,
+        room_id: &OwnedRoomId,
+        email: &str,
+        identity_server: &str,
+        identity_token: &str,
+    ) -> Result<()> {
+        let room = self
+            .client
+            .get_room(room_id)
+            .ok_or_else(|| anyhow::anyhow!("Room not found"))?;
+        let id_server = identity_server
+            .trim_start_matches("https://")
+            .trim_start_matches("http://")
+            .trim_end_matches('/');
+        let invite = Invite3pidInit {
+            id_server: id_server.to_string(),
+            id_access_token: identity_token.to_string(),
+            medium: Medium::Email,
+            address: email.to_string(),
+        };
+        room.invite_user_by_3pid(invite.into()).await?;
+        Ok(())
+    }
+
+    /// Set (or clear, with None) this account's presence status message
+    pub async fn set_status_message(&self, status: Option<&str>) -> Result<()> {
+        let user_id = self
+            .client
+            .user_id()
+            .ok_or_else(|| anyhow::anyhow!("Not logged in"))?
+            .to_owned();
+        let mut request = set_presence::v3::Request::new(user_id, PresenceState::Online);
+        request.status_msg = status.map(str::to_string);
+        self.client.send(request).await?;
+        Ok(())
+    }
+
+    /// Emails and phone numbers on the account, as (medium, address)
+    pub async fn threepids(&self) -> Result<Vec<(Medium, String)>> {
+        let response = self.client.account().get_3pids().await?;
+        Ok(response
+            .threepids
+            .into_iter()
+            .map(|t| (t.medium, t.address))
+            .collect())
+    }
+
+    /// Ask the homeserver to email a validation link for a new address
+    pub async fn request_email_token(&self, email: &str) -> Result<ThreepidRequest> {
+        let client_secret = ClientSecret::new();
+        let response = self
+            .client
+            .account()
+            .request_3pid_email_token(&client_secret, email, UInt::from(1u32))
+            .await?;
+        Ok(ThreepidRequest {
+            client_secret,
+            sid: response.sid,
+            submit_url: None,
+            auth_session: None,
+        })
+    }
+
+    /// Ask the homeserver to text a validation code; `country` is a two-letter code like "GB"
+    pub async fn request_phone_token(&self, country: &str, number: &str) -> Result<ThreepidRequest> {
+        let client_secret = ClientSecret::new();
+        let response = self
+            .client
+            .account()
+            .request_3pid_msisdn_token(&client_secret, country, number, UInt::from(1u32))
+            .await?;
+        Ok(ThreepidRequest {
+            client_secret,
+            sid: response.sid,
+            submit_url: response.submit_url,
+            auth_session: None,
+        })
+    }
+
+    /// Submit the SMS code for a phone number
+    pub async fn submit_phone_code(&self, request: &ThreepidRequest, code: &str) -> Result<()> {
+        let url = request
+            .submit_url
+            .as_deref()
+            .ok_or_else(|| anyhow::anyhow!("Homeserver gave no URL to submit the code to"))?;
+        let body = serde_json::json!({
+            "sid": request.sid.as_str(),
+            "client_secret": request.client_secret.as_str(),
+            "token": code,
+        });
+        self.client
+            .http_client()
+            .post(url)
+            .header("Content-Type", "application/json")
+            .body(body.to_string())
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+
+    /// Add a validated email/phone to the account. Returns Ok(false) when the server
+    /// wants the account password first; call again with it.
+    pub async fn add_threepid(
+        &self,
+        request: &mut ThreepidRequest,
+        password: Option<&str>,
+    ) -> Result<bool> {
+        let auth = match password {
+            Some(password) => {
+                let user_id = self
+                    .client
+                    .user_id()
+                    .ok_or_else(|| anyhow::anyhow!("Not logged in"))?;
+                let mut auth = Password::new(
+                    UserIdentifier::UserIdOrLocalpart(user_id.to_string()),
+                    password.to_string(),
+                );
+                auth.session = request.auth_session.clone();
+                Some(AuthData::Password(auth))
+            }
+            None => None,
+        };
+        match self
+            .client
+            .account()
+            .add_3pid(&request.client_secret, &request.sid, auth)
+            .await
+        {
+            Ok(_) => Ok(true),
+            Err(e) => match e.as_uiaa_response() {
+                Some(info) if password.is_none() => {
+                    request.auth_session = info.session.clone();
+                    Ok(false)
+                }
+                _ => Err(e.into()),
+            },
+        }
+    }
+
+    /// Remove an email/phone from the account
+    pub async fn remove_threepid(&self, medium: Medium, address: &str) -> Result<()> {
+        self.client.account().delete_3pid(address, medium, None).await?;
+        Ok(())
+    }
+
+    /// The identity server set in account data (`m.identity_server`), if any
+    pub async fn get_identity_server(&self) -> Result<Option<String>> {
+        let raw = self
+            .client
+            .account()
+            .fetch_account_data(GlobalAccountDataEventType::from("m.identity_server"))
+            .await?;
+        let Some(raw) = raw else {
+            return Ok(None);
+        };
+        let content: serde_json::Value = serde_json::from_str(raw.json().get())?;
+        Ok(content
+            .get("base_url")
+            .and_then(|v| v.as_str())
+            .filter(|s| !s.is_empty())
+            .map(str::to_string))
+    }
+
+    /// Fetch an identity server's terms of service as (policy name, URL) pairs
+    pub async fn identity_server_terms(&self, base_url: &str) -> Result<Vec<(String, String)>> {
+        let base = normalize_homeserver(base_url.trim_end_matches('/'));
+        let url = format!("{}/_matrix/identity/v2/terms", base);
+        let text = self
+            .client
+            .http_client()
+            .get(url)
+            .send()
+            .await?
+            .error_for_status()?
+            .text()
+            .await?;
+        let body: serde_json::Value = serde_json::from_str(&text)?;
+        let mut terms = Vec::new();
+        if let Some(policies) = body.get("policies").and_then(|p| p.as_object()) {
+            for policy in policies.values() {
+                // Each policy is {"version": .., "<lang>": {"name", "url"}}; prefer English
+                let Some(langs) = policy.as_object() else {
+                    continue;
+                };
+                let doc = langs.get("en").or_else(|| {
+                    langs
+                        .iter()
+                        .find(|(k, v)| *k != "version" && v.is_object())
+                        .map(|(_, v)| v)
+                });
+                if let Some(doc) = doc {
+                    let name = doc.get("name").and_then(|v| v.as_str()).unwrap_or("Terms");
+                    if let Some(url) = doc.get("url").and_then(|v| v.as_str()) {
+                        terms.push((name.to_string(), url.to_string()));
+                    }
+                }
+            }
+        }
+        Ok(terms)
+    }
+
+    /// Register with an identity server, accept its terms and make it this account's
+    /// identity server. Returns the identity server access token.
+    pub async fn connect_identity_server(
+        &self,
+        base_url: &str,
+        accepted_terms: &[String],
+    ) -> Result<String> {
+        let base = normalize_homeserver(base_url.trim_end_matches('/'));
+        let user_id = self
+            .client
+            .user_id()
+            .ok_or_else(|| anyhow::anyhow!("Not logged in"))?
+            .to_owned();
+        let openid = self
+            .client
+            .send(request_openid_token::v3::Request::new(user_id))
+            .await?;
+        let http = self.client.http_client();
+
+        // Trade an OpenID token from the homeserver for an identity server token
+        let register = serde_json::json!({
+            "access_token": openid.access_token,
+            "token_type": "Bearer",
+            "matrix_server_name": openid.matrix_server_name.to_string(),
+            "expires_in": openid.expires_in.as_secs(),
+        });
+        let text = http
+            .post(format!("{}/_matrix/identity/v2/account/register", base))
+            .header("Content-Type", "application/json")
+            .body(register.to_string())
+            .send()
+            .await?
+            .error_for_status()?
+            .text()
+            .await?;
+        let token = serde_json::from_str::<serde_json::Value>(&text)?
+            .get("token")
+            .and_then(|t| t.as_str())
+            .ok_or_else(|| anyhow::anyhow!("Identity server returned no token"))?
+            .to_string();
+
+        if !accepted_terms.is_empty() {
+            let accept = serde_json::json!({ "user_accepts": accepted_terms });
+            http.post(format!("{}/_matrix/identity/v2/terms", base))
+                .header("Authorization", format!("Bearer {}", token))
+                .header("Content-Type", "application/json")
+                .body(accept.to_string())
+                .send()
+                .await?
+                .error_for_status()?;
+        }
+
+        self.set_identity_server(Some(&base)).await?;
+        Ok(token)
+    }
+
+    /// Set or clear `m.identity_server` in account data
+    pub async fn set_identity_server(&self, base_url: Option<&str>) -> Result<()> {
+        let content = serde_json::json!({ "base_url": base_url });
+        let raw = Raw::from_json(serde_json::value::to_raw_value(&content)?);
+        self.client
+            .account()
+            .set_account_data_raw(GlobalAccountDataEventType::from("m.identity_server"), raw)
+            .await?;
+        Ok(())
+    }
+
+    /// Join a room by ID or alias, returning the joined room's ID. A `?via=server`
+    /// suffix (as in matrix.to links) names servers to join through, which room IDs
+    /// on other homeservers need
+    pub async fn join_room(&self, target: &str) -> Result<OwnedRoomId> {
+        join_room(&self.client, target).await
+    }
+
+    /// A joined room's canonical alias, if it has one
+    pub fn room_alias(&self, room_id: &OwnedRoomId) -> Option<String> {
+        self.client
+            .get_room(room_id)?
+            .canonical_alias()
+            .map(|a| a.to_string())
+    }
+
     /// Leave a room
     pub async fn leave_room(&self, room_id: &OwnedRoomId) -> Result<()> {
         let room = self
@@ -785,6 +1539,16 @@ impl Account {
         Ok(())
     }
 
+    /// Forget a room we've already left or been removed from
+    pub async fn forget_left_room(&self, room_id: &OwnedRoomId) -> Result<()> {
+        let room = self
+            .client
+            .get_room(room_id)
+            .ok_or_else(|| anyhow::anyhow!("Room not found"))?;
+        room.forget().await?;
+        Ok(())
+    }
+
     /// Get room topic (from cached state)
     pub fn get_room_topic(&self, room_id: &OwnedRoomId) -> Option<String> {
         let room = self.client.get_room(room_id)?;
@@ -812,21 +1576,38 @@ impl Account {
         Ok(())
     }
 
-    /// Redact (delete) a message
-    pub async fn redact_message(
-        &self,
-        room_id: &OwnedRoomId,
-        event_id: &str,
-    ) -> Result<()> {
-        use matrix_sdk::ruma::OwnedEventId;
+    /// A room's power levels, from the cached room state
+    pub async fn room_power_levels(&self, room_id: &OwnedRoomId) -> RoomPower {
+        let Some(room) = self.client.get_room(room_id) else {
+            return RoomPower::default();
+        };
+        match room.power_levels().await {
+            Ok(levels) => RoomPower {
+                users: levels
+                    .users
+                    .iter()
+                    .map(|(user_id, level)| (user_id.to_string(), i64::from(*level)))
+                    .collect(),
+                users_default: i64::from(levels.users_default),
+                redact: i64::from(levels.redact),
+                admin: i64::from(
+                    levels
+                        .events
+                        .get(&TimelineEventType::RoomPowerLevels)
+                        .copied()
+                        .unwrap_or(levels.state_default),
+                ),
+            },
+            Err(e) => {
+                info!("No power levels for {}: {}", room_id, e);
+                RoomPower::default()
+            }
+        }
+    }
 
-        let room = self
-            .client
-            .get_room(room_id)
-            .ok_or_else(|| anyhow::anyhow!("Room not found"))?;
-        let eid: OwnedEventId = event_id.parse()?;
-        room.redact(&eid, None, None).await?;
-        Ok(())
+    /// Redact (delete) a message
+    pub async fn redact_message(&self, room_id: &OwnedRoomId, event_id: &str) -> Result<()> {
+        redact_event(&self.client, room_id, event_id).await
     }
 
     /// Send a reply to a message
@@ -836,6 +1617,7 @@ impl Account {
         body: &str,
         reply_to_event_id: &str,
         reply_to_sender: &str,
+        markdown: bool,
     ) -> Result<()> {
         let room = self
             .client
@@ -844,8 +1626,12 @@ impl Account {
         let reply_eid: OwnedEventId = reply_to_event_id.parse()?;
         let reply_uid: OwnedUserId = reply_to_sender.parse()?;
         let metadata = ReplyMetadata::new(&reply_eid, &reply_uid, None);
-        let content = RoomMessageEventContentWithoutRelation::text_plain(body)
-            .make_reply_to(metadata, ForwardThread::Yes, AddMentions::Yes);
+        let content = if markdown {
+            RoomMessageEventContentWithoutRelation::text_markdown(body)
+        } else {
+            RoomMessageEventContentWithoutRelation::text_plain(body)
+        };
+        let content = content.make_reply_to(metadata, ForwardThread::Yes, AddMentions::Yes);
         room.send(content).await?;
         Ok(())
     }
@@ -902,7 +1688,7 @@ impl Account {
         Ok(())
     }
 
-    /// Send a read receipt for a message
+    /// Send a read receipt for a message and move the fully-read marker to it
     pub async fn send_read_receipt(
         &self,
         room_id: &OwnedRoomId,
@@ -913,15 +1699,77 @@ impl Account {
             .get_room(room_id)
             .ok_or_else(|| anyhow::anyhow!("Room not found"))?;
         let eid: OwnedEventId = event_id.parse()?;
-        room.send_single_receipt(
-            create_receipt::v3::ReceiptType::Read,
-            ReceiptThread::Unthreaded,
-            eid,
-        )
-        .await?;
+        let receipts = Receipts::new()
+            .fully_read_marker(eid.clone())
+            .public_read_receipt(eid);
+        room.send_multiple_receipts(receipts).await?;
         Ok(())
     }
 
+    /// Send a read receipt scoped to a thread (MSC3771), leaving the main timeline's alone
+    pub async fn send_thread_read_receipt(
+        &self,
+        room_id: &OwnedRoomId,
+        thread_root: &str,
+        event_id: &str,
+    ) -> Result<()> {
+        let room = self
+            .client
+            .get_room(room_id)
+            .ok_or_else(|| anyhow::anyhow!("Room not found"))?;
+        let root: OwnedEventId = thread_root.parse()?;
+        let eid: OwnedEventId = event_id.parse()?;
+        room.send_single_receipt(CreateReceiptType::Read, ReceiptThread::Thread(root), eid)
+            .await?;
+        Ok(())
+    }
+
+    /// Mark a room read up to its newest event without loading its history
+    pub async fn mark_room_read(&self, room_id: &OwnedRoomId) -> Result<()> {
+        let room = self
+            .client
+            .get_room(room_id)
+            .ok_or_else(|| anyhow::anyhow!("Room not found"))?;
+        let mut options = MessagesOptions::backward();
+        options.limit = UInt::from(1u32);
+        let response = room.messages(options).await?;
+        let eid = response
+            .chunk
+            .first()
+            .and_then(|e| e.event_id())
+            .ok_or_else(|| anyhow::anyhow!("Room has no events"))?;
+        let receipts = Receipts::new()
+            .fully_read_marker(eid.clone())
+            .public_read_receipt(eid);
+        room.send_multiple_receipts(receipts).await?;
+        Ok(())
+    }
+
+    /// Get the room's fully-read marker (`m.fully_read`), shared by all of our clients
+    pub async fn get_fully_read(&self, room_id: &OwnedRoomId) -> Option<String> {
+        let room = self.client.get_room(room_id)?;
+        let raw = room
+            .account_data_static::<FullyReadEventContent>()
+            .await
+            .ok()??;
+        let event = raw.deserialize().ok()?;
+        Some(event.content.event_id.to_string())
+    }
+
+    /// Get the event our own read receipt (public, else private) points at in a room
+    pub async fn get_own_read_receipt(&self, room_id: &OwnedRoomId) -> Option<String> {
+        let room = self.client.get_room(room_id)?;
+        let user_id = self.client.user_id()?;
+        for receipt_type in [ReceiptType::Read, ReceiptType::ReadPrivate] {
+            if let Ok(Some((event_id, _))) = room
+                .load_user_receipt(receipt_type, ReceiptThread::Unthreaded, user_id)
+                .await
+            {
+                return Some(event_id.to_string());
+            }
+        }
+        None
+    }
 
     /// Get detailed room info
     pub fn get_room_details(&self, room_id: &OwnedRoomId) -> Option<RoomDetails> {
@@ -943,9 +1791,130 @@ impl Account {
             member_count,
             encryption,
             room_id: room.room_id().to_string(),
+            published: None,
         })
     }
 
+    /// Publish the room in (or remove it from) the homeserver's room directory
+    pub async fn set_directory_visibility(&self, room_id: &OwnedRoomId, published: bool) -> Result<()> {
+        let visibility = if published { Visibility::Public } else { Visibility::Private };
+        let request = set_room_visibility::v3::Request::new(room_id.clone(), visibility);
+        self.client.send(request).await?;
+        Ok(())
+    }
+
+    /// List joined members of a room, sorted by display name
+    pub async fn room_members(&self, room_id: &OwnedRoomId) -> Result<Vec<MemberInfo>> {
+        let room = self
+            .client
+            .get_room(room_id)
+            .ok_or_else(|| anyhow::anyhow!("Room not found"))?;
+        let mut members: Vec<MemberInfo> = room
+            .members(RoomMemberships::JOIN)
+            .await?
+            .iter()
+            .map(|m| MemberInfo {
+                user_id: m.user_id().to_string(),
+                display_name: m.display_name().map(|n| n.to_string()),
+            })
+            .collect();
+        members.sort_by_key(|m| {
+            m.display_name
+                .as_deref()
+                .unwrap_or(&m.user_id)
+                .trim_start_matches('@')
+                .to_lowercase()
+        });
+        Ok(members)
+    }
+
+    /// Pending knocks in a room; fails unless we're allowed to invite there
+    pub async fn room_knocks(&self, room_id: &OwnedRoomId) -> Result<Vec<KnockRequest>> {
+        let room = self
+            .client
+            .get_room(room_id)
+            .ok_or_else(|| anyhow::anyhow!("Room not found"))?;
+        if !room.can_user_invite(room.own_user_id()).await? {
+            anyhow::bail!("You don't have permission to let people in here");
+        }
+        let knocks = room
+            .members(RoomMemberships::KNOCK)
+            .await?
+            .iter()
+            .map(|m| KnockRequest {
+                user_id: m.user_id().to_string(),
+                display_name: m.display_name().map(|n| n.to_string()),
+                reason: m.event().original_content().and_then(|c| c.reason.clone()),
+            })
+            .collect();
+        Ok(knocks)
+    }
+
+    /// Accept a knock by inviting the user
+    pub async fn approve_knock(&self, room_id: &OwnedRoomId, user_id: &str) -> Result<()> {
+        let room = self
+            .client
+            .get_room(room_id)
+            .ok_or_else(|| anyhow::anyhow!("Room not found"))?;
+        let uid: OwnedUserId = user_id.parse()?;
+        room.invite_user_by_id(&uid).await?;
+        Ok(())
+    }
+
+    /// Refuse a knock; kicking a knocking user rejects the request
+    pub async fn deny_knock(&self, room_id: &OwnedRoomId, user_id: &str) -> Result<()> {
+        let room = self
+            .client
+            .get_room(room_id)
+            .ok_or_else(|| anyhow::anyhow!("Room not found"))?;
+        let uid: OwnedUserId = user_id.parse()?;
+        room.kick_user(&uid, None).await?;
+        Ok(())
+    }
+
+    /// Devices of the room's members that a message would be encrypted for, other
+    /// than this session. Empty for unencrypted rooms; reads the local crypto store only.
+    pub async fn room_devices(&self, room_id: &OwnedRoomId) -> Result<Vec<RoomDevice>> {
+        let room = self
+            .client
+            .get_room(room_id)
+            .ok_or_else(|| anyhow::anyhow!("Room not found"))?;
+        if !room.encryption_state().is_encrypted() {
+            return Ok(Vec::new());
+        }
+        let encryption = self.client.encryption();
+        let own_device = self.client.device_id();
+        let mut devices = Vec::new();
+        for member in room.members(RoomMemberships::ACTIVE).await? {
+            let user_devices = encryption.get_user_devices(member.user_id()).await?;
+            for device in user_devices.devices() {
+                if Some(device.device_id()) == own_device {
+                    continue;
+                }
+                devices.push(RoomDevice {
+                    user_id: member.user_id().to_string(),
+                    device_id: device.device_id().to_string(),
+                    display_name: device.display_name().map(|n| n.to_string()),
+                    verified: device.is_verified(),
+                });
+            }
+        }
+        Ok(devices)
+    }
+
+    /// Mark devices as locally trusted, so they no longer count as unverified
+    pub async fn trust_devices(&self, devices: &[RoomDevice]) -> Result<()> {
+        let encryption = self.client.encryption();
+        for d in devices {
+            let uid: OwnedUserId = d.user_id.parse()?;
+            let did: OwnedDeviceId = d.device_id.as_str().into();
+            if let Some(device) = encryption.get_device(&uid, &did).await? {
+                device.set_local_trust(LocalTrust::Verified).await?;
+            }
+        }
+        Ok(())
+    }
+
     /// Recover E2EE secrets using a recovery key (or passphrase)
     pub async fn recover_with_key(&self, recovery_key: &str) -> Result<()> {
         self.client
@@ -1217,6 +2186,149 @@ fn e2ee_settings() -> EncryptionSettings {
     }
 }
 
+/// An email or phone number being added to an account, between requesting the
+/// validation token and the final (possibly password-authenticated) add
+#[derive(Debug, Clone)]
+pub struct ThreepidRequest {
+    pub client_secret: OwnedClientSecret,
+    pub sid: OwnedSessionId,
+    /// Where to send an SMS code, for phone numbers validated by the homeserver
+    pub submit_url: Option<String>,
+    /// Interactive auth session, once the server has asked for a password
+    pub auth_session: Option<String>,
+}
+
+/// Fetch a single message from the server, returning (sender, body) for a reply quote
+pub async fn fetch_reply_original(
+    client: &Client,
+    room_id: &OwnedRoomId,
+    event_id: &str,
+) -> Result<(String, String)> {
+    let room = client
+        .get_room(room_id)
+        .ok_or_else(|| anyhow::anyhow!("Room not found"))?;
+    let eid: OwnedEventId = event_id.parse()?;
+    let event = room.event(&eid, None).await?;
+    match event.raw().deserialize()? {
+        AnySyncTimelineEvent::MessageLike(AnySyncMessageLikeEvent::RoomMessage(
+            SyncRoomMessageEvent::Original(original),
+        )) => {
+            let body = match &original.content.msgtype {
+                MessageType::Emote(e) => format!("* {}", e.body),
+                MessageType::Image(img) => format!("[image: {}]", img.filename()),
+                MessageType::File(f) => format!("[file: {}]", f.filename()),
+                MessageType::Video(v) => format!("[video: {}]", v.filename()),
+                MessageType::Audio(a) => format!("[audio: {}]", a.filename()),
+                other => other.body().to_string(),
+            };
+            let body = match original.content.relates_to {
+                Some(Relation::Reply { .. }) => strip_reply_fallback(&body),
+                _ => body,
+            };
+            Ok((original.sender.to_string(), body))
+        }
+        _ => Err(anyhow::anyhow!("Not a message event")),
+    }
+}
+
+/// Token for paging backwards from just before an event, via `/context`
+pub async fn token_before_event(
+    client: &Client,
+    room_id: &OwnedRoomId,
+    event_id: &str,
+) -> Result<Option<String>> {
+    let eid: OwnedEventId = event_id.parse()?;
+    let mut request = get_context::v3::Request::new(room_id.clone(), eid);
+    request.limit = UInt::from(0u32);
+    let response = client.send(request).await?;
+    Ok(response.start)
+}
+
+/// Send a text message through a client, so sends can run off the UI task
+pub async fn send_text(client: &Client, room_id: &OwnedRoomId, body: &str, markdown: bool) -> Result<()> {
+    let room = client
+        .get_room(room_id)
+        .ok_or_else(|| anyhow::anyhow!("Room not found"))?;
+    let content = if markdown {
+        RoomMessageEventContent::text_markdown(body)
+    } else {
+        RoomMessageEventContent::text_plain(body)
+    };
+    room.send(content).await?;
+    Ok(())
+}
+
+/// Whether a room is published in the homeserver's room directory, through a client
+/// so the lookup can run off the UI task
+pub async fn directory_visibility(client: &Client, room_id: &OwnedRoomId) -> Result<bool> {
+    let request = get_room_visibility::v3::Request::new(room_id.clone());
+    let response = client.send(request).await?;
+    Ok(response.visibility == Visibility::Public)
+}
+
+/// Fetch another user's presence status message through a client, so opening a
+/// DM doesn't wait on it
+pub async fn status_message(client: &Client, user_id: &str) -> Result<Option<String>> {
+    let user_id = <&UserId>::try_from(user_id)?.to_owned();
+    let response = client.send(get_presence::v3::Request::new(user_id)).await?;
+    Ok(response.status_msg.filter(|s| !s.is_empty()))
+}
+
+/// Redact an event through a client, so bulk deletes can run off the UI task
+pub async fn redact_event(client: &Client, room_id: &OwnedRoomId, event_id: &str) -> Result<()> {
+    let room = client
+        .get_room(room_id)
+        .ok_or_else(|| anyhow::anyhow!("Room not found"))?;
+    let eid: OwnedEventId = event_id.parse()?;
+    room.redact(&eid, None, None).await?;
+    Ok(())
+}
+
+/// Join a room through a client, so joins can run off the UI task. Takes the same
+/// targets as `Account::join_room`
+pub async fn join_room(client: &Client, target: &str) -> Result<OwnedRoomId> {
+    let (id_or_alias, query) = target.split_once('?').unwrap_or((target, ""));
+    let via: Vec<OwnedServerName> = query
+        .split('&')
+        .filter_map(|p| p.strip_prefix("via="))
+        .filter_map(|s| s.parse().ok())
+        .collect();
+    let id_or_alias: OwnedRoomOrAliasId = id_or_alias.parse()?;
+    let room = client.join_room_by_id_or_alias(&id_or_alias, &via).await?;
+    Ok(room.room_id().to_owned())
+}
+
+/// Open a saved account's session store without logging in, to check it isn't corrupt
+pub async fn check_session_store(saved: &SavedAccount) -> Result<()> {
+    let db_path = session_db_path(&saved.user_id, &saved.homeserver);
+    if !db_path.exists() {
+        anyhow::bail!("session store missing at {}", db_path.display());
+    }
+    Client::builder()
+        .homeserver_url(normalize_homeserver(&saved.homeserver))
+        .sqlite_store(&db_path, None)
+        .with_encryption_settings(e2ee_settings())
+        .build()
+        .await?;
+    Ok(())
+}
+
+/// Plain-text body for a code block message
+pub fn code_block_body(code: &str) -> String {
+    format!("```\n{}\n```", code.trim_end_matches('\n'))
+}
+
+/// HTML body for a code block message
+fn code_block_html(code: &str) -> String {
+    let escaped = code
+        .trim_end_matches('\n')
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;");
+    format!("<pre><code>{}</code></pre>", escaped)
+}
+
 fn normalize_homeserver(hs: &str) -> String {
     if hs.starts_with("http://") || hs.starts_with("https://") {
         hs.to_string()
@@ -1,20 +1,27 @@
 use anyhow::Result;
-use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers, MouseButton, MouseEvent, MouseEventKind};
 use matrix_sdk::encryption::verification::SasVerification;
 use matrix_sdk::ruma::OwnedRoomId;
+use matrix_sdk::ruma::events::receipt::ReceiptThread;
 use matrix_sdk::ruma::events::room::MediaSource;
+use matrix_sdk::ruma::thirdparty::Medium;
 use ratatui::prelude::*;
 use ratatui_image::picker::Picker;
 use ratatui_image::protocol::StatefulProtocol;
-use std::cell::Cell;
-use std::collections::HashMap;
+use std::borrow::Cow;
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::sync::{Arc, Mutex};
 use tokio::sync::mpsc;
-use tracing::{error, info};
+use tracing::{error, info, warn};
 
-use crate::account::{Account, MatrixEvent, RoomDetails, RoomInfo};
-use crate::config::Config;
+use crate::account::{
+    Account, KnockRequest, MatrixEvent, MemberInfo, RoomDetails, RoomDevice, RoomInfo, RoomPower,
+    ThreepidRequest,
+};
+use crate::config::{Config, FavoriteEntry, FavoritesList};
 use crate::event::{AppEvent, spawn_input_reader, spawn_matrix_bridge};
+use crate::perf::PerfStats;
 use crate::ui;
 
 /// How rooms (outside favorites) are sorted
@@ -57,6 +64,28 @@ impl RoomSortMode {
     }
 }
 
+/// What a mouse button does on a room or message (see `MouseConfig`)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MouseAction {
+    Select,
+    Open,
+    Reply,
+    React,
+    None,
+}
+
+impl MouseAction {
+    pub fn from_str(s: &str) -> Self {
+        match s {
+            "select" => Self::Select,
+            "open" => Self::Open,
+            "reply" => Self::Reply,
+            "react" => Self::React,
+            _ => Self::None,
+        }
+    }
+}
+
 /// Which panel has focus
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Focus {
@@ -83,6 +112,120 @@ pub enum Overlay {
     EmojiPicker,
     RoomInfo,
     FileConfirm,
+    CodePaste,
+    MemberList,
+    Diagnostics,
+    IdentityServer,
+    Threepids,
+    RoomBatch,
+    Knocks,
+    JoinFavorites,
+    TimelineFilters,
+    Confirm,
+    StatusHistory,
+    DeviceReview,
+    Broadcast,
+}
+
+/// Minimum gap between read receipts in the same room; the newest event is sent
+/// by a later tick, so busy rooms cost one request per interval
+const RECEIPT_INTERVAL: std::time::Duration = std::time::Duration::from_secs(3);
+
+/// How many past status messages the history overlay (`H`) keeps
+const STATUS_HISTORY_LEN: usize = 100;
+
+/// How far a room may grow past `max_messages_per_room` before it's trimmed back,
+/// so a busy room at the cap isn't trimmed on every message
+const TRIM_SLACK: usize = 50;
+
+/// A destructive action held by the confirm overlay until Enter (Esc backs out)
+#[derive(Debug, Clone)]
+pub enum PendingConfirm {
+    RemoveAccount(String),
+    LogOut(String),
+    ClearCache,
+    RedactFromSender {
+        room_id: OwnedRoomId,
+        sender: String,
+        event_ids: Vec<String>,
+    },
+    LeaveRoom(String),
+    DeleteRoom(String),
+    LeaveMarkedRooms(usize),
+    RemoveThreepid(String),
+}
+
+impl PendingConfirm {
+    /// Action name and what happens if it goes ahead
+    pub fn describe(&self) -> (String, Vec<&'static str>) {
+        match self {
+            Self::RemoveAccount(user_id) => (
+                format!("Remove {}?", user_id),
+                vec![
+                    "Stops syncing and forgets the saved access token.",
+                    "The session stays signed in on the server; use Log Out to revoke it.",
+                ],
+            ),
+            Self::LogOut(user_id) => (
+                format!("Log out {}?", user_id),
+                vec![
+                    "Signs this session out on the server, revoking its access token.",
+                    "Deletes its local E2EE keys: encrypted history needs key backup to read again.",
+                    "Removes the account from MatrixTUI.",
+                ],
+            ),
+            Self::ClearCache => (
+                "Clear cache?".to_string(),
+                vec![
+                    "Deletes every account's local store (E2EE keys and sync state).",
+                    "Sessions must be verified again to read encrypted history.",
+                    "Restart MatrixTUI afterwards.",
+                ],
+            ),
+            Self::RedactFromSender { sender, event_ids, .. } => (
+                format!("Delete {} messages from {}?", event_ids.len(), sender),
+                vec![
+                    "Redacts every loaded message from this sender in the room.",
+                    "Older messages that aren't loaded are left alone.",
+                    "Redactions can't be undone.",
+                ],
+            ),
+            Self::LeaveRoom(name) => (
+                format!("Leave {}?", name),
+                vec![
+                    "You'll need a new invite to rejoin an invite-only room.",
+                    "The room stays in the list until it's forgotten.",
+                ],
+            ),
+            Self::DeleteRoom(name) => (
+                format!("Delete {}?", name),
+                vec![
+                    "Leaves the room and forgets it on every device.",
+                    "Its history is gone from MatrixTUI; other members keep theirs.",
+                ],
+            ),
+            Self::LeaveMarkedRooms(count) => (
+                format!("Leave {} rooms?", count),
+                vec!["You'll need a new invite to rejoin invite-only rooms."],
+            ),
+            Self::RemoveThreepid(address) => (
+                format!("Remove {}?", address),
+                vec![
+                    "It can no longer be used to sign in or reset your password.",
+                    "Adding it back means validating it again.",
+                ],
+            ),
+        }
+    }
+}
+
+/// Step of the emails & phone numbers overlay
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ThreepidStep {
+    List,          // browse addresses; a/p to add, d to remove
+    EnterAddress,  // typing the new email, or country code and phone number
+    Validate,      // waiting for the email link to be clicked, or typing the SMS code
+    Password,      // the server wants the account password before adding
 }
 
 /// State of the SAS verification overlay
@@ -112,6 +255,8 @@ pub enum MessageContent {
         body: String, // filename / caption
         source: MediaSource,
         protocol: Option<Arc<Mutex<StatefulProtocol>>>,
+        /// Decoded image, kept so a font size change can re-encode it without a download
+        image: Option<Arc<image::DynamicImage>>,
         loading: bool,
     },
     File {
@@ -150,6 +295,131 @@ pub struct DisplayMessage {
     pub reply_to_body: Option<String>,
     pub reply_to_event_id_raw: Option<String>,
     pub reactions: Vec<(String, u16)>,
+    /// Set on a local echo whose send failed; offers Resend / Discard in the action menu
+    pub send_error: Option<String>,
+    /// Sent as m.notice (usually by bots)
+    pub notice: bool,
+}
+
+/// Slash commands offered by the composer's completion popup, with usage hints
+pub const SLASH_COMMANDS: &[(&str, &str)] = &[
+    ("markdown", "[on|off]  send as markdown in this room"),
+    ("notify", "[on|off]  desktop notifications for this room"),
+    ("images", "[on|off]  inline images in this room"),
+    ("timesort", "[on|off]  order history by timestamp"),
+    ("sound", "<dm|mention> [cmd|bell|off]  this room's alert"),
+    ("topic", "[text]  edit or set the room topic"),
+    ("status", "[text]  set or clear your status message"),
+    ("join", "<#alias|!id>  join a room"),
+    ("forget", "forget a room you were removed from"),
+    ("broadcast", "<message>  send to every marked room, confirming each"),
+    ("peek", "<#alias|link>  read a world-readable room"),
+    ("export-keymap", "[path]  save the keymap as a Markdown cheat sheet"),
+    ("export-settings", "[path]  save settings without tokens"),
+    ("import-settings", "<path>  load exported settings"),
+    ("export-favorites", "[path]  save favorites as a shareable list"),
+    ("import-favorites", "<path>  add favorites from a list"),
+];
+
+/// Completion popup over the composer for `@` users, `#` room aliases and `/` commands
+#[derive(Debug, Clone)]
+pub struct Completion {
+    /// Byte offset in `input` where the word being completed starts
+    pub start: usize,
+    /// (text to insert, hint shown beside it)
+    pub items: Vec<(String, String)>,
+    pub selected: usize,
+}
+
+/// A room we were kicked or banned from, kept open read-only until forgotten
+#[derive(Debug, Clone)]
+pub struct RemovedFrom {
+    pub room_name: String,
+    /// "You were banned by …: reason"
+    pub notice: String,
+}
+
+/// Something to post in the active room; every kind goes through `send_outgoing`
+#[derive(Debug, Clone)]
+pub enum Outgoing {
+    Text(String),
+    /// Reply body and the (event_id, sender, body) it answers, as in `replying_to`
+    Reply(String, (String, String, String)),
+    Code(String),
+    /// Path of a file to upload
+    File(String),
+}
+
+/// The first send to an encrypted room with unverified devices, held until the
+/// user reviews, trusts or accepts them
+#[derive(Debug, Clone)]
+pub struct DeviceReview {
+    pub room_id: OwnedRoomId,
+    pub devices: Vec<RoomDevice>,
+    pub outgoing: Outgoing,
+    /// Composer text to put back, exactly as typed, if the send is cancelled
+    pub typed: Option<String>,
+    /// Showing the unverified devices instead of just the summary
+    pub listing: bool,
+    pub scroll: usize,
+}
+
+impl DeviceReview {
+    pub fn unverified(&self) -> impl Iterator<Item = &RoomDevice> {
+        self.devices.iter().filter(|d| !d.verified)
+    }
+}
+
+/// One message going to the marked rooms, confirmed room by room
+#[derive(Debug, Clone)]
+pub struct Broadcast {
+    pub body: String,
+    pub targets: Vec<RoomInfo>,
+    /// Index of the target waiting for confirmation
+    pub next: usize,
+    /// That target's devices, if it's encrypted with unverified devices and this is
+    /// our first send there (see `DeviceReview`)
+    pub devices: Option<Vec<RoomDevice>>,
+    /// Rooms whose sends were spawned but haven't reported back
+    pub in_flight: Vec<String>,
+    pub sent: usize,
+    pub skipped: usize,
+    pub failed: Vec<String>,
+    /// Stopped with Esc; the marked rooms are kept for a retry
+    pub stopped: bool,
+}
+
+/// Replies in a thread that arrived since we last read it
+#[derive(Debug, Clone)]
+pub struct ThreadUnread {
+    /// Newest reply, which a threaded read receipt points at
+    pub latest_event_id: String,
+    pub count: usize,
+}
+
+/// Whether `name` appears in `body` as a whole word, ignoring case
+fn mentions_word(body: &str, name: &str) -> bool {
+    if name.is_empty() {
+        return false;
+    }
+    let body = body.to_lowercase();
+    let name = name.to_lowercase();
+    let is_word = |c: char| c.is_alphanumeric() || c == '_';
+    body.match_indices(&name).any(|(i, _)| {
+        let before = body[..i].chars().next_back();
+        let after = body[i + name.len()..].chars().next();
+        !before.is_some_and(is_word) && !after.is_some_and(is_word)
+    })
+}
+
+/// Shorten quoted text to `max_chars` characters, flattened onto one line
+pub fn reply_snippet(body: &str, max_chars: usize) -> String {
+    let flat = body.replace('\n', " ");
+    if flat.chars().count() > max_chars {
+        format!("{}...", flat.chars().take(max_chars).collect::<String>())
+    } else {
+        flat
+    }
 }
 
 impl DisplayMessage {
@@ -170,13 +440,33 @@ pub struct App {
     pub focus: Focus,
     pub overlay: Overlay,
     pub running: bool,
+    // Problems hit while shutting down, printed once the terminal is restored
+    pub shutdown_warnings: Vec<String>,
     pub picker: Picker,
+    /// Decoded account avatars for the accounts panel, keyed by user ID
+    pub account_avatars: HashMap<String, Arc<Mutex<StatefulProtocol>>>,
+    /// The avatars' decoded images, re-encoded when the font size changes
+    pub avatar_images: HashMap<String, Arc<image::DynamicImage>>,
 
     // Room state
     pub all_rooms: Vec<RoomInfo>,
     pub selected_room: usize,
     pub active_room: Option<OwnedRoomId>,
     pub active_account_id: Option<String>,
+    // Set while the active room is a read-only peek (the #alias or !id typed)
+    pub peeking: Option<String>,
+    /// Rooms we were kicked or banned from since starting
+    pub removed_from: HashMap<OwnedRoomId, RemovedFrom>,
+    // Confirm overlay: the pending action and the overlay to return to
+    pub pending_confirm: Option<PendingConfirm>,
+    confirm_return: Overlay,
+    // Timeline filter menu (`F` in chat)
+    pub filter_selected: usize,
+    filters_changed: bool,
+    // Rooms marked with Space for batch actions (`b`)
+    pub marked_rooms: HashSet<OwnedRoomId>,
+    pub room_batch_selected: usize,
+    pub broadcast: Option<Broadcast>,
 
     // Chat state
     pub messages: Vec<DisplayMessage>,
@@ -185,13 +475,20 @@ pub struct App {
     pending_echoes: Vec<String>,
     pub downloading_keys: bool,
     pub first_unread_index: Option<usize>,
+    /// Display names of others typing in the active room
     pub typing_users: Vec<String>,
-    pub replying_to: Option<(String, String, String)>, // (event_id, sender, body_snippet)
+    /// Ticks while someone is typing; drives the animated ellipsis
+    pub typing_tick: usize,
+    pub replying_to: Option<(String, String, String)>, // (event_id, sender, body)
 
     // Input state
     pub input: String,
     pub cursor_pos: usize,
     pub last_typing_sent: Option<std::time::Instant>,
+    pub completion: Option<Completion>,
+    // Held first message to an encrypted room, and rooms already checked this session
+    pub device_review: Option<DeviceReview>,
+    device_checked: HashSet<OwnedRoomId>,
 
     // Login form state
     pub login_homeserver: String,
@@ -206,11 +503,11 @@ pub struct App {
     pub switcher_selected: usize,
 
     // Settings overlay state
-    pub settings_selected: usize,          // 0=Accounts, 1=Theme
+    pub settings_selected: usize,          // 0=Accounts, 1=Theme, 2=Sort, 3=Clock, 4=Diagnostics, 5=Clear Cache
     pub settings_accounts_open: bool,
     pub settings_accounts_selected: usize, // 0=Add Account, 1..=N for accounts
     pub settings_account_action_open: bool,
-    pub settings_account_action_selected: usize, // 0=Reconnect, 1=Remove, 2=Edit Profile, 3=Verify Session
+    pub settings_account_action_selected: usize, // 0=Reconnect, 1=Remove, 2=Edit Profile, 3=Verify Session, 4=Disable, 5=Set as Default, 6=Identity Server, 7=Emails & Phones
     pub settings_verify_open: bool,
     pub settings_verify_selected: usize, // 0=Recovery Key, 1=Another Device
     pub settings_theme_open: bool,
@@ -233,6 +530,25 @@ pub struct App {
     pub profile_current_name: String,
     pub profile_current_avatar: String,
 
+    // Identity server overlay state
+    pub identity_account_idx: usize,
+    pub identity_url: String,
+    pub identity_current: Option<String>,
+    pub identity_terms: Option<Vec<(String, String)>>, // (name, url), fetched for identity_url
+    pub identity_error: Option<String>,
+    pub identity_busy: bool,
+
+    // Emails & phone numbers overlay state
+    pub threepid_account_idx: usize,
+    pub threepid_list: Vec<(Medium, String)>,
+    pub threepid_selected: usize,
+    pub threepid_step: ThreepidStep,
+    pub threepid_phone: bool, // adding a phone number rather than an email
+    pub threepid_input: String,
+    threepid_request: Option<ThreepidRequest>,
+    pub threepid_error: Option<String>,
+    pub threepid_busy: bool,
+
     // Room creator overlay state
     pub creator_name: String,
     pub creator_topic: String,
@@ -252,8 +568,7 @@ pub struct App {
     pub editor_focus: usize,
     pub editor_error: Option<String>,
     pub editor_busy: bool,
-    pub editor_confirm_leave: bool,
-    pub editor_confirm_delete: bool,
+    pub editor_published: Option<bool>, // room directory listing, None if unknown
     pub editor_room_id: Option<OwnedRoomId>,
     pub editor_account_id: Option<String>,
 
@@ -266,6 +581,38 @@ pub struct App {
     // Message selection state
     pub selected_message: Option<usize>,
 
+    // In-room message search
+    pub search_active: bool, // typing a query
+    pub search_query: String,
+
+    // Timeline filter: only show messages from this sender
+    pub sender_filter: Option<String>,
+
+    // Presence status messages by user ID, from sync and DM lookups
+    pub presence_status: HashMap<String, String>,
+
+    // Replies (by event_id) whose quoted original is shown in full
+    pub expanded_replies: HashSet<String>,
+    // Reply originals already requested from the server, so each is fetched once
+    reply_fetches: HashSet<String>,
+
+    // Diagnostics overlay (same checks as `mtui doctor`)
+    pub diagnostics: Vec<crate::doctor::Check>,
+
+    // Member list overlay state
+    pub members: Vec<MemberInfo>,
+    /// Power levels of the active room, for sender badges and redact permission
+    pub room_power: RoomPower,
+    pub member_filter: String,
+    pub member_selected: usize,
+
+    // Knock requests overlay state (active room)
+    pub knocks: Vec<KnockRequest>,
+    pub knock_selected: usize,
+
+    // Composer edit-last mode: event_id of the message being edited in the input box
+    pub composer_edit: Option<String>,
+
     // Message action overlay state
     pub message_action_selected: usize, // 0=Edit, 1=Delete
     pub message_editing: bool,
@@ -285,12 +632,36 @@ pub struct App {
 
     // Pagination tokens for loading older messages
     pub room_history_tokens: HashMap<OwnedRoomId, Option<String>>,
+    /// Rooms trimmed since their token was stored; it's refetched on the next page back
+    pub stale_history_tokens: HashSet<OwnedRoomId>,
 
     // Viewport size (messages that fit on screen), updated during draw
     pub chat_viewport_msgs: Cell<usize>,
 
+    // Mouse hit-testing: screen rows of rooms and messages, updated during draw
+    pub room_hits: RefCell<Vec<(Rect, usize)>>,
+    pub message_hits: RefCell<Vec<(Rect, usize)>>,
+    pub message_area: Cell<Rect>,
+    pub mouse_pos: Option<(u16, u16)>,
+    last_click: Option<(std::time::Instant, (u16, u16))>,
+
+    // Terminal window focus; receipts wait for focus and notifications only fire without it
+    pub terminal_focused: bool,
+    // Whether the chat panel was drawn last frame (not the case in single column room list)
+    pub chat_visible: Cell<bool>,
+    last_receipt: Option<String>,
+    // Room and time of the last receipt request, for RECEIPT_INTERVAL
+    last_receipt_at: Option<(OwnedRoomId, std::time::Instant)>,
+    // Where our own read receipt sits in the active room, as synced from any of our clients
+    pub own_read_receipt: Option<String>,
+    // Unread thread replies per room, keyed by thread root event ID
+    pub thread_unread: HashMap<OwnedRoomId, HashMap<String, ThreadUnread>>,
+
     // Help overlay scroll
     pub help_scroll: usize,
+    // `/` in the help overlay filters it
+    pub help_search: String,
+    pub help_searching: bool,
 
     // Emoji picker state
     pub emoji_picker_selected: usize,
@@ -304,12 +675,29 @@ pub struct App {
 
     // Status
     pub status_msg: String,
+    // When status_msg last changed; transient messages clear after config.status_ttl_secs
+    status_seen: String,
+    status_since: std::time::Instant,
+    // Persistent state (e.g. a sync error) shown again whenever a transient status expires
+    status_sticky: Option<String>,
+    // Past status messages, newest last, for the history overlay
+    pub status_history: VecDeque<(chrono::DateTime<chrono::Local>, String)>,
+    pub status_history_scroll: usize,
 
     // Selected account in account list
     pub selected_account: usize,
 
     // File drop state
     pub pending_file_drop: Option<String>,
+    // Multi-line paste waiting for "send as code block?"
+    pub pending_code_paste: Option<String>,
+    // Rooms from an imported favorites list that no account has joined yet
+    pub favorite_joins: Vec<FavoriteEntry>,
+    pub favorite_join_account: usize,
+    pub favorite_join_busy: bool,
+
+    // Frame and event timings, only collected with `--perf`
+    pub perf: Option<PerfStats>,
 
     // Channels
     matrix_tx: mpsc::UnboundedSender<MatrixEvent>,
@@ -328,11 +716,23 @@ impl App {
             focus: Focus::Rooms,
             overlay: Overlay::None,
             running: true,
+            shutdown_warnings: Vec::new(),
             picker,
+            account_avatars: HashMap::new(),
+            avatar_images: HashMap::new(),
             all_rooms: Vec::new(),
             selected_room: 0,
             active_room: None,
             active_account_id: None,
+            peeking: None,
+            removed_from: HashMap::new(),
+            pending_confirm: None,
+            confirm_return: Overlay::None,
+            filter_selected: 0,
+            filters_changed: false,
+            marked_rooms: HashSet::new(),
+            room_batch_selected: 0,
+            broadcast: None,
             messages: Vec::new(),
             scroll_offset: 0,
             room_messages: HashMap::new(),
@@ -340,9 +740,13 @@ impl App {
             downloading_keys: false,
             first_unread_index: None,
             typing_users: Vec::new(),
+            typing_tick: 0,
             replying_to: None,
             input: String::new(),
             cursor_pos: 0,
+            completion: None,
+            device_review: None,
+            device_checked: HashSet::new(),
             last_typing_sent: None,
             login_homeserver: String::new(),
             login_username: String::new(),
@@ -374,6 +778,21 @@ impl App {
             profile_account_idx: 0,
             profile_current_name: String::new(),
             profile_current_avatar: String::new(),
+            identity_account_idx: 0,
+            identity_url: String::new(),
+            identity_current: None,
+            identity_terms: None,
+            identity_error: None,
+            identity_busy: false,
+            threepid_account_idx: 0,
+            threepid_list: Vec::new(),
+            threepid_selected: 0,
+            threepid_step: ThreepidStep::List,
+            threepid_phone: false,
+            threepid_input: String::new(),
+            threepid_request: None,
+            threepid_error: None,
+            threepid_busy: false,
             creator_name: String::new(),
             creator_topic: String::new(),
             creator_visibility: 0,
@@ -390,8 +809,7 @@ impl App {
             editor_focus: 0,
             editor_error: None,
             editor_busy: false,
-            editor_confirm_leave: false,
-            editor_confirm_delete: false,
+            editor_published: None,
             editor_room_id: None,
             editor_account_id: None,
             recovery_key: String::new(),
@@ -399,7 +817,21 @@ impl App {
             recovery_busy: false,
             recovery_account_idx: 0,
             selected_message: None,
+            search_active: false,
+            search_query: String::new(),
+            sender_filter: None,
+            presence_status: HashMap::new(),
+            expanded_replies: HashSet::new(),
+            reply_fetches: HashSet::new(),
             message_action_selected: 0,
+            diagnostics: Vec::new(),
+            members: Vec::new(),
+            room_power: RoomPower::default(),
+            member_filter: String::new(),
+            member_selected: 0,
+            knocks: Vec::new(),
+            knock_selected: 0,
+            composer_edit: None,
             message_editing: false,
             message_edit_text: String::new(),
             message_edit_cursor: 0,
@@ -413,15 +845,39 @@ impl App {
             sas_user_id: None,
             sas_handle: None,
             help_scroll: 0,
+            help_search: String::new(),
+            help_searching: false,
             emoji_picker_selected: 0,
             emoji_picker_event_id: None,
             room_details: None,
             room_history_tokens: HashMap::new(),
+            stale_history_tokens: HashSet::new(),
             chat_viewport_msgs: Cell::new(10),
+            room_hits: RefCell::new(Vec::new()),
+            message_hits: RefCell::new(Vec::new()),
+            message_area: Cell::new(Rect::default()),
+            mouse_pos: None,
+            last_click: None,
+            terminal_focused: true,
+            chat_visible: Cell::new(false),
+            last_receipt: None,
+            last_receipt_at: None,
+            own_read_receipt: None,
+            thread_unread: HashMap::new(),
             theme,
             status_msg: "No accounts — press 'a' to add one".to_string(),
+            status_seen: String::new(),
+            status_since: std::time::Instant::now(),
+            status_history: VecDeque::new(),
+            status_history_scroll: 0,
+            status_sticky: None,
             selected_account: 0,
             pending_file_drop: None,
+            pending_code_paste: None,
+            favorite_joins: Vec::new(),
+            favorite_join_account: 0,
+            favorite_join_busy: false,
+            perf: None,
             matrix_tx,
             matrix_rx: Some(matrix_rx),
             app_tx: None,
@@ -432,7 +888,7 @@ impl App {
     pub async fn restore_sessions(&mut self) {
         let saved = self.config.accounts.clone();
         let mut errors = Vec::new();
-        for sa in &saved {
+        for sa in saved.iter().filter(|a| !a.disabled) {
             self.status_msg = format!("Restoring {}...", sa.user_id);
             match Account::restore(sa).await {
                 Ok(mut account) => {
@@ -450,7 +906,16 @@ impl App {
         if !errors.is_empty() {
             self.status_msg = format!("Restore failed: {}", errors.join("; "));
         } else if !self.accounts.is_empty() {
-            self.status_msg = format!("{} account(s) connected", self.accounts.len());
+            let disabled = self.config.disabled_accounts().len();
+            if disabled > 0 {
+                self.status_msg = format!(
+                    "{} account(s) connected, {} disabled",
+                    self.accounts.len(),
+                    disabled
+                );
+            } else {
+                self.status_msg = format!("{} account(s) connected", self.accounts.len());
+            }
         }
     }
 
@@ -468,26 +933,271 @@ impl App {
         }
 
         while self.running {
+            let draw_started = std::time::Instant::now();
             terminal.draw(|f| ui::draw(f, self))?;
+            if let Some(perf) = &mut self.perf {
+                perf.frame = draw_started.elapsed();
+                perf.record("draw", perf.frame);
+                if self.chat_visible.get() {
+                    perf.record("draw_chat_panel", perf.chat_panel.get());
+                }
+            }
 
             if let Some(event) = app_rx.recv().await {
+                let event_started = std::time::Instant::now();
+                let event_kind = event.kind();
                 match event {
                     AppEvent::Key(key) => self.handle_key(key).await,
-                    AppEvent::Resize => {} // ratatui handles this on next draw
+                    AppEvent::Mouse(mouse) => self.handle_mouse(mouse).await,
+                    // ratatui relayouts on the next draw; images need the new cell size
+                    AppEvent::Resize => self.handle_resize(),
                     AppEvent::Matrix(mev) => self.handle_matrix_event(mev).await,
                     AppEvent::Paste(data) => self.handle_paste(data).await,
-                    AppEvent::ImageReady { room_id, event_id, protocol } => {
-                        self.handle_image_ready(&room_id, &event_id, protocol);
+                    AppEvent::ImageReady {
+                        room_id,
+                        event_id,
+                        protocol,
+                        image,
+                    } => {
+                        self.handle_image_ready(&room_id, &event_id, protocol, image);
+                    }
+                    AppEvent::AvatarReady {
+                        user_id,
+                        protocol,
+                        image,
+                    } => {
+                        self.account_avatars.insert(user_id.clone(), protocol);
+                        self.avatar_images.insert(user_id, image);
                     }
-                    AppEvent::Tick => {}
+                    AppEvent::FocusChanged(focused) => {
+                        self.terminal_focused = focused;
+                        self.flush_read_receipt().await;
+                    }
+                    AppEvent::Tick => {
+                        if !self.typing_users.is_empty() {
+                            self.typing_tick = self.typing_tick.wrapping_add(1);
+                        }
+                        self.flush_read_receipt().await;
+                        self.expire_status();
+                        self.check_sync_watchdog().await;
+                    }
+                }
+                self.track_status();
+                if let Some(perf) = &mut self.perf {
+                    perf.event = event_started.elapsed();
+                    perf.event_kind = event_kind;
+                    perf.record(event_kind, perf.event);
                 }
             }
         }
 
+        self.status_msg = "Shutting down...".to_string();
+        terminal.draw(|f| ui::draw(f, self))?;
+        self.shutdown().await;
+
         Ok(())
     }
 
+    /// Sync and send notifications without a terminal (`--daemon`) until `stop`
+    /// delivers something, which is returned once the syncs have stopped
+    pub async fn run_headless<T>(&mut self, mut stop: mpsc::UnboundedReceiver<Option<T>>) -> Option<T> {
+        let (app_tx, mut app_rx) = mpsc::unbounded_channel();
+        self.app_tx = Some(app_tx.clone());
+        // Nobody is looking: notify for everything and hold back read receipts
+        self.terminal_focused = false;
+
+        if let Some(mrx) = self.matrix_rx.take() {
+            spawn_matrix_bridge(mrx, app_tx);
+        }
+
+        let mut tick = tokio::time::interval(std::time::Duration::from_secs(1));
+        let reason = loop {
+            tokio::select! {
+                reason = stop.recv() => break reason.flatten(),
+                event = app_rx.recv() => match event {
+                    Some(AppEvent::Matrix(mev)) => self.handle_matrix_event(mev).await,
+                    Some(_) => {}
+                    None => break None,
+                },
+                _ = tick.tick() => self.check_sync_watchdog().await,
+            }
+        };
+        self.shutdown().await;
+        reason
+    }
+
+    /// Finish outstanding work before exit: save the composer as a draft, clear
+    /// our typing notice, send the last read receipt, stop every sync loop and
+    /// sign out guest sessions
+    async fn shutdown(&mut self) {
+        let guest_room = self
+            .accounts
+            .iter()
+            .any(|a| a.ephemeral && self.active_account_id.as_deref() == Some(a.user_id.as_str()));
+        if let Some(room_id) = self.active_room.clone() {
+            if !self.input.trim().is_empty() && self.composer_edit.is_none() && !guest_room {
+                self.config.drafts.insert(room_id.to_string(), self.input.clone());
+                self.shutdown_warnings
+                    .push(format!("Unsent message in {} saved as a draft", room_id));
+            }
+            if self.last_typing_sent.is_some() {
+                if let Some(account) = self
+                    .accounts
+                    .iter()
+                    .find(|a| self.active_account_id.as_deref() == Some(a.user_id.as_str()))
+                {
+                    if let Some(room) = account.client.get_room(&room_id) {
+                        let notice = room.typing_notice(false);
+                        let _ = tokio::time::timeout(std::time::Duration::from_secs(2), notice).await;
+                    }
+                }
+            }
+        }
+        self.last_receipt_at = None;
+        self.flush_read_receipt().await;
+        // Spawned sends and joins die with the runtime, so say which ones may not
+        // have gone through
+        if let Some(b) = self.broadcast.as_ref().filter(|b| !b.in_flight.is_empty()) {
+            self.shutdown_warnings.push(format!(
+                "Broadcast may not have reached: {}",
+                b.in_flight.join(", ")
+            ));
+        }
+        if self.favorite_join_busy {
+            let rooms: Vec<&str> = self
+                .favorite_joins
+                .iter()
+                .map(|e| e.alias.as_deref().or(e.name.as_deref()).unwrap_or(&e.room_id))
+                .collect();
+            self.shutdown_warnings.push(format!(
+                "Favorite rooms may not have been joined: {}",
+                rooms.join(", ")
+            ));
+        }
+        for account in &mut self.accounts {
+            account.stop_sync();
+            if account.ephemeral {
+                if let Some(warning) = Self::sign_out_guest(account).await {
+                    self.shutdown_warnings.push(warning);
+                }
+            }
+        }
+        if let Err(e) = self.config.save() {
+            self.shutdown_warnings.push(format!("Failed to save config: {}", e));
+        }
+    }
+
+    /// Sign out a guest session, whose token nothing else can revoke once we drop
+    /// it; returns what went wrong, if anything
+    async fn sign_out_guest(account: &mut Account) -> Option<String> {
+        let logout = tokio::time::timeout(std::time::Duration::from_secs(5), account.logout());
+        match logout.await {
+            Ok(Ok(())) => None,
+            Ok(Err(e)) => Some(format!("Failed to sign out guest session {}: {}", account.user_id, e)),
+            Err(_) => Some(format!("Timed out signing out guest session {}", account.user_id)),
+        }
+    }
+
+    fn handle_help_key(&mut self, key: KeyEvent) {
+        if self.help_searching {
+            match key.code {
+                KeyCode::Esc => {
+                    self.help_searching = false;
+                    self.help_search.clear();
+                }
+                KeyCode::Enter => self.help_searching = false,
+                KeyCode::Backspace => {
+                    self.help_search.pop();
+                }
+                KeyCode::Char(c) => self.help_search.push(c),
+                _ => return,
+            }
+            self.help_scroll = 0;
+            return;
+        }
+        match key.code {
+            KeyCode::Esc if !self.help_search.is_empty() => {
+                self.help_search.clear();
+                self.help_scroll = 0;
+            }
+            KeyCode::Esc | KeyCode::Char('?') => {
+                self.overlay = Overlay::None;
+                self.help_scroll = 0;
+            }
+            KeyCode::Char('/') => self.help_searching = true,
+            KeyCode::Up | KeyCode::Char('k') => {
+                self.help_scroll = self.help_scroll.saturating_sub(1);
+            }
+            KeyCode::Down | KeyCode::Char('j') => {
+                self.help_scroll += 1;
+            }
+            _ => {}
+        }
+    }
+
+    /// Step the layout override: auto -> 1 -> 2 -> 3 columns -> auto
+    fn cycle_layout(&mut self) {
+        self.config.layout_columns = (self.config.layout_columns + 1) % 4;
+        if let Err(e) = self.config.save() {
+            error!("Failed to save config: {}", e);
+        }
+        self.status_msg = match self.config.layout_columns {
+            0 => format!(
+                "Layout: auto (3 columns from {}, 2 from {} wide)",
+                self.config.three_column_width, self.config.two_column_width
+            ),
+            1 => "Layout: 1 column".to_string(),
+            n => format!("Layout: {} columns", n),
+        };
+    }
+
+    /// Show a status that outlives transient messages until `clear_status_sticky`
+    fn set_status_sticky(&mut self, msg: String) {
+        self.status_msg = msg.clone();
+        self.status_sticky = Some(msg);
+    }
+
+    /// Resolve the persistent status if it starts with `prefix`
+    fn clear_status_sticky(&mut self, prefix: &str) {
+        if let Some(sticky) = self.status_sticky.take_if(|s| s.starts_with(prefix)) {
+            if self.status_msg == sticky {
+                self.status_msg.clear();
+            }
+        }
+    }
+
+    /// Restart the status timeout whenever status_msg is replaced
+    fn track_status(&mut self) {
+        if self.status_msg != self.status_seen {
+            self.status_seen = self.status_msg.clone();
+            self.status_since = std::time::Instant::now();
+            // A sticky status coming back after a transient one isn't news
+            let repeat = self.status_history.back().is_some_and(|(_, m)| *m == self.status_msg);
+            if !self.status_msg.is_empty() && !repeat {
+                if self.status_history.len() == STATUS_HISTORY_LEN {
+                    self.status_history.pop_front();
+                }
+                self.status_history
+                    .push_back((chrono::Local::now(), self.status_msg.clone()));
+            }
+        }
+    }
+
+    /// Drop a transient status once it has been shown for status_ttl_secs,
+    /// falling back to the persistent one if there is any
+    fn expire_status(&mut self) {
+        let ttl = self.config.status_ttl_secs;
+        if ttl == 0 || self.status_msg.is_empty() || self.status_sticky.as_ref() == Some(&self.status_msg) {
+            return;
+        }
+        if self.status_since.elapsed() >= std::time::Duration::from_secs(ttl) {
+            self.status_msg = self.status_sticky.clone().unwrap_or_default();
+        }
+    }
+
     fn open_settings(&mut self) {
+        // Settings is where new sessions get verified or removed
+        self.clear_status_sticky("\u{26a0} New session");
         self.overlay = Overlay::Settings;
         self.settings_selected = 0;
         self.settings_accounts_open = false;
@@ -512,7 +1222,10 @@ impl App {
                 return;
             }
             (KeyModifiers::CONTROL, KeyCode::Char('u')) => {
-                if self.overlay == Overlay::None && self.active_room.is_some() {
+                if self.overlay == Overlay::None
+                    && self.active_room.is_some()
+                    && !self.blocked_read_only()
+                {
                     self.open_file_picker().await;
                 }
                 return;
@@ -522,7 +1235,9 @@ impl App {
                     if let Some(ref room_id) = self.active_room {
                         if let Some(ref aid) = self.active_account_id {
                             if let Some(account) = self.accounts.iter().find(|a| &a.user_id == aid) {
+                                // Directory listing shows as unknown until the server answers
                                 self.room_details = account.get_room_details(room_id);
+                                self.request_directory_visibility(aid, room_id);
                                 self.overlay = Overlay::RoomInfo;
                             }
                         }
@@ -534,13 +1249,15 @@ impl App {
         }
 
         // Global shortcuts when no overlay is active and not typing
-        if self.overlay == Overlay::None && self.focus != Focus::Input {
+        if self.overlay == Overlay::None && self.focus != Focus::Input && !self.search_active {
+            // 'n' steps through search matches while a search is shown in the chat panel
+            let searching = self.focus == Focus::Chat && !self.search_query.is_empty();
             match key.code {
                 KeyCode::Char('s') => {
                     self.open_settings();
                     return;
                 }
-                KeyCode::Char('n') if !self.accounts.is_empty() => {
+                KeyCode::Char('n') if !self.accounts.is_empty() && !searching => {
                     self.open_room_creator();
                     return;
                 }
@@ -548,6 +1265,15 @@ impl App {
                     self.open_room_editor().await;
                     return;
                 }
+                KeyCode::Char('L') => {
+                    self.cycle_layout();
+                    return;
+                }
+                KeyCode::Char('H') => {
+                    self.status_history_scroll = 0;
+                    self.overlay = Overlay::StatusHistory;
+                    return;
+                }
                 _ => {}
             }
         }
@@ -555,24 +1281,13 @@ impl App {
         // Route to overlay or focused panel
         match self.overlay {
             Overlay::Login => self.handle_login_key(key).await,
-            Overlay::Help => {
-                match key.code {
-                    KeyCode::Esc | KeyCode::Char('?') => {
-                        self.overlay = Overlay::None;
-                        self.help_scroll = 0;
-                    }
-                    KeyCode::Up | KeyCode::Char('k') => {
-                        self.help_scroll = self.help_scroll.saturating_sub(1);
-                    }
-                    KeyCode::Down | KeyCode::Char('j') => {
-                        self.help_scroll += 1;
-                    }
-                    _ => {}
-                }
-            }
+            Overlay::Help => self.handle_help_key(key),
             Overlay::RoomSwitcher => self.handle_switcher_key(key).await,
             Overlay::Settings => self.handle_settings_key(key).await,
             Overlay::ProfileEditor => self.handle_profile_key(key).await,
+            Overlay::IdentityServer => self.handle_identity_key(key).await,
+            Overlay::Threepids => self.handle_threepid_key(key).await,
+            Overlay::RoomBatch => self.handle_room_batch_key(key).await,
             Overlay::RoomCreator => self.handle_creator_key(key).await,
             Overlay::RoomEditor => self.handle_editor_key(key).await,
             Overlay::Recovery => self.handle_recovery_key(key).await,
@@ -585,6 +1300,31 @@ impl App {
                 }
             }
             Overlay::FileConfirm => self.handle_file_confirm_key(key).await,
+            Overlay::JoinFavorites => self.handle_join_favorites_key(key).await,
+            Overlay::CodePaste => self.handle_code_paste_key(key).await,
+            Overlay::MemberList => self.handle_member_list_key(key).await,
+            Overlay::Knocks => self.handle_knocks_key(key).await,
+            Overlay::DeviceReview => self.handle_device_review_key(key).await,
+            Overlay::Broadcast => self.handle_broadcast_key(key).await,
+            Overlay::Confirm => self.handle_confirm_key(key).await,
+            Overlay::StatusHistory => match key.code {
+                KeyCode::Esc | KeyCode::Char('H') => self.overlay = Overlay::None,
+                KeyCode::Up => {
+                    self.status_history_scroll = self.status_history_scroll.saturating_sub(1);
+                }
+                KeyCode::Down => {
+                    if self.status_history_scroll + 1 < self.status_history.len() {
+                        self.status_history_scroll += 1;
+                    }
+                }
+                _ => {}
+            },
+            Overlay::TimelineFilters => self.handle_timeline_filters_key(key).await,
+            Overlay::Diagnostics => {
+                if key.code == KeyCode::Esc {
+                    self.overlay = Overlay::Settings;
+                }
+            }
             Overlay::None => match self.focus {
                 Focus::Accounts => self.handle_accounts_key(key),
                 Focus::Rooms => self.handle_rooms_key(key).await,
@@ -649,6 +1389,21 @@ impl App {
             (_, KeyCode::Char('f')) => {
                 self.toggle_favorite().await;
             }
+            (_, KeyCode::Char(' ')) => {
+                if let Some(room) = self.all_rooms.get(self.selected_room) {
+                    if !self.marked_rooms.remove(&room.id) {
+                        self.marked_rooms.insert(room.id.clone());
+                    }
+                    if self.selected_room + 1 < self.all_rooms.len() {
+                        self.selected_room += 1;
+                    }
+                }
+            }
+            (_, KeyCode::Char('b')) if !self.marked_rooms.is_empty() => {
+                self.room_batch_selected = 0;
+                self.overlay = Overlay::RoomBatch;
+            }
+            (_, KeyCode::Esc) => self.marked_rooms.clear(),
             (_, KeyCode::Char('a')) => {
                 self.overlay = Overlay::Login;
                 self.login_homeserver = "matrix.org".to_string();
@@ -676,83 +1431,407 @@ impl App {
         self.refresh_rooms().await;
     }
 
-    async fn reorder_favorite_up(&mut self) {
-        if self.selected_room == 0 || self.selected_room >= self.favorites_count {
-            return;
-        }
-        // Swap in config.favorites
-        let idx = self.selected_room;
-        self.config.favorites.swap(idx, idx - 1);
-        let _ = self.config.save();
-        self.selected_room -= 1;
-        self.refresh_rooms().await;
-    }
+    // --- Batch room actions ---
 
-    async fn reorder_favorite_down(&mut self) {
-        if self.selected_room + 1 >= self.favorites_count {
-            return;
-        }
-        let idx = self.selected_room;
-        self.config.favorites.swap(idx, idx + 1);
-        let _ = self.config.save();
-        self.selected_room += 1;
-        self.refresh_rooms().await;
+    /// Marked rooms in room list order
+    fn marked_room_list(&self) -> Vec<RoomInfo> {
+        self.all_rooms
+            .iter()
+            .filter(|r| self.marked_rooms.contains(&r.id))
+            .cloned()
+            .collect()
     }
 
-    // --- Room Creator ---
-
-    fn open_room_creator(&mut self) {
-        self.overlay = Overlay::RoomCreator;
-        self.creator_name.clear();
-        self.creator_topic.clear();
-        self.creator_visibility = 0;
-        self.creator_e2ee = true;
-        self.creator_federated = true;
-        self.creator_invite.clear();
-        self.creator_account_idx = self
-            .accounts
+    /// Batch actions for the marked rooms; toggles flip only if every marked room is already set
+    pub fn room_batch_labels(&self) -> Vec<&'static str> {
+        let marked = self.marked_room_list();
+        let all_fav = marked
             .iter()
-            .position(|a| Some(&a.user_id) == self.active_account_id.as_ref())
-            .unwrap_or(0);
-        self.creator_focus = 0;
-        self.creator_error = None;
-        self.creator_busy = false;
+            .all(|r| self.config.favorites.iter().any(|f| f == r.id.as_str()));
+        let all_muted = marked
+            .iter()
+            .all(|r| !self.config.room_settings(r.id.as_str()).notifications);
+        vec![
+            if all_fav { "Unfavorite" } else { "Favorite" },
+            if all_muted { "Unmute" } else { "Mute" },
+            "Mark as Read",
+            "Leave Rooms",
+        ]
     }
 
-    async fn handle_creator_key(&mut self, key: KeyEvent) {
-        if self.creator_busy {
-            return;
-        }
-        // Focus: 0=account, 1=name, 2=topic, 3=visibility, 4=e2ee, 5=federated, 6=invite
+    async fn handle_room_batch_key(&mut self, key: KeyEvent) {
+        let actions = self.room_batch_labels();
         match key.code {
-            KeyCode::Tab => {
-                self.creator_focus = (self.creator_focus + 1) % 7;
+            KeyCode::Up => {
+                self.room_batch_selected = self.room_batch_selected.saturating_sub(1);
             }
-            KeyCode::BackTab => {
-                self.creator_focus = if self.creator_focus == 0 { 6 } else { self.creator_focus - 1 };
+            KeyCode::Down => {
+                if self.room_batch_selected + 1 < actions.len() {
+                    self.room_batch_selected += 1;
+                }
             }
             KeyCode::Enter => {
-                match self.creator_focus {
-                    0 if self.accounts.len() > 1 => {
-                        self.creator_account_idx = (self.creator_account_idx + 1) % self.accounts.len();
+                let label = actions.get(self.room_batch_selected).copied().unwrap_or("");
+                match label {
+                    "Favorite" | "Unfavorite" => self.batch_set_favorite(label == "Favorite").await,
+                    "Mute" | "Unmute" => self.batch_set_muted(label == "Mute"),
+                    "Mark as Read" => self.batch_mark_read().await,
+                    "Leave Rooms" => {
+                        self.ask_confirm(PendingConfirm::LeaveMarkedRooms(self.marked_rooms.len()));
+                        return;
                     }
-                    3 => self.creator_visibility = 1 - self.creator_visibility,
-                    4 => self.creator_e2ee = !self.creator_e2ee,
-                    5 => self.creator_federated = !self.creator_federated,
-                    _ => self.do_create_room().await,
+                    _ => return,
                 }
+                self.marked_rooms.clear();
+                self.overlay = Overlay::None;
             }
-            KeyCode::Left if self.creator_focus == 0 && self.accounts.len() > 1 => {
-                self.creator_account_idx = if self.creator_account_idx == 0 {
-                    self.accounts.len() - 1
-                } else {
-                    self.creator_account_idx - 1
-                };
+            KeyCode::Esc => self.overlay = Overlay::None,
+            _ => {}
+        }
+    }
+
+    // --- Broadcast to marked rooms ---
+
+    async fn handle_broadcast_key(&mut self, key: KeyEvent) {
+        let Some(ref b) = self.broadcast else {
+            self.overlay = Overlay::None;
+            return;
+        };
+        // Once every target is decided, only the outstanding sends are left to wait for
+        let deciding = b.next < b.targets.len();
+        let flagged = b.devices.is_some();
+        match key.code {
+            KeyCode::Enter | KeyCode::Char('y') if deciding => {
+                self.spawn_broadcast_send();
+                self.advance_broadcast().await;
+            }
+            KeyCode::Char('t') if deciding && flagged => {
+                self.trust_broadcast_target().await;
+            }
+            KeyCode::Char('a') if deciding => {
+                // Send to the rest, stopping at any room whose devices need a look first
+                loop {
+                    self.spawn_broadcast_send();
+                    self.advance_broadcast().await;
+                    match self.broadcast {
+                        Some(ref b) if b.next < b.targets.len() && b.devices.is_none() => {}
+                        _ => break,
+                    }
+                }
             }
-            KeyCode::Right if self.creator_focus == 0 && self.accounts.len() > 1 => {
-                self.creator_account_idx = (self.creator_account_idx + 1) % self.accounts.len();
+            KeyCode::Char('n') if deciding => {
+                self.release_broadcast_review();
+                if let Some(ref mut b) = self.broadcast {
+                    b.skipped += 1;
+                }
+                self.advance_broadcast().await;
             }
-            KeyCode::Char(' ') if self.creator_focus == 0 && self.accounts.len() > 1 => {
+            KeyCode::Esc => {
+                self.release_broadcast_review();
+                if let Some(ref mut b) = self.broadcast {
+                    b.skipped += b.targets.len() - b.next;
+                    b.next = b.targets.len();
+                    b.stopped = true;
+                }
+            }
+            _ => {}
+        }
+        self.maybe_finish_broadcast();
+    }
+
+    /// Move on to the next target and check its devices before asking about it
+    async fn advance_broadcast(&mut self) {
+        let target = match self.broadcast {
+            Some(ref mut b) => {
+                b.next += 1;
+                b.devices = None;
+                b.targets.get(b.next).cloned()
+            }
+            None => return,
+        };
+        if let Some(room) = target {
+            let devices = self.unreviewed_devices(&room.account_id, &room.id).await;
+            if let Some(ref mut b) = self.broadcast {
+                b.devices = devices;
+            }
+        }
+    }
+
+    /// A target skipped without sending hasn't been reviewed; warn again on the next send
+    fn release_broadcast_review(&mut self) {
+        if let Some(room) = self
+            .broadcast
+            .as_ref()
+            .filter(|b| b.devices.is_some())
+            .and_then(|b| b.targets.get(b.next))
+        {
+            self.device_checked.remove(&room.id);
+        }
+    }
+
+    /// Trust the current target's unverified devices, then send to it
+    async fn trust_broadcast_target(&mut self) {
+        let Some(b) = self.broadcast.as_ref() else {
+            return;
+        };
+        let (Some(room), Some(devices)) = (b.targets.get(b.next), b.devices.as_ref()) else {
+            return;
+        };
+        let Some(account) = self.accounts.iter().find(|a| a.user_id == room.account_id) else {
+            return;
+        };
+        let unverified: Vec<RoomDevice> = devices.iter().filter(|d| !d.verified).cloned().collect();
+        let result = account.trust_devices(&unverified).await;
+        match result {
+            Ok(()) => {
+                self.spawn_broadcast_send();
+                self.advance_broadcast().await;
+            }
+            Err(e) => self.status_msg = format!("Failed to trust devices: {}", e),
+        }
+    }
+
+    /// Send to the target awaiting confirmation without blocking the UI; the result
+    /// comes back as `MatrixEvent::BroadcastSent`
+    fn spawn_broadcast_send(&mut self) {
+        let Some(ref mut b) = self.broadcast else {
+            return;
+        };
+        let Some(room) = b.targets.get(b.next).cloned() else {
+            return;
+        };
+        let client = self
+            .accounts
+            .iter()
+            .find(|a| a.user_id == room.account_id)
+            .map(|a| a.client.clone());
+        let Some(client) = client else {
+            b.failed.push(room.name);
+            return;
+        };
+        let markdown = self.config.room_settings(room.id.as_str()).markdown;
+        let body = b.body.clone();
+        let tx = self.matrix_tx.clone();
+        b.in_flight.push(room.name.clone());
+        tokio::spawn(async move {
+            let result = crate::account::send_text(&client, &room.id, &body, markdown).await;
+            let _ = tx.send(MatrixEvent::BroadcastSent {
+                room_name: room.name,
+                error: result.err().map(|e| e.to_string()),
+            });
+        });
+    }
+
+    /// Close the overlay once every target is decided and every send has finished
+    fn maybe_finish_broadcast(&mut self) {
+        let done = self
+            .broadcast
+            .as_ref()
+            .is_some_and(|b| b.next >= b.targets.len() && b.in_flight.is_empty());
+        if !done {
+            return;
+        }
+        let Some(b) = self.broadcast.take() else {
+            return;
+        };
+        let mut summary = format!("Broadcast sent to {} of {} rooms", b.sent, b.targets.len());
+        if b.skipped > 0 {
+            summary.push_str(&format!(", {} skipped", b.skipped));
+        }
+        if !b.failed.is_empty() {
+            summary.push_str(&format!(", failed: {}", b.failed.join(", ")));
+        }
+        self.status_msg = summary;
+        // Keep the marks after a stop or failure so the broadcast can be retried
+        if !b.stopped && b.failed.is_empty() {
+            self.marked_rooms.clear();
+        }
+        if self.overlay == Overlay::Broadcast {
+            self.overlay = Overlay::None;
+        }
+    }
+
+    async fn batch_set_favorite(&mut self, favorite: bool) {
+        let marked = self.marked_room_list();
+        for room in &marked {
+            let id = room.id.to_string();
+            let pos = self.config.favorites.iter().position(|f| f == &id);
+            match (favorite, pos) {
+                (true, None) => self.config.favorites.push(id),
+                (false, Some(pos)) => {
+                    self.config.favorites.remove(pos);
+                }
+                _ => {}
+            }
+        }
+        if let Err(e) = self.config.save() {
+            error!("Failed to save config: {}", e);
+        }
+        self.status_msg = format!(
+            "{} {} rooms",
+            if favorite { "Favorited" } else { "Unfavorited" },
+            marked.len()
+        );
+        self.refresh_rooms().await;
+    }
+
+    fn batch_set_muted(&mut self, muted: bool) {
+        let marked = self.marked_room_list();
+        for room in &marked {
+            let key = room.id.to_string();
+            let mut settings = self.config.room_settings(&key);
+            settings.notifications = !muted;
+            self.config.room_settings.insert(key, settings);
+        }
+        if let Err(e) = self.config.save() {
+            error!("Failed to save config: {}", e);
+        }
+        self.status_msg = format!(
+            "{} {} rooms",
+            if muted { "Muted" } else { "Unmuted" },
+            marked.len()
+        );
+    }
+
+    async fn batch_mark_read(&mut self) {
+        let marked = self.marked_room_list();
+        let mut failed = 0;
+        for room in &marked {
+            let Some(account) = self.accounts.iter().find(|a| a.user_id == room.account_id) else {
+                failed += 1;
+                continue;
+            };
+            match account.mark_room_read(&room.id).await {
+                Ok(()) => {
+                    if let Some(r) = self.all_rooms.iter_mut().find(|r| r.id == room.id) {
+                        r.unread = 0;
+                    }
+                }
+                Err(e) => {
+                    info!("Mark read failed for {}: {}", room.id, e);
+                    failed += 1;
+                }
+            }
+        }
+        self.status_msg = if failed == 0 {
+            format!("Marked {} rooms as read", marked.len())
+        } else {
+            format!("Marked {} rooms as read, {} failed", marked.len() - failed, failed)
+        };
+    }
+
+    async fn batch_leave(&mut self) {
+        let marked = self.marked_room_list();
+        let mut failed = Vec::new();
+        for room in &marked {
+            let Some(account) = self.accounts.iter().find(|a| a.user_id == room.account_id) else {
+                failed.push(room.name.clone());
+                continue;
+            };
+            match account.leave_room(&room.id).await {
+                Ok(()) => {
+                    if self.active_room.as_ref() == Some(&room.id) {
+                        self.active_room = None;
+                        self.active_account_id = None;
+                        self.messages.clear();
+                    }
+                }
+                Err(e) => {
+                    info!("Leave failed for {}: {}", room.id, e);
+                    failed.push(room.name.clone());
+                }
+            }
+        }
+        self.status_msg = if failed.is_empty() {
+            format!("Left {} rooms", marked.len())
+        } else {
+            format!("Left {} rooms; failed: {}", marked.len() - failed.len(), failed.join(", "))
+        };
+        self.refresh_rooms().await;
+    }
+
+    async fn reorder_favorite_up(&mut self) {
+        if self.selected_room == 0 || self.selected_room >= self.favorites_count {
+            return;
+        }
+        // Swap in config.favorites
+        let idx = self.selected_room;
+        self.config.favorites.swap(idx, idx - 1);
+        let _ = self.config.save();
+        self.selected_room -= 1;
+        self.refresh_rooms().await;
+    }
+
+    async fn reorder_favorite_down(&mut self) {
+        if self.selected_room + 1 >= self.favorites_count {
+            return;
+        }
+        let idx = self.selected_room;
+        self.config.favorites.swap(idx, idx + 1);
+        let _ = self.config.save();
+        self.selected_room += 1;
+        self.refresh_rooms().await;
+    }
+
+    // --- Room Creator ---
+
+    /// Index of the configured default account, falling back to the first account
+    pub fn default_account_idx(&self) -> usize {
+        self.config
+            .default_account
+            .as_ref()
+            .and_then(|id| self.accounts.iter().position(|a| &a.user_id == id))
+            .unwrap_or(0)
+    }
+
+    fn open_room_creator(&mut self) {
+        self.overlay = Overlay::RoomCreator;
+        self.creator_name.clear();
+        self.creator_topic.clear();
+        self.creator_visibility = 0;
+        self.creator_e2ee = true;
+        self.creator_federated = true;
+        self.creator_invite.clear();
+        self.creator_account_idx = self.default_account_idx();
+        self.creator_focus = 0;
+        self.creator_error = None;
+        self.creator_busy = false;
+    }
+
+    async fn handle_creator_key(&mut self, key: KeyEvent) {
+        if self.creator_busy {
+            return;
+        }
+        // Focus: 0=account, 1=name, 2=topic, 3=visibility, 4=e2ee, 5=federated, 6=invite
+        match key.code {
+            KeyCode::Tab => {
+                self.creator_focus = (self.creator_focus + 1) % 7;
+            }
+            KeyCode::BackTab => {
+                self.creator_focus = if self.creator_focus == 0 { 6 } else { self.creator_focus - 1 };
+            }
+            KeyCode::Enter => {
+                match self.creator_focus {
+                    0 if self.accounts.len() > 1 => {
+                        self.creator_account_idx = (self.creator_account_idx + 1) % self.accounts.len();
+                    }
+                    3 => self.creator_visibility = 1 - self.creator_visibility,
+                    4 => self.creator_e2ee = !self.creator_e2ee,
+                    5 => self.creator_federated = !self.creator_federated,
+                    _ => self.do_create_room().await,
+                }
+            }
+            KeyCode::Left if self.creator_focus == 0 && self.accounts.len() > 1 => {
+                self.creator_account_idx = if self.creator_account_idx == 0 {
+                    self.accounts.len() - 1
+                } else {
+                    self.creator_account_idx - 1
+                };
+            }
+            KeyCode::Right if self.creator_focus == 0 && self.accounts.len() > 1 => {
+                self.creator_account_idx = (self.creator_account_idx + 1) % self.accounts.len();
+            }
+            KeyCode::Char(' ') if self.creator_focus == 0 && self.accounts.len() > 1 => {
                 self.creator_account_idx = (self.creator_account_idx + 1) % self.accounts.len();
             }
             KeyCode::Char(' ') if self.creator_focus == 3 => {
@@ -862,8 +1941,8 @@ impl App {
             self.editor_focus = 0;
             self.editor_error = None;
             self.editor_busy = false;
-            self.editor_confirm_leave = false;
-            self.editor_confirm_delete = false;
+            self.editor_published = None;
+            self.request_directory_visibility(&account_id, &room_id);
             self.editor_room_id = Some(room_id);
             self.editor_account_id = Some(account_id);
         }
@@ -873,51 +1952,27 @@ impl App {
         if self.editor_busy {
             return;
         }
-        // Focus: 0=name, 1=topic, 2=invite, 3=leave, 4=delete
+        // Focus: 0=name, 1=topic, 2=invite, 3=directory, 4=leave, 5=delete
         match key.code {
             KeyCode::Tab => {
-                self.editor_focus = (self.editor_focus + 1) % 5;
-                self.editor_confirm_leave = false;
-                self.editor_confirm_delete = false;
+                self.editor_focus = (self.editor_focus + 1) % 6;
             }
             KeyCode::BackTab => {
-                self.editor_focus = if self.editor_focus == 0 { 4 } else { self.editor_focus - 1 };
-                self.editor_confirm_leave = false;
-                self.editor_confirm_delete = false;
+                self.editor_focus = if self.editor_focus == 0 { 5 } else { self.editor_focus - 1 };
             }
             KeyCode::Enter => {
                 match self.editor_focus {
                     0 => self.do_edit_room_name().await,
                     1 => self.do_edit_room_topic().await,
                     2 => self.do_invite_user().await,
-                    3 => {
-                        if self.editor_confirm_leave {
-                            self.do_leave_room().await;
-                        } else {
-                            self.editor_confirm_leave = true;
-                        }
-                    }
-                    4 => {
-                        if self.editor_confirm_delete {
-                            self.do_delete_room().await;
-                        } else {
-                            self.editor_confirm_delete = true;
-                        }
-                    }
+                    3 => self.do_toggle_directory().await,
+                    4 => self.ask_confirm(PendingConfirm::LeaveRoom(self.editor_room_name())),
+                    5 => self.ask_confirm(PendingConfirm::DeleteRoom(self.editor_room_name())),
                     _ => {}
                 }
             }
-            KeyCode::Esc => {
-                if self.editor_confirm_leave || self.editor_confirm_delete {
-                    self.editor_confirm_leave = false;
-                    self.editor_confirm_delete = false;
-                } else {
-                    self.overlay = Overlay::None;
-                }
-            }
+            KeyCode::Esc => self.overlay = Overlay::None,
             KeyCode::Char(c) => {
-                self.editor_confirm_leave = false;
-                self.editor_confirm_delete = false;
                 match self.editor_focus {
                     0 => self.editor_name.push(c),
                     1 => self.editor_topic.push(c),
@@ -926,8 +1981,6 @@ impl App {
                 }
             }
             KeyCode::Backspace => {
-                self.editor_confirm_leave = false;
-                self.editor_confirm_delete = false;
                 match self.editor_focus {
                     0 => { self.editor_name.pop(); }
                     1 => { self.editor_topic.pop(); }
@@ -939,6 +1992,15 @@ impl App {
         }
     }
 
+    /// Name of the room being edited as it's shown in the room list
+    fn editor_room_name(&self) -> String {
+        self.editor_room_id
+            .as_ref()
+            .and_then(|id| self.all_rooms.iter().find(|r| &r.id == id))
+            .map(|r| r.name.clone())
+            .unwrap_or_else(|| "this room".to_string())
+    }
+
     async fn do_edit_room_name(&mut self) {
         let (room_id, account_id) = match (&self.editor_room_id, &self.editor_account_id) {
             (Some(r), Some(a)) => (r.clone(), a.clone()),
@@ -987,10 +2049,16 @@ impl App {
             self.editor_error = Some("Enter a user ID".to_string());
             return;
         }
+        let invitee = self.editor_invite_user.trim().to_string();
+        // An address without a leading '@' is an email, invited via the identity server
+        if !invitee.starts_with('@') && invitee.contains('@') {
+            self.do_invite_by_email(&room_id, &account_id, &invitee).await;
+            return;
+        }
         self.editor_busy = true;
         self.editor_error = None;
         if let Some(acct) = self.accounts.iter().find(|a| a.user_id == account_id) {
-            match acct.invite_user(&room_id, self.editor_invite_user.trim()).await {
+            match acct.invite_user(&room_id, &invitee).await {
                 Ok(()) => {
                     self.status_msg = format!("Invited {}", self.editor_invite_user.trim());
                     self.editor_invite_user.clear();
@@ -1001,6 +2069,87 @@ impl App {
         self.editor_busy = false;
     }
 
+    async fn do_invite_by_email(&mut self, room_id: &OwnedRoomId, account_id: &str, email: &str) {
+        let Some(acct) = self.accounts.iter().find(|a| a.user_id == account_id) else {
+            return;
+        };
+        let token = self
+            .config
+            .accounts
+            .iter()
+            .find(|a| a.user_id == account_id)
+            .and_then(|a| a.identity_server_token.clone());
+        self.editor_busy = true;
+        self.editor_error = None;
+        let server = acct.get_identity_server().await.ok().flatten();
+        let (Some(server), Some(token)) = (server, token) else {
+            self.editor_error =
+                Some("Set an identity server in Settings to invite by email".to_string());
+            self.editor_busy = false;
+            return;
+        };
+        match acct.invite_by_email(room_id, email, &server, &token).await {
+            Ok(()) => {
+                self.status_msg = format!("Invited {}", email);
+                self.editor_invite_user.clear();
+            }
+            Err(e) => self.editor_error = Some(e.to_string()),
+        }
+        self.editor_busy = false;
+    }
+
+    async fn do_toggle_directory(&mut self) {
+        let (room_id, account_id) = match (&self.editor_room_id, &self.editor_account_id) {
+            (Some(r), Some(a)) => (r.clone(), a.clone()),
+            _ => return,
+        };
+        // Toggling an unknown state could publish a room that's meant to be private
+        let Some(published) = self.editor_published else {
+            self.editor_error = Some("Directory listing unknown, can't toggle it".to_string());
+            return;
+        };
+        let publish = !published;
+        self.editor_busy = true;
+        self.editor_error = None;
+        if let Some(acct) = self.accounts.iter().find(|a| a.user_id == account_id) {
+            match acct.set_directory_visibility(&room_id, publish).await {
+                Ok(()) => {
+                    self.editor_published = Some(publish);
+                    self.status_msg = if publish {
+                        "Room published in the directory".to_string()
+                    } else {
+                        "Room removed from the directory".to_string()
+                    };
+                }
+                Err(e) => self.editor_error = Some(e.to_string()),
+            }
+        }
+        self.editor_busy = false;
+    }
+
+    /// Look up whether a room is published in the directory, for Room Info and the
+    /// room editor. The answer arrives as `MatrixEvent::DirectoryVisibility`.
+    fn request_directory_visibility(&self, account_id: &str, room_id: &OwnedRoomId) {
+        let Some(client) = self
+            .accounts
+            .iter()
+            .find(|a| a.user_id == account_id)
+            .map(|a| a.client.clone())
+        else {
+            return;
+        };
+        let tx = self.matrix_tx.clone();
+        let room_id = room_id.clone();
+        tokio::spawn(async move {
+            match crate::account::directory_visibility(&client, &room_id).await {
+                Ok(published) => {
+                    let _ = tx.send(MatrixEvent::DirectoryVisibility { room_id, published });
+                }
+                Err(e) => info!("Directory visibility unavailable for {}: {}", room_id, e),
+            }
+        });
+    }
+
     async fn do_leave_room(&mut self) {
         let (room_id, account_id) = match (&self.editor_room_id, &self.editor_account_id) {
             (Some(r), Some(a)) => (r.clone(), a.clone()),
@@ -1154,6 +2303,8 @@ impl App {
             Ok(()) => {
                 self.profile_current_avatar = self.profile_avatar_url.clone();
                 self.status_msg = "Avatar URL updated".to_string();
+                let user_id = self.accounts[idx].user_id.clone();
+                self.spawn_avatar_download(&user_id);
                 self.overlay = Overlay::None;
             }
             Err(e) => self.profile_error = Some(e.to_string()),
@@ -1172,6 +2323,8 @@ impl App {
             Ok(mxc_url) => {
                 self.profile_current_avatar = mxc_url;
                 self.status_msg = "Avatar uploaded".to_string();
+                let user_id = self.accounts[idx].user_id.clone();
+                self.spawn_avatar_download(&user_id);
                 self.overlay = Overlay::None;
             }
             Err(e) => self.profile_error = Some(e.to_string()),
@@ -1179,31 +2332,353 @@ impl App {
         self.profile_busy = false;
     }
 
-    fn open_recovery(&mut self, account_idx: usize) {
-        self.recovery_account_idx = account_idx;
-        self.recovery_key.clear();
-        self.recovery_error = None;
-        self.recovery_busy = false;
-        self.overlay = Overlay::Recovery;
+    // --- Identity Server ---
+
+    async fn open_identity_server(&mut self, account_idx: usize) {
+        if account_idx >= self.accounts.len() {
+            return;
+        }
+        self.identity_account_idx = account_idx;
+        self.identity_busy = true;
+        self.overlay = Overlay::IdentityServer;
+        self.identity_terms = None;
+        self.identity_error = None;
+
+        self.identity_current = match self.accounts[account_idx].get_identity_server().await {
+            Ok(current) => current,
+            Err(e) => {
+                self.identity_error = Some(e.to_string());
+                None
+            }
+        };
+        self.identity_url = self
+            .identity_current
+            .clone()
+            .unwrap_or_else(|| "https://vector.im".to_string());
+        self.identity_busy = false;
     }
 
-    async fn handle_recovery_key(&mut self, key: KeyEvent) {
-        if self.recovery_busy {
+    async fn handle_identity_key(&mut self, key: KeyEvent) {
+        if self.identity_busy {
             return;
         }
-        match key.code {
-            KeyCode::Enter => {
-                if !self.recovery_key.is_empty() {
-                    self.do_recover().await;
+        match (key.modifiers, key.code) {
+            (KeyModifiers::CONTROL, KeyCode::Char('d')) => self.do_disconnect_identity_server().await,
+            (_, KeyCode::Enter) => {
+                // First Enter shows the server's terms, the second accepts them
+                if self.identity_terms.is_none() {
+                    self.do_fetch_identity_terms().await;
+                } else {
+                    self.do_connect_identity_server().await;
                 }
             }
-            KeyCode::Char(c) => {
-                self.recovery_key.push(c);
+            (_, KeyCode::Esc) => {
+                self.overlay = Overlay::None;
             }
-            KeyCode::Backspace => {
-                self.recovery_key.pop();
+            (_, KeyCode::Char(c)) => {
+                self.identity_url.push(c);
+                self.identity_terms = None;
             }
-            KeyCode::Esc => {
+            (_, KeyCode::Backspace) => {
+                self.identity_url.pop();
+                self.identity_terms = None;
+            }
+            _ => {}
+        }
+    }
+
+    async fn do_fetch_identity_terms(&mut self) {
+        let idx = self.identity_account_idx;
+        if idx >= self.accounts.len() || self.identity_url.trim().is_empty() {
+            return;
+        }
+        self.identity_busy = true;
+        self.identity_error = None;
+        match self.accounts[idx].identity_server_terms(self.identity_url.trim()).await {
+            Ok(terms) => self.identity_terms = Some(terms),
+            Err(e) => self.identity_error = Some(format!("Could not reach identity server: {}", e)),
+        }
+        self.identity_busy = false;
+    }
+
+    async fn do_connect_identity_server(&mut self) {
+        let idx = self.identity_account_idx;
+        if idx >= self.accounts.len() {
+            return;
+        }
+        let accepted: Vec<String> = self
+            .identity_terms
+            .iter()
+            .flatten()
+            .map(|(_, url)| url.clone())
+            .collect();
+        self.identity_busy = true;
+        self.identity_error = None;
+        let url = self.identity_url.trim().to_string();
+        match self.accounts[idx].connect_identity_server(&url, &accepted).await {
+            Ok(token) => {
+                let user_id = self.accounts[idx].user_id.clone();
+                if let Some(saved) = self.config.accounts.iter_mut().find(|a| a.user_id == user_id) {
+                    saved.identity_server_token = Some(token);
+                }
+                if let Err(e) = self.config.save() {
+                    error!("Failed to save config: {}", e);
+                }
+                self.status_msg = format!("Identity server set to {}", url);
+                self.overlay = Overlay::None;
+            }
+            Err(e) => self.identity_error = Some(e.to_string()),
+        }
+        self.identity_busy = false;
+    }
+
+    async fn do_disconnect_identity_server(&mut self) {
+        let idx = self.identity_account_idx;
+        if idx >= self.accounts.len() {
+            return;
+        }
+        self.identity_busy = true;
+        self.identity_error = None;
+        match self.accounts[idx].set_identity_server(None).await {
+            Ok(()) => {
+                let user_id = self.accounts[idx].user_id.clone();
+                if let Some(saved) = self.config.accounts.iter_mut().find(|a| a.user_id == user_id) {
+                    saved.identity_server_token = None;
+                }
+                if let Err(e) = self.config.save() {
+                    error!("Failed to save config: {}", e);
+                }
+                self.identity_current = None;
+                self.status_msg = "Identity server disconnected".to_string();
+                self.overlay = Overlay::None;
+            }
+            Err(e) => self.identity_error = Some(e.to_string()),
+        }
+        self.identity_busy = false;
+    }
+
+    // --- Emails & Phones ---
+
+    async fn open_threepids(&mut self, account_idx: usize) {
+        if account_idx >= self.accounts.len() {
+            return;
+        }
+        self.threepid_account_idx = account_idx;
+        self.threepid_step = ThreepidStep::List;
+        self.threepid_selected = 0;
+        self.threepid_input.clear();
+        self.threepid_request = None;
+        self.threepid_error = None;
+        self.overlay = Overlay::Threepids;
+        self.load_threepids().await;
+    }
+
+    async fn load_threepids(&mut self) {
+        let idx = self.threepid_account_idx;
+        if idx >= self.accounts.len() {
+            return;
+        }
+        self.threepid_busy = true;
+        match self.accounts[idx].threepids().await {
+            Ok(list) => self.threepid_list = list,
+            Err(e) => self.threepid_error = Some(e.to_string()),
+        }
+        self.threepid_selected = self
+            .threepid_selected
+            .min(self.threepid_list.len().saturating_sub(1));
+        self.threepid_busy = false;
+    }
+
+    async fn handle_threepid_key(&mut self, key: KeyEvent) {
+        if self.threepid_busy {
+            return;
+        }
+        match self.threepid_step {
+            ThreepidStep::List => match key.code {
+                KeyCode::Up => {
+                    self.threepid_selected = self.threepid_selected.saturating_sub(1);
+                }
+                KeyCode::Down => {
+                    if self.threepid_selected + 1 < self.threepid_list.len() {
+                        self.threepid_selected += 1;
+                    }
+                }
+                KeyCode::Char(c @ ('a' | 'p')) => {
+                    self.threepid_phone = c == 'p';
+                    self.threepid_input.clear();
+                    self.threepid_error = None;
+                    self.threepid_step = ThreepidStep::EnterAddress;
+                }
+                KeyCode::Char('d') | KeyCode::Delete => {
+                    if let Some((_, address)) = self.threepid_list.get(self.threepid_selected) {
+                        self.threepid_error = None;
+                        self.ask_confirm(PendingConfirm::RemoveThreepid(address.clone()));
+                    }
+                }
+                KeyCode::Esc => {
+                    self.overlay = Overlay::None;
+                }
+                _ => {}
+            },
+            step => match key.code {
+                KeyCode::Esc => {
+                    self.threepid_step = ThreepidStep::List;
+                    self.threepid_request = None;
+                    self.threepid_input.clear();
+                    self.threepid_error = None;
+                }
+                KeyCode::Enter => match step {
+                    ThreepidStep::EnterAddress => self.do_request_threepid_token().await,
+                    ThreepidStep::Validate => self.do_validate_threepid().await,
+                    _ => self.do_add_threepid().await,
+                },
+                KeyCode::Char(c) => self.threepid_input.push(c),
+                KeyCode::Backspace => {
+                    self.threepid_input.pop();
+                }
+                _ => {}
+            },
+        }
+    }
+
+    async fn do_request_threepid_token(&mut self) {
+        let idx = self.threepid_account_idx;
+        let input = self.threepid_input.trim().to_string();
+        if idx >= self.accounts.len() || input.is_empty() {
+            return;
+        }
+        self.threepid_busy = true;
+        self.threepid_error = None;
+        let result = if self.threepid_phone {
+            match input.split_once(char::is_whitespace) {
+                Some((country, number)) => {
+                    self.accounts[idx]
+                        .request_phone_token(&country.to_uppercase(), number.trim())
+                        .await
+                }
+                None => Err(anyhow::anyhow!(
+                    "Enter a country code and number, e.g. GB 07700900123"
+                )),
+            }
+        } else {
+            self.accounts[idx].request_email_token(&input).await
+        };
+        match result {
+            Ok(request) => {
+                self.threepid_request = Some(request);
+                self.threepid_input.clear();
+                self.threepid_step = ThreepidStep::Validate;
+            }
+            Err(e) => self.threepid_error = Some(e.to_string()),
+        }
+        self.threepid_busy = false;
+    }
+
+    async fn do_validate_threepid(&mut self) {
+        let idx = self.threepid_account_idx;
+        if idx >= self.accounts.len() {
+            return;
+        }
+        if self.threepid_phone {
+            let Some(request) = self.threepid_request.as_ref() else {
+                return;
+            };
+            self.threepid_busy = true;
+            self.threepid_error = None;
+            let code = self.threepid_input.trim().to_string();
+            let submitted = self.accounts[idx].submit_phone_code(request, &code).await;
+            self.threepid_busy = false;
+            if let Err(e) = submitted {
+                self.threepid_error = Some(e.to_string());
+                return;
+            }
+            self.threepid_input.clear();
+        }
+        self.do_add_threepid().await;
+    }
+
+    /// Add the validated address, asking for the password if the server requires it
+    async fn do_add_threepid(&mut self) {
+        let idx = self.threepid_account_idx;
+        if idx >= self.accounts.len() {
+            return;
+        }
+        let Some(mut request) = self.threepid_request.take() else {
+            return;
+        };
+        let password = (self.threepid_step == ThreepidStep::Password)
+            .then(|| self.threepid_input.clone());
+        self.threepid_busy = true;
+        self.threepid_error = None;
+        let result = self.accounts[idx]
+            .add_threepid(&mut request, password.as_deref())
+            .await;
+        self.threepid_busy = false;
+        match result {
+            Ok(true) => {
+                self.threepid_input.clear();
+                self.threepid_step = ThreepidStep::List;
+                self.status_msg = "Address added".to_string();
+                self.load_threepids().await;
+            }
+            Ok(false) => {
+                self.threepid_request = Some(request);
+                self.threepid_input.clear();
+                self.threepid_step = ThreepidStep::Password;
+            }
+            Err(e) => {
+                self.threepid_request = Some(request);
+                self.threepid_error = Some(e.to_string());
+            }
+        }
+    }
+
+    async fn do_remove_threepid(&mut self) {
+        let idx = self.threepid_account_idx;
+        let Some((medium, address)) = self.threepid_list.get(self.threepid_selected).cloned() else {
+            return;
+        };
+        if idx >= self.accounts.len() {
+            return;
+        }
+        self.threepid_busy = true;
+        self.threepid_error = None;
+        let result = self.accounts[idx].remove_threepid(medium, &address).await;
+        self.threepid_busy = false;
+        match result {
+            Ok(()) => {
+                self.status_msg = format!("Removed {}", address);
+                self.load_threepids().await;
+            }
+            Err(e) => self.threepid_error = Some(e.to_string()),
+        }
+    }
+
+    fn open_recovery(&mut self, account_idx: usize) {
+        self.recovery_account_idx = account_idx;
+        self.recovery_key.clear();
+        self.recovery_error = None;
+        self.recovery_busy = false;
+        self.overlay = Overlay::Recovery;
+    }
+
+    async fn handle_recovery_key(&mut self, key: KeyEvent) {
+        if self.recovery_busy {
+            return;
+        }
+        match key.code {
+            KeyCode::Enter => {
+                if !self.recovery_key.is_empty() {
+                    self.do_recover().await;
+                }
+            }
+            KeyCode::Char(c) => {
+                self.recovery_key.push(c);
+            }
+            KeyCode::Backspace => {
+                self.recovery_key.pop();
+            }
+            KeyCode::Esc => {
                 self.overlay = Overlay::Settings;
             }
             _ => {}
@@ -1349,19 +2824,39 @@ impl App {
     /// Get context-sensitive action labels for the selected message
     pub fn message_action_labels(&self) -> Vec<&'static str> {
         match self.selected_message.and_then(|i| self.messages.get(i)) {
+            Some(msg) if msg.send_error.is_some() => vec!["Resend", "Discard"],
             Some(msg) => {
                 let is_own = self.active_account_id.as_deref() == Some(&msg.sender);
-                match (&msg.content, is_own) {
+                let mut labels = match (&msg.content, is_own) {
                     (MessageContent::Text(_), true) => vec!["Edit Message", "Delete Message"],
                     (MessageContent::Text(_), false) => vec!["Delete Message"],
                     _ => vec!["Download", "Delete Message"],
+                };
+                // Other people's messages can only be redacted with the room's redact level
+                let can_redact_others = self
+                    .active_account_id
+                    .as_deref()
+                    .is_some_and(|me| self.room_power.level(me) >= self.room_power.redact);
+                if !is_own && self.peeking.is_none() && can_redact_others {
+                    labels.push("Delete All by Sender");
+                }
+                if !is_own {
+                    labels.push(if self.config.is_bot(&msg.sender) {
+                        "Untag Bot"
+                    } else {
+                        "Tag Sender as Bot"
+                    });
                 }
+                labels
             }
             None => vec!["Delete Message"],
         }
     }
 
     fn open_message_action(&mut self) {
+        if self.blocked_read_only() {
+            return;
+        }
         if let Some(idx) = self.selected_message {
             if idx < self.messages.len() {
                 self.message_action_selected = 0;
@@ -1468,9 +2963,22 @@ impl App {
                     "Delete Message" => {
                         self.do_delete_message().await;
                     }
+                    "Delete All by Sender" => {
+                        self.confirm_redact_from_sender();
+                    }
                     "Download" => {
                         self.do_download_media().await;
                     }
+                    "Resend" => {
+                        self.resend_failed_message().await;
+                    }
+                    "Discard" => {
+                        self.take_failed_message();
+                        self.overlay = Overlay::None;
+                    }
+                    "Tag Sender as Bot" | "Untag Bot" => {
+                        self.toggle_bot_tag();
+                    }
                     _ => {}
                 }
             }
@@ -1481,6 +2989,141 @@ impl App {
         }
     }
 
+    /// Why we can't post in the open room, if we were kicked or banned from it
+    pub fn active_removed(&self) -> Option<&RemovedFrom> {
+        self.removed_from.get(self.active_room.as_ref()?)
+    }
+
+    /// Why nothing can be sent to the open room, if so
+    fn read_only_notice(&self) -> Option<&'static str> {
+        if self.peeking.is_some() {
+            return Some("Read-only preview \u{2014} /join to participate");
+        }
+        if self.active_removed().is_some() {
+            return Some("You're no longer in this room \u{2014} /forget to remove it");
+        }
+        None
+    }
+
+    /// Every send and message action checks this first; shows why it's refused
+    fn blocked_read_only(&mut self) -> bool {
+        match self.read_only_notice() {
+            Some(notice) => {
+                self.status_msg = notice.to_string();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Banner shown in place of the typing indicator when the composer is disabled
+    pub fn chat_banner(&self) -> Option<String> {
+        if self.peeking.is_some() {
+            return Some("Join to participate \u{2014} type /join".to_string());
+        }
+        self.active_removed()
+            .map(|r| format!("{} \u{2014} /forget to remove the room", r.notice))
+    }
+
+    /// Tag or untag the selected message's sender as a bot
+    fn toggle_bot_tag(&mut self) {
+        let Some(sender) = self
+            .selected_message
+            .and_then(|i| self.messages.get(i))
+            .map(|m| m.sender.clone())
+        else {
+            return;
+        };
+        let tagged = match self.config.bots.iter().position(|b| *b == sender) {
+            Some(pos) => {
+                self.config.bots.remove(pos);
+                false
+            }
+            None => {
+                self.config.bots.push(sender.clone());
+                true
+            }
+        };
+        if let Err(e) = self.config.save() {
+            error!("Failed to save config: {}", e);
+        }
+        self.status_msg = if tagged {
+            format!("{} tagged as a bot (dimmed, no notifications)", sender)
+        } else {
+            format!("{} is no longer tagged as a bot", sender)
+        };
+        self.overlay = Overlay::None;
+    }
+
+    /// Sender name as shown in the chat panel, with an admin / mod badge if enabled
+    pub fn sender_label<'a>(&self, msg: &'a DisplayMessage) -> Cow<'a, str> {
+        if !self.config.power_badges {
+            return Cow::Borrowed(&msg.sender);
+        }
+        match self.room_power.badge(&msg.sender) {
+            Some(badge) => Cow::Owned(format!("{} [{}]", msg.sender, badge)),
+            None => Cow::Borrowed(&msg.sender),
+        }
+    }
+
+    /// Text from a tagged bot, drawn dimmed on a single line with its sender
+    pub fn is_compact_bot(&self, msg: &DisplayMessage) -> bool {
+        matches!(msg.content, MessageContent::Text(_)) && self.config.is_bot(&msg.sender)
+    }
+
+    /// Remove the selected failed local echo from both timelines and return it
+    fn take_failed_message(&mut self) -> Option<DisplayMessage> {
+        let idx = self.selected_message?;
+        if self.messages.get(idx)?.send_error.is_none() {
+            return None;
+        }
+        let msg = self.messages.remove(idx);
+        if let Some(cached) = self
+            .active_room
+            .as_ref()
+            .and_then(|r| self.room_messages.get_mut(r))
+        {
+            if let Some(pos) = cached.iter().position(|m| {
+                m.send_error.is_some()
+                    && m.timestamp == msg.timestamp
+                    && m.body_text() == msg.body_text()
+            }) {
+                cached.remove(pos);
+            }
+        }
+        self.selected_message = None;
+        Some(msg)
+    }
+
+    /// Send a failed message again the same way it was first sent
+    async fn resend_failed_message(&mut self) {
+        let msg = match self.take_failed_message() {
+            Some(m) => m,
+            None => return,
+        };
+        self.overlay = Overlay::None;
+        let body = msg.body_text().to_string();
+        let code = body
+            .strip_prefix("```\n")
+            .and_then(|rest| rest.strip_suffix("\n```"))
+            .map(str::to_string);
+        // If held and cancelled, the text goes back in the composer, escaped like typed input
+        let typed = if body.starts_with('/') {
+            format!("/{}", body)
+        } else {
+            body.clone()
+        };
+        let outgoing = match (msg.reply_to_event_id_raw, msg.reply_to_sender, code) {
+            (Some(event_id), Some(sender), _) => {
+                let quoted = msg.reply_to_body.unwrap_or_default();
+                Outgoing::Reply(body, (event_id, sender, quoted))
+            }
+            (_, _, Some(code)) => Outgoing::Code(code),
+            _ => Outgoing::Text(body),
+        };
+        self.send_outgoing(outgoing, Some(typed)).await;
+    }
+
     async fn do_edit_message(&mut self) {
         let msg_idx = match self.selected_message {
             Some(idx) => idx,
@@ -1571,6 +3214,95 @@ impl App {
         self.message_edit_busy = false;
     }
 
+    /// Ask before redacting every loaded message from the selected message's sender
+    fn confirm_redact_from_sender(&mut self) {
+        let Some(room_id) = self.active_room.clone() else {
+            return;
+        };
+        let Some(sender) = self
+            .selected_message
+            .and_then(|i| self.messages.get(i))
+            .map(|m| m.sender.clone())
+        else {
+            return;
+        };
+        let event_ids: Vec<String> = self
+            .messages
+            .iter()
+            .filter(|m| m.sender == sender)
+            .filter_map(|m| m.event_id.clone())
+            .collect();
+        if event_ids.is_empty() {
+            self.message_edit_error = Some("Nothing to delete: no event IDs".to_string());
+            return;
+        }
+        self.ask_confirm(PendingConfirm::RedactFromSender { room_id, sender, event_ids });
+    }
+
+    /// Redact the messages one after another in the background; each one's result
+    /// comes back as `MatrixEvent::SenderRedaction`
+    fn redact_from_sender(&mut self, room_id: OwnedRoomId, sender: String, event_ids: Vec<String>) {
+        let Some(client) = self
+            .accounts
+            .iter()
+            .find(|a| self.active_account_id.as_deref() == Some(a.user_id.as_str()))
+            .map(|a| a.client.clone())
+        else {
+            return;
+        };
+        let tx = self.matrix_tx.clone();
+        let total = event_ids.len();
+        self.status_msg = format!("Deleting {} messages from {}...", total, sender);
+        tokio::spawn(async move {
+            let mut deleted = 0;
+            let mut last_error = None;
+            for (i, event_id) in event_ids.into_iter().enumerate() {
+                let result = crate::account::redact_event(&client, &room_id, &event_id).await;
+                let redacted = match result {
+                    Ok(()) => {
+                        deleted += 1;
+                        Some(event_id)
+                    }
+                    Err(e) => {
+                        info!("Redacting {} failed: {}", event_id, e);
+                        last_error = Some(e.to_string());
+                        None
+                    }
+                };
+                let _ = tx.send(MatrixEvent::SenderRedaction {
+                    room_id: room_id.clone(),
+                    sender: sender.clone(),
+                    redacted,
+                    deleted,
+                    done: i + 1,
+                    total,
+                    last_error: last_error.clone(),
+                });
+            }
+        });
+    }
+
+    /// Take a redacted message out of the open room and the room's cache
+    fn remove_redacted(&mut self, room_id: &OwnedRoomId, event_id: &str) {
+        if self.active_room.as_ref() == Some(room_id) {
+            if let Some(idx) = self
+                .messages
+                .iter()
+                .position(|m| m.event_id.as_deref() == Some(event_id))
+            {
+                self.messages.remove(idx);
+                self.selected_message = match self.selected_message {
+                    Some(sel) if sel == idx => None,
+                    Some(sel) if sel > idx => Some(sel - 1),
+                    sel => sel,
+                };
+            }
+        }
+        if let Some(cached) = self.room_messages.get_mut(room_id) {
+            cached.retain(|m| m.event_id.as_deref() != Some(event_id));
+        }
+    }
+
     async fn do_download_media(&mut self) {
         let msg_idx = match self.selected_message {
             Some(idx) => idx,
@@ -1635,6 +3367,11 @@ impl App {
             (Some(r), Some(a)) => (r.clone(), a.clone()),
             _ => return,
         };
+        if self.stale_history_tokens.contains(&room_id)
+            && !self.refresh_history_token(&room_id, &account_id).await
+        {
+            return;
+        }
         let token = match self.room_history_tokens.get(&room_id) {
             Some(Some(t)) => t.clone(),
             _ => return, // no more history or no token stored
@@ -1643,25 +3380,32 @@ impl App {
         self.status_msg = "Loading older messages...".to_string();
 
         if let Some(account) = self.accounts.iter().find(|a| a.user_id == account_id) {
-            match account
-                .fetch_history_paged(&room_id, Some(&token), 50)
-                .await
-            {
+            let page = if self.peeking.is_some() {
+                account.peek_history(&room_id, Some(&token), 50).await
+            } else {
+                account.fetch_history_paged(&room_id, Some(&token), 50).await
+            };
+            match page {
                 Ok((mut older_msgs, next_token)) => {
                     if older_msgs.is_empty() {
                         self.room_history_tokens.insert(room_id, None);
                         self.status_msg = "No more messages".to_string();
                         return;
                     }
+                    older_msgs.retain(|m| self.passes_room_filters(&room_id, m));
                     let count = older_msgs.len();
                     // Prepend older messages
                     older_msgs.append(&mut self.messages);
                     self.messages = older_msgs;
+                    Self::resolve_all_replies(&mut self.messages);
+                    self.fetch_missing_replies();
                     // Adjust selected_message and scroll_offset for the prepended messages
                     if let Some(sel) = self.selected_message {
                         self.selected_message = Some(sel + count);
                     }
                     self.scroll_offset += count;
+                    // Backfilled pages can overlap the loaded ones in time
+                    self.sort_active_by_time();
                     // Store next token for further pagination
                     self.room_history_tokens.insert(room_id, next_token);
                     self.status_msg = format!("Loaded {} older messages", count);
@@ -1673,53 +3417,704 @@ impl App {
         }
     }
 
-    async fn handle_chat_key(&mut self, key: KeyEvent) {
-        let viewport = self.chat_viewport_msgs.get().max(1);
-
-        match key.code {
-            KeyCode::Up => {
-                if self.messages.is_empty() {
-                    return;
-                }
-                match self.selected_message {
-                    None => {
-                        // Start selecting from the bottom
-                        self.selected_message = Some(self.messages.len() - 1);
-                        self.scroll_offset = 0;
-                    }
-                    Some(0) => {
-                        // At top — try to load older messages
-                        self.fetch_older_messages().await;
-                    }
-                    Some(idx) => {
-                        let new_idx = idx - 1;
-                        self.selected_message = Some(new_idx);
-                        // Only scroll if selection would go above the visible area
-                        let end = self.messages.len().saturating_sub(self.scroll_offset);
-                        let start = end.saturating_sub(viewport);
-                        if new_idx < start {
-                            self.scroll_offset = self.scroll_offset.saturating_add(1);
-                        }
-                    }
-                }
+    /// Replace a trimmed room's token with one from just before its oldest kept message,
+    /// returning false if the server couldn't give us one
+    async fn refresh_history_token(&mut self, room_id: &OwnedRoomId, account_id: &str) -> bool {
+        let Some(event_id) = self.messages.iter().find_map(|m| m.event_id.clone()) else {
+            return false;
+        };
+        let Some(account) = self.accounts.iter().find(|a| a.user_id == account_id) else {
+            return false;
+        };
+        match crate::account::token_before_event(&account.client, room_id, &event_id).await {
+            Ok(token) => {
+                self.stale_history_tokens.remove(room_id);
+                self.room_history_tokens.insert(room_id.clone(), token);
+                true
             }
-            KeyCode::Down => {
+            Err(e) => {
+                // Stays stale, so the next page back tries again
+                self.status_msg = format!("Failed to load history: {}", e);
+                false
+            }
+        }
+    }
+
+    // --- Member list ---
+
+    async fn open_member_list(&mut self) {
+        let (room_id, account_id) = match (&self.active_room, &self.active_account_id) {
+            (Some(r), Some(a)) => (r.clone(), a.clone()),
+            _ => return,
+        };
+        let Some(account) = self.accounts.iter().find(|a| a.user_id == account_id) else {
+            return;
+        };
+        match account.room_members(&room_id).await {
+            Ok(members) => {
+                self.members = members;
+                self.member_filter.clear();
+                self.member_selected = 0;
+                self.overlay = Overlay::MemberList;
+            }
+            Err(e) => {
+                self.status_msg = format!("Failed to load members: {}", e);
+            }
+        }
+    }
+
+    /// Members matching the member list filter (name or user ID)
+    pub fn filtered_members(&self) -> Vec<&MemberInfo> {
+        let q = self.member_filter.to_lowercase();
+        self.members
+            .iter()
+            .filter(|m| {
+                q.is_empty()
+                    || m.user_id.to_lowercase().contains(&q)
+                    || m
+                        .display_name
+                        .as_ref()
+                        .is_some_and(|n| n.to_lowercase().contains(&q))
+            })
+            .collect()
+    }
+
+    async fn handle_member_list_key(&mut self, key: KeyEvent) {
+        match key.code {
+            KeyCode::Esc => {
+                self.overlay = Overlay::None;
+            }
+            KeyCode::Enter => {
+                let user_id = self
+                    .filtered_members()
+                    .get(self.member_selected)
+                    .map(|m| m.user_id.clone());
+                if let Some(user_id) = user_id {
+                    self.overlay = Overlay::None;
+                    self.focus = Focus::Chat;
+                    self.jump_to_last_message_from(&user_id).await;
+                }
+            }
+            KeyCode::Tab => {
+                // Only show this member's messages
+                let user_id = self
+                    .filtered_members()
+                    .get(self.member_selected)
+                    .map(|m| m.user_id.clone());
+                if let Some(user_id) = user_id {
+                    self.overlay = Overlay::None;
+                    self.focus = Focus::Chat;
+                    self.toggle_sender_filter(&user_id);
+                }
+            }
+            KeyCode::Up => {
+                self.member_selected = self.member_selected.saturating_sub(1);
+            }
+            KeyCode::Down => {
+                if self.member_selected + 1 < self.filtered_members().len() {
+                    self.member_selected += 1;
+                }
+            }
+            KeyCode::Char(c) => {
+                self.member_filter.push(c);
+                self.member_selected = 0;
+            }
+            KeyCode::Backspace => {
+                self.member_filter.pop();
+                self.member_selected = 0;
+            }
+            _ => {}
+        }
+    }
+
+    // --- Knock requests ---
+
+    async fn open_knocks(&mut self) {
+        if self.blocked_read_only() {
+            return;
+        }
+        let (room_id, account_id) = match (&self.active_room, &self.active_account_id) {
+            (Some(r), Some(a)) => (r.clone(), a.clone()),
+            _ => return,
+        };
+        let Some(account) = self.accounts.iter().find(|a| a.user_id == account_id) else {
+            return;
+        };
+        match account.room_knocks(&room_id).await {
+            Ok(knocks) => {
+                self.knocks = knocks;
+                self.knock_selected = 0;
+                self.overlay = Overlay::Knocks;
+            }
+            Err(e) => {
+                self.status_msg = format!("Failed to load join requests: {}", e);
+            }
+        }
+    }
+
+    async fn handle_knocks_key(&mut self, key: KeyEvent) {
+        match key.code {
+            KeyCode::Esc => {
+                self.overlay = Overlay::None;
+            }
+            KeyCode::Up => {
+                self.knock_selected = self.knock_selected.saturating_sub(1);
+            }
+            KeyCode::Down => {
+                if self.knock_selected + 1 < self.knocks.len() {
+                    self.knock_selected += 1;
+                }
+            }
+            KeyCode::Enter | KeyCode::Char('a') => self.resolve_knock(true).await,
+            KeyCode::Char('d') => self.resolve_knock(false).await,
+            _ => {}
+        }
+    }
+
+    /// Approve (invite) or deny (kick) the selected knock
+    async fn resolve_knock(&mut self, approve: bool) {
+        let (room_id, account_id) = match (&self.active_room, &self.active_account_id) {
+            (Some(r), Some(a)) => (r.clone(), a.clone()),
+            _ => return,
+        };
+        let Some(user_id) = self.knocks.get(self.knock_selected).map(|k| k.user_id.clone()) else {
+            return;
+        };
+        let Some(account) = self.accounts.iter().find(|a| a.user_id == account_id) else {
+            return;
+        };
+        let result = if approve {
+            account.approve_knock(&room_id, &user_id).await
+        } else {
+            account.deny_knock(&room_id, &user_id).await
+        };
+        match result {
+            Ok(()) => {
+                self.knocks.retain(|k| k.user_id != user_id);
+                self.knock_selected = self.knock_selected.min(self.knocks.len().saturating_sub(1));
+                self.status_msg = format!(
+                    "{} {}",
+                    if approve { "Let in" } else { "Turned away" },
+                    user_id
+                );
+            }
+            Err(e) => {
+                self.status_msg = format!("Failed to update {}: {}", user_id, e);
+            }
+        }
+    }
+
+    // --- Device review before the first encrypted send ---
+
+    /// Every send in the active room goes through here: refused in read-only rooms,
+    /// and held for review the first time an encrypted room has unverified devices.
+    /// `typed` is the composer text to restore if a held send is cancelled.
+    async fn send_outgoing(&mut self, outgoing: Outgoing, typed: Option<String>) {
+        if self.blocked_read_only() {
+            return;
+        }
+        let (room_id, account_id) = match (&self.active_room, &self.active_account_id) {
+            (Some(r), Some(a)) => (r.clone(), a.clone()),
+            _ => return,
+        };
+        if let Some(devices) = self.unreviewed_devices(&account_id, &room_id).await {
+            self.device_review = Some(DeviceReview {
+                room_id,
+                devices,
+                outgoing,
+                typed,
+                listing: false,
+                scroll: 0,
+            });
+            self.overlay = Overlay::DeviceReview;
+            return;
+        }
+        self.dispatch_outgoing(outgoing).await;
+    }
+
+    async fn dispatch_outgoing(&mut self, outgoing: Outgoing) {
+        match outgoing {
+            Outgoing::Text(body) => self.send_current_message(&body).await,
+            Outgoing::Reply(body, (event_id, sender, _)) => {
+                self.send_reply_message(&body, &event_id, &sender).await;
+            }
+            Outgoing::Code(code) => self.send_code_block(&code).await,
+            Outgoing::File(path) => self.send_file_attachment(&path).await,
+        }
+    }
+
+    /// The devices of an encrypted room, if some are unverified and this is our first
+    /// send there this session. Each room is only checked once.
+    async fn unreviewed_devices(
+        &mut self,
+        account_id: &str,
+        room_id: &OwnedRoomId,
+    ) -> Option<Vec<RoomDevice>> {
+        if !self.device_checked.insert(room_id.clone()) {
+            return None;
+        }
+        let account = self.accounts.iter().find(|a| a.user_id == account_id)?;
+        match account.room_devices(room_id).await {
+            Ok(devices) if devices.iter().any(|d| !d.verified) => Some(devices),
+            Ok(_) => None,
+            Err(e) => {
+                warn!("Device check failed for {}: {}", room_id, e);
+                None
+            }
+        }
+    }
+
+    async fn handle_device_review_key(&mut self, key: KeyEvent) {
+        let Some(ref mut review) = self.device_review else {
+            self.overlay = Overlay::None;
+            return;
+        };
+        match key.code {
+            KeyCode::Esc => {
+                // Put typed text back and ask again on the next send
+                let Some(review) = self.device_review.take() else {
+                    return;
+                };
+                self.device_checked.remove(&review.room_id);
+                if let Some(typed) = review.typed {
+                    self.cursor_pos = typed.len();
+                    self.input = typed;
+                    if let Outgoing::Reply(_, to) = review.outgoing {
+                        self.replying_to = Some(to);
+                    }
+                }
+                self.overlay = Overlay::None;
+                self.status_msg = "Not sent".to_string();
+            }
+            KeyCode::Char('r') => {
+                review.listing = !review.listing;
+                review.scroll = 0;
+            }
+            KeyCode::Up if review.listing => {
+                review.scroll = review.scroll.saturating_sub(1);
+            }
+            KeyCode::Down if review.listing => {
+                if review.scroll + 1 < review.unverified().count() {
+                    review.scroll += 1;
+                }
+            }
+            KeyCode::Char('t') => self.trust_and_send_reviewed().await,
+            KeyCode::Enter | KeyCode::Char('p') => self.send_reviewed().await,
+            _ => {}
+        }
+    }
+
+    /// Trust every unverified device in the held room, then send
+    async fn trust_and_send_reviewed(&mut self) {
+        let Some(ref review) = self.device_review else {
+            return;
+        };
+        let Some(account) = self
+            .active_account_id
+            .as_ref()
+            .and_then(|aid| self.accounts.iter().find(|a| &a.user_id == aid))
+        else {
+            return;
+        };
+        let unverified: Vec<RoomDevice> = review.unverified().cloned().collect();
+        let result = account.trust_devices(&unverified).await;
+        match result {
+            Ok(()) => {
+                self.status_msg = format!("Trusted {} devices", unverified.len());
+                self.send_reviewed().await;
+            }
+            Err(e) => self.status_msg = format!("Failed to trust devices: {}", e),
+        }
+    }
+
+    /// Send the held message to the room it was written for
+    async fn send_reviewed(&mut self) {
+        let Some(review) = self.device_review.take() else {
+            return;
+        };
+        self.overlay = Overlay::None;
+        if self.active_room.as_ref() != Some(&review.room_id) {
+            self.status_msg = "Room changed \u{2014} held message not sent".to_string();
+            return;
+        }
+        self.dispatch_outgoing(review.outgoing).await;
+    }
+
+    /// Select the most recent message from a user, paginating backwards if needed
+    async fn jump_to_last_message_from(&mut self, user_id: &str) {
+        const MAX_PAGES: usize = 10;
+        let room_id = match &self.active_room {
+            Some(id) => id.clone(),
+            None => return,
+        };
+        for _ in 0..=MAX_PAGES {
+            if let Some(idx) = self.messages.iter().rposition(|m| m.sender == user_id) {
+                self.jump_to_message(idx);
+                self.status_msg = format!("Last message from {}", user_id);
+                return;
+            }
+            if !matches!(self.room_history_tokens.get(&room_id), Some(Some(_))) {
+                break;
+            }
+            let before = self.messages.len();
+            self.fetch_older_messages().await;
+            if self.messages.len() == before {
+                break;
+            }
+        }
+        self.status_msg = format!("No recent messages from {}", user_id);
+    }
+
+    // --- Message search ---
+
+    /// Indices of messages in the active room matching the search query
+    pub fn search_matches(&self) -> Vec<usize> {
+        if self.search_query.is_empty() {
+            return Vec::new();
+        }
+        let q = self.search_query.to_lowercase();
+        self.messages
+            .iter()
+            .enumerate()
+            .filter(|(_, m)| self.is_message_visible(m))
+            .filter(|(_, m)| m.body_text().to_lowercase().contains(&q))
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    /// Whether this message is where another of our clients last read up to.
+    /// Hidden once this client has sent a receipt for the same event.
+    pub fn is_read_elsewhere(&self, msg: &DisplayMessage) -> bool {
+        match (&msg.event_id, &self.own_read_receipt) {
+            (Some(eid), Some(read)) => eid == read && self.last_receipt.as_ref() != Some(read),
+            _ => false,
+        }
+    }
+
+    /// Quoted original shown above a reply: a snippet, or the full text if expanded
+    pub fn reply_quote(&self, msg: &DisplayMessage) -> String {
+        let body = msg.reply_to_body.as_deref().unwrap_or("");
+        let expanded = msg
+            .event_id
+            .as_ref()
+            .is_some_and(|id| self.expanded_replies.contains(id));
+        if expanded {
+            body.replace('\n', " ")
+        } else {
+            reply_snippet(body, self.config.reply_snippet_len)
+        }
+    }
+
+    /// Full text of the original shown under the selected reply, when its quote was cut short
+    pub fn reply_preview(&self, idx: usize) -> Option<&str> {
+        if self.selected_message != Some(idx) {
+            return None;
+        }
+        let msg = self.messages.get(idx)?;
+        let body = msg.reply_to_body.as_deref()?;
+        let expanded = msg
+            .event_id
+            .as_ref()
+            .is_some_and(|id| self.expanded_replies.contains(id));
+        let truncated =
+            body.contains('\n') || body.chars().count() > self.config.reply_snippet_len;
+        (!expanded && truncated).then_some(body)
+    }
+
+    /// Whether a message survives the room's saved timeline filters; messages that
+    /// don't are dropped when loading from sync or history. Our own always pass.
+    fn passes_room_filters(&self, room_id: &OwnedRoomId, msg: &DisplayMessage) -> bool {
+        if self.accounts.iter().any(|a| a.user_id == msg.sender) {
+            return true;
+        }
+        let filters = self.config.room_settings(room_id.as_str()).filters;
+        let matches_any = |patterns: &[String]| {
+            patterns.iter().any(|p| crate::config::wildcard_match(p, &msg.sender))
+        };
+        !((filters.hide_notices && msg.notice)
+            || (filters.hide_media && !matches!(msg.content, MessageContent::Text(_)))
+            || (filters.hide_bots
+                && (self.config.is_bot(&msg.sender) || matches_any(&self.config.bot_patterns)))
+            || (filters.hide_bridges && matches_any(&self.config.bridge_patterns)))
+    }
+
+    /// Drop loaded messages hidden by the active room's timeline filters
+    fn filter_active_messages(&mut self) {
+        let Some(room_id) = self.active_room.clone() else {
+            return;
+        };
+        let mut msgs = std::mem::take(&mut self.messages);
+        msgs.retain(|m| self.passes_room_filters(&room_id, m));
+        self.messages = msgs;
+    }
+
+    /// Reorder the loaded timeline by origin_server_ts if the room asks for it,
+    /// keeping the same message selected
+    fn sort_active_by_time(&mut self) {
+        let enabled = self
+            .active_room
+            .as_ref()
+            .is_some_and(|id| self.config.room_settings(id.as_str()).sort_by_time);
+        if !enabled {
+            return;
+        }
+        // Undecryptable placeholders have no timestamp; they stay after the message before them
+        let mut last = 0;
+        let keys: Vec<u64> = self
+            .messages
+            .iter()
+            .map(|m| {
+                if m.timestamp != 0 {
+                    last = m.timestamp;
+                }
+                last
+            })
+            .collect();
+        if keys.is_sorted() {
+            return;
+        }
+        let mut order: Vec<usize> = (0..self.messages.len()).collect();
+        // Stable, so events from the same second keep the server's order
+        order.sort_by_key(|&i| keys[i]);
+        self.selected_message = self
+            .selected_message
+            .and_then(|sel| order.iter().position(|&i| i == sel));
+        let mut old: Vec<Option<DisplayMessage>> =
+            std::mem::take(&mut self.messages).into_iter().map(Some).collect();
+        self.messages = order.iter().filter_map(|&i| old[i].take()).collect();
+    }
+
+    /// Labels and current state of the active room's timeline filters
+    pub fn timeline_filter_items(&self) -> Vec<(&'static str, bool)> {
+        let filters = self
+            .active_room
+            .as_ref()
+            .map(|id| self.config.room_settings(id.as_str()).filters)
+            .unwrap_or_default();
+        vec![
+            ("Hide notices", filters.hide_notices),
+            ("Hide bots", filters.hide_bots),
+            ("Hide media", filters.hide_media),
+            ("Hide bridge users", filters.hide_bridges),
+        ]
+    }
+
+    async fn handle_timeline_filters_key(&mut self, key: KeyEvent) {
+        match key.code {
+            KeyCode::Up => {
+                self.filter_selected = self.filter_selected.saturating_sub(1);
+            }
+            KeyCode::Down => {
+                if self.filter_selected + 1 < self.timeline_filter_items().len() {
+                    self.filter_selected += 1;
+                }
+            }
+            KeyCode::Enter | KeyCode::Char(' ') => {
+                let Some(room_id) = self.active_room.clone() else {
+                    return;
+                };
+                let key = room_id.to_string();
+                let mut settings = self.config.room_settings(&key);
+                let flag = match self.filter_selected {
+                    0 => &mut settings.filters.hide_notices,
+                    1 => &mut settings.filters.hide_bots,
+                    2 => &mut settings.filters.hide_media,
+                    _ => &mut settings.filters.hide_bridges,
+                };
+                *flag = !*flag;
+                self.config.room_settings.insert(key, settings);
+                if let Err(e) = self.config.save() {
+                    error!("Failed to save config: {}", e);
+                }
+                self.filters_changed = true;
+            }
+            KeyCode::Esc => {
+                self.overlay = Overlay::None;
+                if self.filters_changed {
+                    self.reload_active_room().await;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Rebuild the open room's timeline, e.g. after its filters change
+    async fn reload_active_room(&mut self) {
+        let Some(room_id) = self.active_room.clone() else {
+            return;
+        };
+        if self.peeking.is_some() {
+            // Peeks aren't re-fetched; just drop what the filters now hide
+            self.filter_active_messages();
+            return;
+        }
+        if let Some(idx) = self.all_rooms.iter().position(|r| r.id == room_id) {
+            // Drop the cache so hidden messages aren't kept and shown ones come back
+            self.messages.clear();
+            self.room_messages.remove(&room_id);
+            self.selected_room = idx;
+            self.open_selected_room().await;
+        }
+    }
+
+    /// Whether a message passes the active timeline filters
+    pub fn is_message_visible(&self, msg: &DisplayMessage) -> bool {
+        self.sender_filter
+            .as_ref()
+            .is_none_or(|sender| &msg.sender == sender)
+    }
+
+    /// Nearest visible message before `idx`
+    fn prev_visible_message(&self, idx: usize) -> Option<usize> {
+        (0..idx)
+            .rev()
+            .find(|&i| self.is_message_visible(&self.messages[i]))
+    }
+
+    /// Nearest visible message after `idx`
+    fn next_visible_message(&self, idx: usize) -> Option<usize> {
+        (idx + 1..self.messages.len()).find(|&i| self.is_message_visible(&self.messages[i]))
+    }
+
+    /// Show only messages from `sender`, or clear the filter if it's already active
+    fn toggle_sender_filter(&mut self, sender: &str) {
+        if self.sender_filter.as_deref() == Some(sender) {
+            self.sender_filter = None;
+            self.status_msg = "Showing all messages".to_string();
+        } else {
+            self.sender_filter = Some(sender.to_string());
+            self.status_msg = format!("Showing only messages from {}", sender);
+            // Keep the selection on a message that is still shown
+            if let Some(sel) = self.selected_message {
+                if !self.is_message_visible(&self.messages[sel]) {
+                    match self
+                        .prev_visible_message(sel)
+                        .or_else(|| self.next_visible_message(sel))
+                    {
+                        Some(idx) => self.jump_to_message(idx),
+                        None => self.selected_message = None,
+                    }
+                }
+            }
+        }
+    }
+
+    fn handle_search_key(&mut self, key: KeyEvent) {
+        match key.code {
+            KeyCode::Enter => {
+                self.search_active = false;
+                // Jump to the newest match
+                if let Some(&idx) = self.search_matches().last() {
+                    self.jump_to_message(idx);
+                } else if !self.search_query.is_empty() {
+                    self.status_msg = format!("No matches for \"{}\"", self.search_query);
+                }
+            }
+            KeyCode::Esc => {
+                self.search_active = false;
+                self.search_query.clear();
+            }
+            KeyCode::Char(c) => self.search_query.push(c),
+            KeyCode::Backspace => {
+                self.search_query.pop();
+            }
+            _ => {}
+        }
+    }
+
+    /// Move the selection to the next older (or newer) search match
+    fn step_search_match(&mut self, older: bool) {
+        let matches = self.search_matches();
+        let current = self.selected_message.unwrap_or(self.messages.len());
+        let target = if older {
+            matches.iter().rev().find(|&&i| i < current).copied()
+        } else {
+            matches.iter().find(|&&i| i > current).copied()
+        };
+        match target {
+            Some(idx) => self.jump_to_message(idx),
+            None => {
+                self.status_msg = if older {
+                    "No older matches".to_string()
+                } else {
+                    "No newer matches".to_string()
+                };
+            }
+        }
+    }
+
+    /// Select a message and scroll so it's inside the viewport
+    fn jump_to_message(&mut self, idx: usize) {
+        if idx >= self.messages.len() {
+            return;
+        }
+        self.selected_message = Some(idx);
+        let viewport = self.chat_viewport_msgs.get().max(1);
+        let end = self.messages.len().saturating_sub(self.scroll_offset);
+        let start = end.saturating_sub(viewport);
+        if idx < start || idx >= end {
+            self.scroll_offset = self.messages.len() - idx - 1;
+        }
+    }
+
+    async fn handle_chat_key(&mut self, key: KeyEvent) {
+        let viewport = self.chat_viewport_msgs.get().max(1);
+
+        if self.search_active {
+            self.handle_search_key(key);
+            return;
+        }
+
+        match key.code {
+            KeyCode::Up => {
+                if self.messages.is_empty() {
+                    return;
+                }
                 match self.selected_message {
-                    Some(idx) if idx + 1 < self.messages.len() => {
-                        let new_idx = idx + 1;
-                        self.selected_message = Some(new_idx);
-                        // Only scroll if selection would go below the visible area
-                        let end = self.messages.len().saturating_sub(self.scroll_offset);
-                        if new_idx >= end {
-                            self.scroll_offset = self.scroll_offset.saturating_sub(1);
+                    None => {
+                        // Start selecting from the bottom
+                        match self.prev_visible_message(self.messages.len()) {
+                            Some(idx) => self.selected_message = Some(idx),
+                            None => self.fetch_older_messages().await,
                         }
-                    }
-                    Some(_) => {
-                        // At bottom — deselect, return to live view
-                        self.selected_message = None;
                         self.scroll_offset = 0;
                     }
-                    None => {}
+                    Some(idx) => match self.prev_visible_message(idx) {
+                        Some(new_idx) => {
+                            self.selected_message = Some(new_idx);
+                            // Only scroll if selection would go above the visible area
+                            let end = self.messages.len().saturating_sub(self.scroll_offset);
+                            let start = end.saturating_sub(viewport);
+                            if new_idx < start {
+                                self.scroll_offset =
+                                    self.scroll_offset.saturating_add(idx - new_idx);
+                            }
+                        }
+                        None => {
+                            // At top — try to load older messages
+                            self.fetch_older_messages().await;
+                        }
+                    },
+                }
+            }
+            KeyCode::Down => {
+                if let Some(idx) = self.selected_message {
+                    match self.next_visible_message(idx) {
+                        Some(new_idx) => {
+                            self.selected_message = Some(new_idx);
+                            // Only scroll if selection would go below the visible area
+                            let end = self.messages.len().saturating_sub(self.scroll_offset);
+                            if new_idx >= end {
+                                self.scroll_offset =
+                                    self.scroll_offset.saturating_sub(new_idx - idx);
+                            }
+                        }
+                        None => {
+                            // At bottom — deselect, return to live view
+                            self.selected_message = None;
+                            self.scroll_offset = 0;
+                        }
+                    }
                 }
             }
             KeyCode::Enter => {
@@ -1733,72 +4128,313 @@ impl App {
                     self.scroll_offset = self.messages.len().saturating_sub(viewport);
                 }
             }
-            KeyCode::End => {
-                self.selected_message = None;
-                self.scroll_offset = 0;
+            KeyCode::End => {
+                self.selected_message = None;
+                self.scroll_offset = 0;
+            }
+            KeyCode::Tab => self.focus = Focus::Input,
+            KeyCode::BackTab => self.focus = Focus::Rooms,
+            KeyCode::Left => self.focus = Focus::Rooms,
+            KeyCode::Esc => {
+                if !self.search_query.is_empty() {
+                    self.search_query.clear();
+                } else if self.sender_filter.is_some() {
+                    self.sender_filter = None;
+                    self.status_msg = "Showing all messages".to_string();
+                } else if self.selected_message.is_some() {
+                    self.selected_message = None;
+                    self.scroll_offset = 0;
+                } else {
+                    self.focus = Focus::Rooms;
+                }
+            }
+            KeyCode::Char('?') => self.overlay = Overlay::Help,
+            // Members of a room we haven't joined aren't loaded
+            KeyCode::Char('m') if self.peeking.is_some() => {
+                self.blocked_read_only();
+            }
+            KeyCode::Char('/') => {
+                self.search_active = true;
+                self.search_query.clear();
+            }
+            KeyCode::Char('n') if !self.search_query.is_empty() => {
+                self.step_search_match(true);
+            }
+            KeyCode::Char('N') if !self.search_query.is_empty() => {
+                self.step_search_match(false);
+            }
+            KeyCode::Char('r') => {
+                // Reply to selected message (auto-select last if none selected)
+                let idx = self.selected_message.or_else(|| {
+                    if !self.messages.is_empty() { Some(self.messages.len() - 1) } else { None }
+                });
+                if let Some(idx) = idx {
+                    self.start_reply(idx);
+                }
+            }
+            KeyCode::Char('e') => {
+                // React to selected message (auto-select last if none selected)
+                let idx = self.selected_message.or_else(|| {
+                    if !self.messages.is_empty() { Some(self.messages.len() - 1) } else { None }
+                });
+                if let Some(idx) = idx {
+                    self.open_emoji_picker(idx);
+                }
+            }
+            KeyCode::Char('E') => {
+                self.react_with_last_used().await;
+            }
+            KeyCode::Char('m') => {
+                self.open_member_list().await;
+            }
+            KeyCode::Char('K') => {
+                self.open_knocks().await;
+            }
+            KeyCode::Char('F') if self.active_room.is_some() => {
+                self.filter_selected = 0;
+                self.filters_changed = false;
+                self.overlay = Overlay::TimelineFilters;
+            }
+            KeyCode::Char('z') => {
+                // Expand / collapse the quoted original of the selected reply
+                let eid = self
+                    .selected_message
+                    .and_then(|i| self.messages.get(i))
+                    .filter(|m| m.reply_to_body.is_some())
+                    .and_then(|m| m.event_id.clone());
+                if let Some(eid) = eid {
+                    if !self.expanded_replies.remove(&eid) {
+                        self.expanded_replies.insert(eid);
+                    }
+                }
+            }
+            KeyCode::Char('o') => {
+                // Only show messages from the selected (or last) message's sender
+                if self.sender_filter.is_some() {
+                    self.sender_filter = None;
+                    self.status_msg = "Showing all messages".to_string();
+                } else {
+                    let idx = self.selected_message.or_else(|| self.messages.len().checked_sub(1));
+                    let sender = idx
+                        .and_then(|i| self.messages.get(i))
+                        .map(|m| m.sender.clone());
+                    if let Some(sender) = sender {
+                        self.toggle_sender_filter(&sender);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Start composing a reply to a message
+    fn start_reply(&mut self, idx: usize) {
+        if self.blocked_read_only() {
+            return;
+        }
+        if let Some(msg) = self.messages.get(idx) {
+            if let Some(ref eid) = msg.event_id {
+                self.replying_to =
+                    Some((eid.clone(), msg.sender.clone(), msg.body_text().to_string()));
+                self.focus = Focus::Input;
+            }
+        }
+    }
+
+    /// Open the reaction picker for a message
+    fn open_emoji_picker(&mut self, idx: usize) {
+        if self.blocked_read_only() {
+            return;
+        }
+        if let Some(msg) = self.messages.get(idx) {
+            if let Some(ref eid) = msg.event_id {
+                self.emoji_picker_event_id = Some(eid.clone());
+                self.emoji_picker_selected = 0;
+                self.overlay = Overlay::EmojiPicker;
+            }
+        }
+    }
+
+    // --- Mouse ---
+
+    async fn handle_mouse(&mut self, mouse: MouseEvent) {
+        let pos = (mouse.column, mouse.row);
+        self.mouse_pos = Some(pos);
+        if self.overlay != Overlay::None {
+            return;
+        }
+
+        let in_chat = self.message_area.get().contains(Position::new(pos.0, pos.1));
+        let action = match mouse.kind {
+            MouseEventKind::Down(MouseButton::Left) => {
+                let now = std::time::Instant::now();
+                let double = self.last_click.is_some_and(|(t, p)| {
+                    p == pos && now.duration_since(t) < std::time::Duration::from_millis(400)
+                });
+                self.last_click = if double { None } else { Some((now, pos)) };
+                if double {
+                    &self.config.mouse.double_click
+                } else {
+                    &self.config.mouse.click
+                }
+            }
+            MouseEventKind::Down(MouseButton::Middle) => &self.config.mouse.middle_click,
+            MouseEventKind::ScrollUp if in_chat => {
+                if self.scroll_offset + 1 >= self.messages.len() {
+                    self.fetch_older_messages().await;
+                } else {
+                    self.scroll_offset = (self.scroll_offset + 3).min(self.messages.len() - 1);
+                }
+                return;
+            }
+            MouseEventKind::ScrollDown if in_chat => {
+                self.scroll_offset = self.scroll_offset.saturating_sub(3);
+                return;
+            }
+            _ => return,
+        };
+        let action = MouseAction::from_str(action);
+
+        let room = Self::hit(&self.room_hits.borrow(), pos);
+        let message = Self::hit(&self.message_hits.borrow(), pos);
+        if let Some(idx) = room {
+            self.room_mouse_action(idx, action).await;
+        } else if let Some(idx) = message {
+            self.message_mouse_action(idx, action);
+        }
+    }
+
+    /// Index recorded for the row under the pointer, if any
+    pub fn hit(hits: &[(Rect, usize)], (x, y): (u16, u16)) -> Option<usize> {
+        hits.iter()
+            .find(|(rect, _)| rect.contains(Position::new(x, y)))
+            .map(|(_, idx)| *idx)
+    }
+
+    async fn room_mouse_action(&mut self, idx: usize, action: MouseAction) {
+        if action == MouseAction::None || idx >= self.all_rooms.len() {
+            return;
+        }
+        self.selected_room = idx;
+        self.focus = Focus::Rooms;
+        if action == MouseAction::Open {
+            self.open_selected_room().await;
+        }
+    }
+
+    fn message_mouse_action(&mut self, idx: usize, action: MouseAction) {
+        if idx >= self.messages.len() {
+            return;
+        }
+        match action {
+            MouseAction::Select => {
+                self.focus = Focus::Chat;
+                self.selected_message = Some(idx);
+            }
+            MouseAction::Open => {
+                self.focus = Focus::Chat;
+                self.selected_message = Some(idx);
+                self.open_message_action();
+            }
+            MouseAction::Reply => {
+                self.selected_message = Some(idx);
+                self.start_reply(idx);
+            }
+            MouseAction::React => {
+                self.focus = Focus::Chat;
+                self.selected_message = Some(idx);
+                self.open_emoji_picker(idx);
             }
-            KeyCode::Tab => self.focus = Focus::Input,
-            KeyCode::BackTab => self.focus = Focus::Rooms,
-            KeyCode::Left => self.focus = Focus::Rooms,
-            KeyCode::Esc => {
-                if self.selected_message.is_some() {
-                    self.selected_message = None;
-                    self.scroll_offset = 0;
-                } else {
-                    self.focus = Focus::Rooms;
-                }
+            MouseAction::None => {}
+        }
+    }
+
+    /// Apply the most recently used reaction to the selected (or last) message
+    async fn react_with_last_used(&mut self) {
+        if self.blocked_read_only() {
+            return;
+        }
+        let Some(emoji) = self.config.last_reaction.clone() else {
+            self.status_msg = "No recent reaction \u{2014} press 'e' to pick one".to_string();
+            return;
+        };
+        let idx = self.selected_message.or_else(|| self.messages.len().checked_sub(1));
+        let Some(event_id) = idx
+            .and_then(|i| self.messages.get(i))
+            .and_then(|m| m.event_id.clone())
+        else {
+            return;
+        };
+        let (room_id, account_id) = match (&self.active_room, &self.active_account_id) {
+            (Some(r), Some(a)) => (r.clone(), a.clone()),
+            _ => return,
+        };
+        if let Some(account) = self.accounts.iter().find(|a| a.user_id == account_id) {
+            if let Err(e) = account.send_reaction(&room_id, &event_id, &emoji).await {
+                self.status_msg = format!("Reaction failed: {}", e);
             }
-            KeyCode::Char('?') => self.overlay = Overlay::Help,
-            KeyCode::Char('r') => {
-                // Reply to selected message (auto-select last if none selected)
-                let idx = self.selected_message.or_else(|| {
-                    if !self.messages.is_empty() { Some(self.messages.len() - 1) } else { None }
-                });
-                if let Some(idx) = idx {
-                    if let Some(msg) = self.messages.get(idx) {
-                        if let Some(ref eid) = msg.event_id {
-                            let body = msg.body_text();
-                            let snippet = if body.len() > 50 {
-                                format!("{}...", &body[..50])
-                            } else {
-                                body.to_string()
-                            };
-                            self.replying_to = Some((
-                                eid.clone(),
-                                msg.sender.clone(),
-                                snippet,
-                            ));
-                            self.focus = Focus::Input;
-                        }
+        }
+    }
+
+    async fn handle_input_key(&mut self, key: KeyEvent) {
+        if self.completion.is_some() {
+            match key.code {
+                KeyCode::Tab | KeyCode::Enter => {
+                    self.accept_completion();
+                    return;
+                }
+                KeyCode::Up => {
+                    if let Some(ref mut c) = self.completion {
+                        c.selected = c.selected.saturating_sub(1);
                     }
+                    return;
                 }
-            }
-            KeyCode::Char('e') => {
-                // React to selected message (auto-select last if none selected)
-                let idx = self.selected_message.or_else(|| {
-                    if !self.messages.is_empty() { Some(self.messages.len() - 1) } else { None }
-                });
-                if let Some(idx) = idx {
-                    if let Some(msg) = self.messages.get(idx) {
-                        if let Some(ref eid) = msg.event_id {
-                            self.emoji_picker_event_id = Some(eid.clone());
-                            self.emoji_picker_selected = 0;
-                            self.overlay = Overlay::EmojiPicker;
+                KeyCode::Down => {
+                    if let Some(ref mut c) = self.completion {
+                        if c.selected + 1 < c.items.len() {
+                            c.selected += 1;
                         }
                     }
+                    return;
+                }
+                KeyCode::Esc => {
+                    self.completion = None;
+                    return;
                 }
+                _ => {}
             }
-            _ => {}
         }
+        self.handle_input_edit_key(key).await;
+        self.update_completion();
     }
 
-    async fn handle_input_key(&mut self, key: KeyEvent) {
+    async fn handle_input_edit_key(&mut self, key: KeyEvent) {
         match key.code {
             KeyCode::Enter => {
-                if !self.input.is_empty() {
-                    let msg = self.input.clone();
+                if let Some(event_id) = self.composer_edit.take() {
+                    let body = self.input.clone();
+                    self.input.clear();
+                    self.cursor_pos = 0;
+                    if body.trim().is_empty() {
+                        self.status_msg = "Edit cancelled".to_string();
+                    } else {
+                        self.send_composer_edit(&event_id, &body).await;
+                    }
+                } else if self.input.starts_with('/') && !self.input.starts_with("//") {
+                    let cmd = self.input.clone();
                     self.input.clear();
                     self.cursor_pos = 0;
+                    self.run_command(&cmd).await;
+                } else if !self.input.is_empty() && self.blocked_read_only() {
+                    // Text stays in the composer
+                } else if !self.input.is_empty() {
+                    let typed = std::mem::take(&mut self.input);
+                    // A leading "//" sends a literal slash
+                    let msg = match typed.strip_prefix('/') {
+                        Some(rest) if rest.starts_with('/') => rest.to_string(),
+                        _ => typed.clone(),
+                    };
+                    self.cursor_pos = 0;
                     self.last_typing_sent = None;
                     // Send typing=false (non-blocking)
                     if let (Some(ref room_id), Some(ref aid)) =
@@ -1814,11 +4450,11 @@ impl App {
                             });
                         }
                     }
-                    if let Some((reply_eid, reply_sender, _)) = self.replying_to.take() {
-                        self.send_reply_message(&msg, &reply_eid, &reply_sender).await;
-                    } else {
-                        self.send_current_message(&msg).await;
-                    }
+                    let outgoing = match self.replying_to.take() {
+                        Some(to) => Outgoing::Reply(msg, to),
+                        None => Outgoing::Text(msg),
+                    };
+                    self.send_outgoing(outgoing, Some(typed)).await;
                 }
             }
             KeyCode::Char(c) => {
@@ -1864,29 +4500,561 @@ impl App {
                     self.cursor_pos += 1;
                 }
             }
-            KeyCode::Home => self.cursor_pos = 0,
-            KeyCode::End => self.cursor_pos = self.input.len(),
+            KeyCode::Up
+                if self.input.is_empty()
+                    && self.composer_edit.is_none()
+                    && self.replying_to.is_none() =>
+            {
+                self.start_composer_edit();
+            }
+            KeyCode::Home => self.cursor_pos = 0,
+            KeyCode::End => self.cursor_pos = self.input.len(),
+            KeyCode::Esc => {
+                if self.composer_edit.take().is_some() {
+                    self.input.clear();
+                    self.cursor_pos = 0;
+                }
+                self.replying_to = None;
+                self.last_typing_sent = None;
+                // Send typing=false (non-blocking)
+                if let (Some(ref room_id), Some(ref aid)) =
+                    (self.active_room.clone(), self.active_account_id.clone())
+                {
+                    if let Some(account) = self.accounts.iter().find(|a| &a.user_id == aid) {
+                        let room_id = room_id.clone();
+                        let client = account.client.clone();
+                        tokio::spawn(async move {
+                            if let Some(room) = client.get_room(&room_id) {
+                                let _ = room.typing_notice(false).await;
+                            }
+                        });
+                    }
+                }
+                self.focus = Focus::Chat;
+            }
+            KeyCode::Tab => self.focus = Focus::Rooms,
+            _ => {}
+        }
+    }
+
+    /// Offer completions for the `@`, `#` or `/` word under the cursor, if any
+    fn update_completion(&mut self) {
+        if self.focus != Focus::Input {
+            self.completion = None;
+            return;
+        }
+        let before = self.input.get(..self.cursor_pos).unwrap_or("");
+        let start = before.rfind(char::is_whitespace).map(|i| i + 1).unwrap_or(0);
+        let word = &before[start..];
+        let needle = word.get(1..).unwrap_or("").to_lowercase();
+        let mut items: Vec<(String, String)> = match word.chars().next() {
+            // Commands only at the start of the composer
+            Some('/') if start == 0 && !word.starts_with("//") => SLASH_COMMANDS
+                .iter()
+                .filter(|(name, _)| name.starts_with(&needle))
+                .map(|(name, hint)| (format!("/{}", name), hint.to_string()))
+                .collect(),
+            Some('#') => {
+                let mut aliases: Vec<(String, String)> = Vec::new();
+                for room in &self.all_rooms {
+                    let alias = self
+                        .accounts
+                        .iter()
+                        .find(|a| a.user_id == room.account_id)
+                        .and_then(|a| a.room_alias(&room.id));
+                    if let Some(alias) = alias {
+                        if alias.to_lowercase().contains(&needle)
+                            && !aliases.iter().any(|(a, _)| *a == alias)
+                        {
+                            aliases.push((alias, room.name.clone()));
+                        }
+                    }
+                }
+                aliases
+            }
+            Some('@') => {
+                // Members fetched for the member list, then anyone who spoke recently
+                let mut users: Vec<(String, String)> = Vec::new();
+                let known = self
+                    .members
+                    .iter()
+                    .map(|m| (m.user_id.clone(), m.display_name.clone().unwrap_or_default()))
+                    .chain(self.messages.iter().rev().map(|m| (m.sender.clone(), String::new())));
+                for (user_id, name) in known {
+                    if (user_id.to_lowercase().contains(&needle)
+                        || name.to_lowercase().contains(&needle))
+                        && !users.iter().any(|(u, _)| *u == user_id)
+                    {
+                        users.push((user_id, name));
+                    }
+                }
+                users
+            }
+            _ => Vec::new(),
+        };
+        items.truncate(8);
+        // Nothing left to complete once the word matches the only candidate
+        if items.is_empty() || (items.len() == 1 && items[0].0 == word) {
+            self.completion = None;
+            return;
+        }
+        let selected = match self.completion {
+            Some(ref c) if c.start == start => c.selected.min(items.len() - 1),
+            _ => 0,
+        };
+        self.completion = Some(Completion { start, items, selected });
+    }
+
+    /// Replace the word under the cursor with the selected completion
+    fn accept_completion(&mut self) {
+        let Some(c) = self.completion.take() else {
+            return;
+        };
+        let Some((text, _)) = c.items.get(c.selected) else {
+            return;
+        };
+        let replacement = format!("{} ", text);
+        self.input.replace_range(c.start..self.cursor_pos, &replacement);
+        self.cursor_pos = c.start + replacement.len();
+    }
+
+    /// Pre-fill the composer with our last text message in the room and switch to edit mode
+    fn start_composer_edit(&mut self) {
+        let Some(ref account_id) = self.active_account_id else {
+            return;
+        };
+        let last_own = self.messages.iter().enumerate().rev().find(|(_, m)| {
+            &m.sender == account_id
+                && m.event_id.is_some()
+                && matches!(m.content, MessageContent::Text(_))
+        });
+        let Some((idx, msg)) = last_own else {
+            self.status_msg = "No message of yours to edit".to_string();
+            return;
+        };
+        self.input = msg.body_text().to_string();
+        self.cursor_pos = self.input.len();
+        self.composer_edit = msg.event_id.clone();
+        self.selected_message = Some(idx);
+    }
+
+    /// Send an edit started from the composer and update the local copy
+    async fn send_composer_edit(&mut self, event_id: &str, body: &str) {
+        let (room_id, account_id) = match (&self.active_room, &self.active_account_id) {
+            (Some(r), Some(a)) => (r.clone(), a.clone()),
+            _ => return,
+        };
+        let Some(account) = self.accounts.iter().find(|a| a.user_id == account_id) else {
+            return;
+        };
+        match account.edit_message(&room_id, event_id, body).await {
+            Ok(()) => {
+                let cached = self.room_messages.get_mut(&room_id).into_iter().flatten();
+                for m in self.messages.iter_mut().chain(cached) {
+                    if m.event_id.as_deref() == Some(event_id) {
+                        m.content = MessageContent::Text(body.to_string());
+                    }
+                }
+                self.selected_message = None;
+                self.status_msg = "Message edited".to_string();
+            }
+            Err(e) => {
+                self.status_msg = format!("Edit failed: {}", e);
+            }
+        }
+    }
+
+    /// Run a slash command typed into the composer
+    async fn run_command(&mut self, input: &str) {
+        let mut parts = input[1..].splitn(2, ' ');
+        let name = parts.next().unwrap_or("");
+        let raw_arg = parts.next();
+        let arg = raw_arg.unwrap_or("").trim();
+
+        match name {
+            "markdown" | "notify" | "images" | "timesort" => {
+                let Some(room_id) = self.active_room.clone() else {
+                    self.status_msg = "No active room".to_string();
+                    return;
+                };
+                let key = room_id.to_string();
+                let mut settings = self.config.room_settings(&key);
+                let flag = match name {
+                    "markdown" => &mut settings.markdown,
+                    "notify" => &mut settings.notifications,
+                    "timesort" => &mut settings.sort_by_time,
+                    _ => &mut settings.images,
+                };
+                *flag = match arg {
+                    "on" => true,
+                    "off" => false,
+                    "" => !*flag,
+                    _ => {
+                        self.status_msg = format!("Usage: /{} [on|off]", name);
+                        return;
+                    }
+                };
+                let value = *flag;
+                let images_changed = name == "images";
+                self.config.room_settings.insert(key, settings);
+                if let Err(e) = self.config.save() {
+                    error!("Failed to save config: {}", e);
+                }
+                self.status_msg =
+                    format!("{} {} for this room", name, if value { "on" } else { "off" });
+                if images_changed {
+                    if value {
+                        self.trigger_image_downloads();
+                    } else {
+                        self.drop_room_images(&room_id);
+                    }
+                }
+                if name == "timesort" && value {
+                    self.sort_active_by_time();
+                }
+            }
+            "topic" => {
+                let (room_id, account_id) = match (&self.active_room, &self.active_account_id) {
+                    (Some(r), Some(a)) => (r.clone(), a.clone()),
+                    _ => {
+                        self.status_msg = "No active room".to_string();
+                        return;
+                    }
+                };
+                let Some(account) = self.accounts.iter().find(|a| a.user_id == account_id) else {
+                    return;
+                };
+                match raw_arg {
+                    // Bare "/topic": put the current topic back in the input for editing
+                    None => {
+                        let topic = account.get_room_topic(&room_id).unwrap_or_default();
+                        self.input = format!("/topic {}", topic);
+                        self.cursor_pos = self.input.len();
+                        self.status_msg =
+                            "Edit the topic and press Enter (leave it empty to clear)".to_string();
+                    }
+                    Some(_) => {
+                        self.status_msg = match account.set_room_topic(&room_id, arg).await {
+                            Ok(()) if arg.is_empty() => "Room topic cleared".to_string(),
+                            Ok(()) => "Room topic updated".to_string(),
+                            Err(e) => format!("Failed to set topic: {}", e),
+                        };
+                    }
+                }
+            }
+            "status" => {
+                let Some(account_id) = self.active_account_id.clone() else {
+                    self.status_msg = "No active account".to_string();
+                    return;
+                };
+                let Some(account) = self.accounts.iter().find(|a| a.user_id == account_id) else {
+                    return;
+                };
+                let status = Some(arg).filter(|a| !a.is_empty());
+                match account.set_status_message(status).await {
+                    Ok(()) => {
+                        match status {
+                            Some(msg) => {
+                                self.presence_status.insert(account_id.clone(), msg.to_string());
+                                self.status_msg = format!("Status for {}: {}", account_id, msg);
+                            }
+                            None => {
+                                self.presence_status.remove(&account_id);
+                                self.status_msg = format!("Status cleared for {}", account_id);
+                            }
+                        }
+                    }
+                    Err(e) => self.status_msg = format!("Failed to set status: {}", e),
+                }
+            }
+            "sound" => {
+                let Some(room_id) = self.active_room.clone() else {
+                    self.status_msg = "No active room".to_string();
+                    return;
+                };
+                let mut sound_args = arg.splitn(2, ' ');
+                let class = sound_args.next().unwrap_or("");
+                let sound = sound_args.next().unwrap_or("").trim();
+                if class != "dm" && class != "mention" {
+                    self.status_msg =
+                        "Usage: /sound <dm|mention> [command|bell|off] (empty resets)".to_string();
+                    return;
+                }
+                let key = room_id.to_string();
+                let mut settings = self.config.room_settings(&key);
+                *settings.sounds.get_mut(class) = (!sound.is_empty()).then(|| sound.to_string());
+                self.config.room_settings.insert(key, settings);
+                if let Err(e) = self.config.save() {
+                    error!("Failed to save config: {}", e);
+                }
+                self.status_msg = if sound.is_empty() {
+                    format!("{} sound for this room follows the global setting", class)
+                } else {
+                    format!("{} sound for this room: {}", class, sound)
+                };
+            }
+            "forget" => {
+                let (room_id, account_id) = match (&self.active_room, &self.active_account_id) {
+                    (Some(r), Some(a)) if self.removed_from.contains_key(r) => (r.clone(), a.clone()),
+                    _ => {
+                        self.status_msg =
+                            "/forget is for rooms you were removed from; leave others in the room editor"
+                                .to_string();
+                        return;
+                    }
+                };
+                let Some(account) = self.accounts.iter().find(|a| a.user_id == account_id) else {
+                    return;
+                };
+                if let Err(e) = account.forget_left_room(&room_id).await {
+                    self.status_msg = format!("Failed to forget room: {}", e);
+                    return;
+                }
+                let name = self
+                    .removed_from
+                    .remove(&room_id)
+                    .map(|r| r.room_name)
+                    .unwrap_or_else(|| room_id.to_string());
+                self.room_messages.remove(&room_id);
+                self.active_room = None;
+                self.active_account_id = None;
+                self.messages.clear();
+                self.selected_message = None;
+                self.focus = Focus::Rooms;
+                self.status_msg = format!("Forgot {}", name);
+            }
+            "broadcast" => {
+                if arg.is_empty() {
+                    self.status_msg = "Usage: /broadcast <message>".to_string();
+                    return;
+                }
+                let mut targets = self.marked_room_list();
+                // Rooms we were kicked or banned from can't be posted to
+                let marked = targets.len();
+                targets.retain(|r| !self.removed_from.contains_key(&r.id));
+                if targets.is_empty() && marked > 0 {
+                    self.status_msg = "You're no longer in any of the marked rooms".to_string();
+                    return;
+                }
+                if targets.is_empty() {
+                    self.status_msg =
+                        "Mark rooms with Space in the room list, then /broadcast <message>".to_string();
+                    return;
+                }
+                let first = targets[0].clone();
+                let devices = self.unreviewed_devices(&first.account_id, &first.id).await;
+                self.broadcast = Some(Broadcast {
+                    body: arg.to_string(),
+                    targets,
+                    next: 0,
+                    devices,
+                    in_flight: Vec::new(),
+                    sent: 0,
+                    skipped: 0,
+                    failed: Vec::new(),
+                    stopped: false,
+                });
+                self.overlay = Overlay::Broadcast;
+            }
+            "export-keymap" => {
+                let path = if arg.is_empty() {
+                    dirs::home_dir().unwrap_or_default().join("matrixtui-keymap.md")
+                } else {
+                    crate::config::expand_home(arg)
+                };
+                self.status_msg = match std::fs::write(&path, ui::help_markdown()) {
+                    Ok(()) => format!("Keymap cheat sheet written to {}", path.display()),
+                    Err(e) => format!("Export failed: {}", e),
+                };
+            }
+            "export-settings" => {
+                let path = if arg.is_empty() {
+                    dirs::home_dir().unwrap_or_default().join("matrixtui-settings.json")
+                } else {
+                    crate::config::expand_home(arg)
+                };
+                self.status_msg = match self.config.export_settings(&path) {
+                    Ok(()) => format!("Settings exported to {}", path.display()),
+                    Err(e) => format!("Export failed: {}", e),
+                };
+            }
+            "import-settings" => {
+                if arg.is_empty() {
+                    self.status_msg = "Usage: /import-settings <path>".to_string();
+                    return;
+                }
+                let path = crate::config::expand_home(arg);
+                if let Err(e) = self.config.import_settings(&path) {
+                    self.status_msg = format!("Import failed: {}", e);
+                    return;
+                }
+                if let Err(e) = self.config.save() {
+                    error!("Failed to save config: {}", e);
+                }
+                self.theme = ui::theme_by_name(&self.config.theme);
+                self.room_sort = RoomSortMode::from_str(&self.config.room_sort);
+                self.refresh_rooms().await;
+                self.status_msg = format!("Settings imported from {}", path.display());
+            }
+            "peek" => {
+                if arg.is_empty() {
+                    self.status_msg = "Usage: /peek <#alias, !room_id or matrix.to link>".to_string();
+                    return;
+                }
+                // Accept matrix.to links: https://matrix.to/#/#room:server?via=...
+                // The via servers are kept for a later /join
+                let target = arg.trim_start_matches("https://matrix.to/#/").to_string();
+                self.peek_room(&target).await;
+            }
+            "join" => {
+                let target = (!arg.is_empty()).then_some(arg);
+                self.join_and_open(target).await;
+            }
+            "export-favorites" => {
+                let path = if arg.is_empty() {
+                    dirs::home_dir().unwrap_or_default().join("matrixtui-favorites.json")
+                } else {
+                    crate::config::expand_home(arg)
+                };
+                let list = self.favorites_list();
+                self.status_msg = match list.save(&path) {
+                    Ok(()) => format!("{} favorites exported to {}", list.rooms.len(), path.display()),
+                    Err(e) => format!("Export failed: {}", e),
+                };
+            }
+            "import-favorites" => {
+                if arg.is_empty() {
+                    self.status_msg = "Usage: /import-favorites <path>".to_string();
+                    return;
+                }
+                let path = crate::config::expand_home(arg);
+                match FavoritesList::load(&path) {
+                    Ok(list) => self.import_favorites(list).await,
+                    Err(e) => self.status_msg = format!("Import failed: {}", e),
+                }
+            }
+            _ => {
+                self.status_msg = format!("Unknown command: /{}", name);
+            }
+        }
+    }
+
+    /// Current favorites in order, with aliases so others can join them
+    fn favorites_list(&self) -> FavoritesList {
+        let rooms = self
+            .config
+            .favorites
+            .iter()
+            .map(|id| {
+                let room = self.all_rooms.iter().find(|r| r.id.as_str() == id);
+                let alias = room.and_then(|r| {
+                    self.accounts
+                        .iter()
+                        .find(|a| a.user_id == r.account_id)
+                        .and_then(|a| a.room_alias(&r.id))
+                });
+                FavoriteEntry {
+                    room_id: id.clone(),
+                    alias,
+                    name: room.map(|r| r.name.clone()),
+                }
+            })
+            .collect();
+        FavoritesList { rooms }
+    }
+
+    /// Favorite the listed rooms we're already in, then offer to join the rest
+    async fn import_favorites(&mut self, list: FavoritesList) {
+        let mut added = 0;
+        let mut missing = Vec::new();
+        for entry in list.rooms {
+            let joined = self.all_rooms.iter().any(|r| r.id.as_str() == entry.room_id);
+            if !joined {
+                missing.push(entry);
+                continue;
+            }
+            if !self.config.favorites.contains(&entry.room_id) {
+                self.config.favorites.push(entry.room_id);
+                added += 1;
+            }
+        }
+        if let Err(e) = self.config.save() {
+            error!("Failed to save config: {}", e);
+        }
+        self.refresh_rooms().await;
+        self.status_msg = format!("Added {} favorites", added);
+        if !missing.is_empty() && !self.accounts.is_empty() {
+            self.favorite_joins = missing;
+            self.favorite_join_account = self.default_account_idx();
+            self.favorite_join_busy = false;
+            self.overlay = Overlay::JoinFavorites;
+        }
+    }
+
+    async fn handle_join_favorites_key(&mut self, key: KeyEvent) {
+        if self.favorite_join_busy {
+            return;
+        }
+        match key.code {
+            KeyCode::Tab if self.accounts.len() > 1 => {
+                self.favorite_join_account = (self.favorite_join_account + 1) % self.accounts.len();
+            }
+            KeyCode::Enter => self.join_favorites(),
             KeyCode::Esc => {
-                self.replying_to = None;
-                self.last_typing_sent = None;
-                // Send typing=false (non-blocking)
-                if let (Some(ref room_id), Some(ref aid)) =
-                    (self.active_room.clone(), self.active_account_id.clone())
-                {
-                    if let Some(account) = self.accounts.iter().find(|a| &a.user_id == aid) {
-                        let room_id = room_id.clone();
-                        let client = account.client.clone();
-                        tokio::spawn(async move {
-                            if let Some(room) = client.get_room(&room_id) {
-                                let _ = room.typing_notice(false).await;
-                            }
-                        });
+                self.status_msg =
+                    format!("Skipped {} rooms you haven't joined", self.favorite_joins.len());
+                self.favorite_joins.clear();
+                self.overlay = Overlay::None;
+            }
+            _ => {}
+        }
+    }
+
+    /// Join every missing room from an imported list in the background; they're
+    /// favorited when `MatrixEvent::FavoritesJoined` comes back
+    fn join_favorites(&mut self) {
+        let Some(account) = self.accounts.get(self.favorite_join_account) else {
+            return;
+        };
+        let client = account.client.clone();
+        // Kept for the overlay to list until the joins finish
+        let entries = self.favorite_joins.clone();
+        let tx = self.matrix_tx.clone();
+        self.favorite_join_busy = true;
+        tokio::spawn(async move {
+            let mut joined = Vec::new();
+            let mut failed = Vec::new();
+            for entry in &entries {
+                let target = entry.alias.as_deref().unwrap_or(&entry.room_id);
+                match crate::account::join_room(&client, target).await {
+                    Ok(room_id) => joined.push(room_id.to_string()),
+                    Err(e) => {
+                        info!("Failed to join {}: {}", target, e);
+                        failed.push(entry.name.clone().unwrap_or_else(|| target.to_string()));
                     }
                 }
-                self.focus = Focus::Chat;
             }
-            KeyCode::Tab => self.focus = Focus::Rooms,
-            _ => {}
+            let _ = tx.send(MatrixEvent::FavoritesJoined { joined, failed });
+        });
+    }
+
+    /// Forget decoded images for a room so they fall back to links
+    fn drop_room_images(&mut self, room_id: &OwnedRoomId) {
+        let active = Some(room_id) == self.active_room.as_ref();
+        let cached = self.room_messages.get_mut(room_id).into_iter().flatten();
+        let current = self.messages.iter_mut().filter(|_| active);
+        for msg in cached.chain(current) {
+            if let MessageContent::Image {
+                protocol: ref mut p,
+                image: ref mut i,
+                loading: ref mut l,
+                ..
+            } = msg.content
+            {
+                *p = None;
+                *i = None;
+                *l = false;
+            }
         }
     }
 
@@ -1908,8 +5076,15 @@ impl App {
                         (self.active_room.clone(), self.active_account_id.clone())
                     {
                         if let Some(account) = self.accounts.iter().find(|a| &a.user_id == aid) {
-                            if let Err(e) = account.send_reaction(room_id, event_id, emoji).await {
-                                self.status_msg = format!("Reaction failed: {}", e);
+                            // Only a reaction that went out becomes the one E repeats
+                            match account.send_reaction(room_id, event_id, emoji).await {
+                                Ok(()) => {
+                                    self.config.last_reaction = Some(emoji.to_string());
+                                    if let Err(e) = self.config.save() {
+                                        error!("Failed to save config: {}", e);
+                                    }
+                                }
+                                Err(e) => self.status_msg = format!("Reaction failed: {}", e),
                             }
                         }
                     }
@@ -2023,6 +5198,20 @@ impl App {
                     self.overlay = Overlay::None;
                 }
             }
+            KeyCode::Up
+                if key.modifiers.contains(KeyModifiers::SHIFT)
+                    && self.settings_accounts_open
+                    && !self.settings_account_action_open =>
+            {
+                self.move_settings_account(true);
+            }
+            KeyCode::Down
+                if key.modifiers.contains(KeyModifiers::SHIFT)
+                    && self.settings_accounts_open
+                    && !self.settings_account_action_open =>
+            {
+                self.move_settings_account(false);
+            }
             KeyCode::Up => {
                 if self.settings_verify_open {
                     self.settings_verify_selected =
@@ -2049,11 +5238,12 @@ impl App {
                         self.settings_verify_selected += 1;
                     }
                 } else if self.settings_account_action_open {
-                    if self.settings_account_action_selected < 3 {
+                    if self.settings_account_action_selected < 8 {
                         self.settings_account_action_selected += 1;
                     }
                 } else if self.settings_accounts_open {
-                    let count = 1 + self.accounts.len(); // Add Account + each account
+                    // Add Account + each active account + each disabled account
+                    let count = 1 + self.accounts.len() + self.config.disabled_accounts().len();
                     if self.settings_accounts_selected + 1 < count {
                         self.settings_accounts_selected += 1;
                     }
@@ -2066,7 +5256,7 @@ impl App {
                     if self.settings_sort_selected + 1 < RoomSortMode::ALL.len() {
                         self.settings_sort_selected += 1;
                     }
-                } else if self.settings_selected < 3 {
+                } else if self.settings_selected < 5 {
                     self.settings_selected += 1;
                 }
             }
@@ -2098,13 +5288,11 @@ impl App {
                         }
                         1 => {
                             // Remove
-                            self.remove_account_by_index(acct_idx).await;
-                            self.settings_account_action_open = false;
-                            // Clamp selection
-                            let count = 1 + self.accounts.len();
-                            if self.settings_accounts_selected >= count {
-                                self.settings_accounts_selected = count.saturating_sub(1);
+                            if let Some(account) = self.accounts.get(acct_idx) {
+                                let user_id = account.user_id.clone();
+                                self.ask_confirm(PendingConfirm::RemoveAccount(user_id));
                             }
+                            self.settings_account_action_open = false;
                         }
                         2 => {
                             // Edit Profile
@@ -2116,10 +5304,56 @@ impl App {
                             self.settings_verify_open = true;
                             self.settings_verify_selected = 0;
                         }
+                        4 => {
+                            // Disable — keep configured, stop syncing
+                            self.disable_account(acct_idx).await;
+                            self.settings_account_action_open = false;
+                        }
+                        5 => {
+                            // Set as Default
+                            if let Some(account) = self.accounts.get(acct_idx) {
+                                let user_id = account.user_id.clone();
+                                self.status_msg = format!("Default account: {}", user_id);
+                                self.config.default_account = Some(user_id);
+                                if let Err(e) = self.config.save() {
+                                    error!("Failed to save config: {}", e);
+                                }
+                            }
+                            self.settings_account_action_open = false;
+                        }
+                        6 => {
+                            // Identity Server
+                            self.settings_account_action_open = false;
+                            self.open_identity_server(acct_idx).await;
+                        }
+                        7 => {
+                            // Emails & Phones
+                            self.settings_account_action_open = false;
+                            self.open_threepids(acct_idx).await;
+                        }
+                        8 => {
+                            // Log Out
+                            if let Some(account) = self.accounts.get(acct_idx) {
+                                let user_id = account.user_id.clone();
+                                self.ask_confirm(PendingConfirm::LogOut(user_id));
+                            }
+                            self.settings_account_action_open = false;
+                        }
                         _ => {}
                     }
                 } else if self.settings_accounts_open {
-                    if self.settings_accounts_selected == 0 {
+                    if self.settings_accounts_selected > self.accounts.len() {
+                        // Disabled account — re-enable and restore it
+                        let disabled_idx = self.settings_accounts_selected - 1 - self.accounts.len();
+                        let user_id = self
+                            .config
+                            .disabled_accounts()
+                            .get(disabled_idx)
+                            .map(|a| a.user_id.clone());
+                        if let Some(user_id) = user_id {
+                            self.enable_account(&user_id).await;
+                        }
+                    } else if self.settings_accounts_selected == 0 {
                         // Add Account
                         self.overlay = Overlay::Login;
                         self.login_homeserver = "matrix.org".to_string();
@@ -2174,42 +5408,268 @@ impl App {
                         .position(|m| m == &self.room_sort)
                         .unwrap_or(0);
                 } else if self.settings_selected == 3 {
+                    // Toggle status bar clock
+                    self.config.show_clock = !self.config.show_clock;
+                    if let Err(e) = self.config.save() {
+                        error!("Failed to save config: {}", e);
+                    }
+                } else if self.settings_selected == 4 {
+                    // Diagnostics
+                    // Homeserver checks can take seconds each, so they run off the UI task
+                    self.status_msg = "Running diagnostics...".to_string();
+                    let open: Vec<String> =
+                        self.accounts.iter().map(|a| a.user_id.clone()).collect();
+                    let picker = self.picker.clone();
+                    let tx = self.matrix_tx.clone();
+                    tokio::spawn(async move {
+                        let checks = crate::doctor::run_checks(&picker, &open, false).await;
+                        let _ = tx.send(MatrixEvent::Diagnostics(checks));
+                    });
+                } else if self.settings_selected == 5 {
                     // Clear Cache
-                    self.do_clear_cache();
-                    self.overlay = Overlay::None;
+                    self.ask_confirm(PendingConfirm::ClearCache);
                 }
             }
             _ => {}
         }
     }
 
-    async fn reconnect_account(&mut self, idx: usize) {
-        if idx >= self.accounts.len() {
+    /// Restart any account whose sync loop has gone quiet for `sync_watchdog_mins`.
+    /// A long-poll returns at least every 30s, so minutes of silence means it's hung.
+    async fn check_sync_watchdog(&mut self) {
+        let limit = self.config.sync_watchdog_mins * 60;
+        if limit == 0 {
+            return;
+        }
+        let stalled = self
+            .accounts
+            .iter()
+            .position(|a| a.syncing && a.sync_complete && a.secs_since_sync() >= limit);
+        let Some(idx) = stalled else {
             return;
+        };
+        let user_id = self.accounts[idx].user_id.clone();
+        let mins = self.accounts[idx].secs_since_sync() / 60;
+        warn!("Sync for {} stalled for {} min, restarting", user_id, mins);
+        let outcome = if self.reconnect_account(idx).await {
+            "restarted"
+        } else {
+            "restart failed, retrying its sync"
+        };
+        self.set_status_sticky(format!(
+            "{}: sync stalled for {} min \u{2014} {}",
+            user_id, mins, outcome
+        ));
+    }
+
+    /// Rebuild an account's client from its saved session, returning false if that
+    /// failed and the old client's sync was restarted instead
+    async fn reconnect_account(&mut self, idx: usize) -> bool {
+        if idx >= self.accounts.len() {
+            return false;
         }
         let user_id = self.accounts[idx].user_id.clone();
         self.status_msg = format!("Reconnecting {}...", user_id);
+        self.accounts[idx].stop_sync();
+
+        // Guest sessions aren't saved, so their sync restarts on the same client
+        if self.accounts[idx].ephemeral {
+            self.accounts[idx].start_sync(self.matrix_tx.clone());
+            self.status_msg = format!("Reconnected {}", user_id);
+            return true;
+        }
+
+        // Re-restore from saved config, keeping the old account until that works
+        let saved = self.config.accounts.iter().find(|a| a.user_id == user_id).cloned();
+        let restored = match saved {
+            Some(saved) => Account::restore(&saved).await,
+            None => Err(anyhow::anyhow!("no saved session")),
+        };
+        let ok = match restored {
+            Ok(mut account) => {
+                account.start_sync(self.matrix_tx.clone());
+                self.status_msg = format!("Reconnected {}", account.user_id);
+                self.accounts[idx] = account;
+                true
+            }
+            Err(e) => {
+                self.accounts[idx].start_sync(self.matrix_tx.clone());
+                self.status_msg = format!("Reconnect failed: {}", user_id);
+                error!("Reconnect failed for {}: {}", user_id, e);
+                false
+            }
+        };
+        self.refresh_rooms().await;
+        ok
+    }
+
+    /// Move the account selected in the Settings accounts list up or down, persisting the order
+    fn move_settings_account(&mut self, up: bool) {
+        let sel = self.settings_accounts_selected;
+        if sel == 0 || sel > self.accounts.len() {
+            return;
+        }
+        let idx = sel - 1;
+        let target = if up {
+            if idx == 0 {
+                return;
+            }
+            idx - 1
+        } else {
+            if idx + 1 >= self.accounts.len() {
+                return;
+            }
+            idx + 1
+        };
+        self.accounts.swap(idx, target);
+        self.settings_accounts_selected = target + 1;
+
+        // Saved accounts follow the active order; disabled ones stay at the end
+        let order: Vec<String> = self.accounts.iter().map(|a| a.user_id.clone()).collect();
+        self.config.accounts.sort_by_key(|a| {
+            order.iter().position(|id| id == &a.user_id).unwrap_or(usize::MAX)
+        });
+        if let Err(e) = self.config.save() {
+            error!("Failed to save config: {}", e);
+        }
+
+        // Keep the accounts panel selection on the same account
+        if self.selected_account == idx {
+            self.selected_account = target;
+        } else if self.selected_account == target {
+            self.selected_account = idx;
+        }
+    }
+
+    /// Stop syncing an account and mark it disabled so it isn't restored at startup
+    async fn disable_account(&mut self, idx: usize) {
+        if idx >= self.accounts.len() {
+            return;
+        }
+        let user_id = self.accounts[idx].user_id.clone();
 
-        // Stop sync and remove old account
         self.accounts[idx].stop_sync();
         self.accounts.remove(idx);
 
-        // Re-restore from saved config
-        if let Some(saved) = self.config.accounts.iter().find(|a| a.user_id == user_id) {
-            let saved = saved.clone();
-            match Account::restore(&saved).await {
-                Ok(mut account) => {
-                    account.start_sync(self.matrix_tx.clone());
-                    self.status_msg = format!("Reconnected {}", account.user_id);
-                    self.accounts.push(account);
+        self.config.set_account_disabled(&user_id, true);
+        if let Err(e) = self.config.save() {
+            error!("Failed to save config: {}", e);
+        }
+
+        if self.active_account_id.as_deref() == Some(&user_id) {
+            self.active_room = None;
+            self.active_account_id = None;
+            self.messages.clear();
+        }
+
+        self.status_msg = format!("Disabled {}", user_id);
+        self.refresh_rooms().await;
+    }
+
+    /// Re-enable a disabled account and restore its session
+    async fn enable_account(&mut self, user_id: &str) {
+        self.config.set_account_disabled(user_id, false);
+        if let Err(e) = self.config.save() {
+            error!("Failed to save config: {}", e);
+        }
+
+        let Some(saved) = self.config.accounts.iter().find(|a| a.user_id == user_id).cloned() else {
+            return;
+        };
+        self.status_msg = format!("Restoring {}...", user_id);
+        match Account::restore(&saved).await {
+            Ok(mut account) => {
+                account.start_sync(self.matrix_tx.clone());
+                self.status_msg = format!("Enabled {}", account.user_id);
+                self.accounts.push(account);
+                // Keep the configured account order
+                let order: Vec<String> =
+                    self.config.accounts.iter().map(|a| a.user_id.clone()).collect();
+                self.accounts.sort_by_key(|a| {
+                    order.iter().position(|id| id == &a.user_id).unwrap_or(usize::MAX)
+                });
+            }
+            Err(e) => {
+                self.status_msg = format!("Enable failed: {}", user_id);
+                error!("Restore failed for {}: {}", user_id, e);
+            }
+        }
+        self.refresh_rooms().await;
+    }
+
+    /// Show the confirm overlay for a destructive action, returning to the current overlay on Esc
+    fn ask_confirm(&mut self, action: PendingConfirm) {
+        self.confirm_return = self.overlay;
+        self.pending_confirm = Some(action);
+        self.overlay = Overlay::Confirm;
+    }
+
+    async fn handle_confirm_key(&mut self, key: KeyEvent) {
+        match key.code {
+            KeyCode::Enter | KeyCode::Char('y') => {
+                self.overlay = self.confirm_return;
+                if let Some(action) = self.pending_confirm.take() {
+                    self.run_confirmed(action).await;
                 }
-                Err(e) => {
-                    self.status_msg = format!("Reconnect failed: {}", user_id);
-                    error!("Reconnect failed for {}: {}", user_id, e);
+            }
+            KeyCode::Esc | KeyCode::Char('n') => {
+                self.pending_confirm = None;
+                self.overlay = self.confirm_return;
+            }
+            _ => {}
+        }
+    }
+
+    async fn run_confirmed(&mut self, action: PendingConfirm) {
+        match action {
+            PendingConfirm::RemoveAccount(user_id) => {
+                if let Some(idx) = self.accounts.iter().position(|a| a.user_id == user_id) {
+                    self.remove_account_by_index(idx).await;
                 }
+                self.clamp_settings_account_selection();
+            }
+            PendingConfirm::LogOut(user_id) => {
+                let Some(idx) = self.accounts.iter().position(|a| a.user_id == user_id) else {
+                    return;
+                };
+                // Guest sessions are signed out as they're removed
+                let guest = self.accounts[idx].ephemeral;
+                if !guest {
+                    if let Err(e) = self.accounts[idx].logout().await {
+                        self.status_msg = format!("Log out failed for {}: {}", user_id, e);
+                        return;
+                    }
+                }
+                self.remove_account_by_index(idx).await;
+                self.clamp_settings_account_selection();
+                if !self.accounts.is_empty() && !guest {
+                    self.status_msg = format!("Logged out {}", user_id);
+                }
+            }
+            PendingConfirm::ClearCache => {
+                self.do_clear_cache();
+                self.overlay = Overlay::None;
+            }
+            PendingConfirm::RedactFromSender { room_id, sender, event_ids } => {
+                self.redact_from_sender(room_id, sender, event_ids);
+                self.overlay = Overlay::None;
+            }
+            PendingConfirm::LeaveRoom(_) => self.do_leave_room().await,
+            PendingConfirm::DeleteRoom(_) => self.do_delete_room().await,
+            PendingConfirm::RemoveThreepid(_) => self.do_remove_threepid().await,
+            PendingConfirm::LeaveMarkedRooms(_) => {
+                self.batch_leave().await;
+                self.marked_rooms.clear();
+                self.overlay = Overlay::None;
             }
         }
-        self.refresh_rooms().await;
+    }
+
+    fn clamp_settings_account_selection(&mut self) {
+        let count = 1 + self.accounts.len() + self.config.disabled_accounts().len();
+        if self.settings_accounts_selected >= count {
+            self.settings_accounts_selected = count.saturating_sub(1);
+        }
     }
 
     async fn remove_account_by_index(&mut self, idx: usize) {
@@ -2217,10 +5677,17 @@ impl App {
             return;
         }
         let user_id = self.accounts[idx].user_id.clone();
+        let guest_error = if self.accounts[idx].ephemeral {
+            Self::sign_out_guest(&mut self.accounts[idx]).await
+        } else {
+            None
+        };
 
         // Stop sync and remove from active accounts
         self.accounts[idx].stop_sync();
         self.accounts.remove(idx);
+        self.account_avatars.remove(&user_id);
+        self.avatar_images.remove(&user_id);
 
         // Remove from config
         self.config.remove_account(&user_id);
@@ -2241,6 +5708,9 @@ impl App {
         if self.accounts.is_empty() {
             self.status_msg = "No accounts \u{2014} press 's' to add one".to_string();
         }
+        if let Some(e) = guest_error {
+            self.status_msg = e;
+        }
     }
 
     fn do_clear_cache(&mut self) {
@@ -2277,15 +5747,24 @@ impl App {
 
         self.status_msg = format!("Logging in to {}...", self.login_homeserver);
 
-        match Account::login(&self.login_homeserver, &self.login_username, &self.login_password).await {
+        let memory = self.config.memory_store();
+        match Account::login(&self.login_homeserver, &self.login_username, &self.login_password, memory)
+            .await
+        {
             Ok((mut account, saved)) => {
                 info!("Logged in as {}", account.user_id);
                 account.start_sync(self.matrix_tx.clone());
-                self.config.add_account(saved);
-                if let Err(e) = self.config.save() {
-                    error!("Failed to save config: {}", e);
+                if memory {
+                    // Guest session: the token never reaches config.json
+                    self.status_msg =
+                        format!("Logged in as {} (guest, signed out on quit)", account.user_id);
+                } else {
+                    self.config.add_account(saved);
+                    if let Err(e) = self.config.save() {
+                        error!("Failed to save config: {}", e);
+                    }
+                    self.status_msg = format!("Logged in as {}", account.user_id);
                 }
-                self.status_msg = format!("Logged in as {}", account.user_id);
                 self.accounts.push(account);
                 self.refresh_rooms().await;
                 self.overlay = Overlay::None;
@@ -2310,37 +5789,39 @@ impl App {
         };
 
         if let Some(account) = self.accounts.iter().find(|a| a.user_id == account_id) {
-            match account.send_message(&room_id, body).await {
-                Ok(_) => {
-                    // Local echo — show our own message immediately
-                    let msg = DisplayMessage {
-                        event_id: None, // filled in when sync returns the event
-                        sender: account.user_id.clone(),
-                        content: MessageContent::Text(body.to_string()),
-                        timestamp: std::time::SystemTime::now()
-                            .duration_since(std::time::UNIX_EPOCH)
-                            .unwrap_or_default()
-                            .as_secs(),
-                        reply_to_sender: None,
-                        reply_to_body: None,
-                        reply_to_event_id_raw: None,
-                        reactions: Vec::new(),
-                    };
-                    self.messages.push(msg.clone());
-                    self.room_messages
-                        .entry(room_id)
-                        .or_default()
-                        .push(msg);
-                    self.pending_echoes.push(body.to_string());
-                    self.scroll_offset = 0;
-                }
-                Err(e) => {
-                    self.status_msg = format!("Send failed: {}", e);
-                }
+            let markdown = self.config.room_settings(room_id.as_str()).markdown;
+            let result = account.send_message(&room_id, body, markdown).await;
+            // Local echo — show our own message immediately, marked if it didn't go out
+            let msg = DisplayMessage {
+                event_id: None, // filled in when sync returns the event
+                sender: account.user_id.clone(),
+                content: MessageContent::Text(body.to_string()),
+                timestamp: std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_secs(),
+                reply_to_sender: None,
+                reply_to_body: None,
+                reply_to_event_id_raw: None,
+                reactions: Vec::new(),
+                send_error: result.as_ref().err().map(|e| e.to_string()),
+                notice: false,
+            };
+            match result {
+                Ok(_) => self.pending_echoes.push(body.to_string()),
+                Err(e) => self.status_msg = format!("Send failed: {}", e),
             }
+            self.push_local_echo(room_id, msg);
         }
     }
 
+    /// Append our own just-sent (or failed) message to the active and cached timelines
+    fn push_local_echo(&mut self, room_id: OwnedRoomId, msg: DisplayMessage) {
+        self.messages.push(msg.clone());
+        self.room_messages.entry(room_id).or_default().push(msg);
+        self.scroll_offset = 0;
+    }
+
     async fn send_reply_message(&mut self, body: &str, reply_to_event_id: &str, reply_to_sender: &str) {
         let room_id = match &self.active_room {
             Some(id) => id.clone(),
@@ -2351,34 +5832,32 @@ impl App {
             None => return,
         };
 
+        let (_, reply_to_body) = self.resolve_reply_context(&room_id, reply_to_event_id);
         if let Some(account) = self.accounts.iter().find(|a| a.user_id == account_id) {
-            match account.send_reply(&room_id, body, reply_to_event_id, reply_to_sender).await {
-                Ok(_) => {
-                    let msg = DisplayMessage {
-                        event_id: None,
-                        sender: account.user_id.clone(),
-                        content: MessageContent::Text(body.to_string()),
-                        timestamp: std::time::SystemTime::now()
-                            .duration_since(std::time::UNIX_EPOCH)
-                            .unwrap_or_default()
-                            .as_secs(),
-                        reply_to_sender: Some(reply_to_sender.to_string()),
-                        reply_to_body: None,
-                        reply_to_event_id_raw: Some(reply_to_event_id.to_string()),
-                        reactions: Vec::new(),
-                    };
-                    self.messages.push(msg.clone());
-                    self.room_messages
-                        .entry(room_id)
-                        .or_default()
-                        .push(msg);
-                    self.pending_echoes.push(body.to_string());
-                    self.scroll_offset = 0;
-                }
-                Err(e) => {
-                    self.status_msg = format!("Reply failed: {}", e);
-                }
+            let markdown = self.config.room_settings(room_id.as_str()).markdown;
+            let result = account
+                .send_reply(&room_id, body, reply_to_event_id, reply_to_sender, markdown)
+                .await;
+            let msg = DisplayMessage {
+                event_id: None,
+                sender: account.user_id.clone(),
+                content: MessageContent::Text(body.to_string()),
+                timestamp: std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_secs(),
+                reply_to_sender: Some(reply_to_sender.to_string()),
+                reply_to_body,
+                reply_to_event_id_raw: Some(reply_to_event_id.to_string()),
+                reactions: Vec::new(),
+                send_error: result.as_ref().err().map(|e| e.to_string()),
+                notice: false,
+            };
+            match result {
+                Ok(_) => self.pending_echoes.push(body.to_string()),
+                Err(e) => self.status_msg = format!("Reply failed: {}", e),
             }
+            self.push_local_echo(room_id, msg);
         }
     }
 
@@ -2401,18 +5880,118 @@ impl App {
                     })
             });
         if let Some(orig) = found {
-            let body = orig.body_text();
-            let snippet = if body.len() > 50 {
-                format!("{}...", &body[..50])
-            } else {
-                body.to_string()
-            };
-            (Some(orig.sender.clone()), Some(snippet))
+            (Some(orig.sender.clone()), Some(orig.body_text().to_string()))
         } else {
             (None, None)
         }
     }
 
+    /// Remove messages beyond `cap` from the front of the list, returning how many went
+    fn drop_oldest(messages: &mut Vec<DisplayMessage>, cap: usize) -> usize {
+        if cap == 0 || messages.len() <= cap {
+            return 0;
+        }
+        let excess = messages.len() - cap;
+        messages.drain(..excess);
+        excess
+    }
+
+    /// Keep a room's in-memory history within config.max_messages_per_room, trimming
+    /// once it's TRIM_SLACK over. Dropped messages come back by scrolling up, or when
+    /// the room is reopened.
+    fn trim_messages(&mut self, room_id: &OwnedRoomId) {
+        let cap = self.config.max_messages_per_room;
+        let over = |len: usize| cap > 0 && len > cap + TRIM_SLACK;
+        if let Some(cached) = self.room_messages.get_mut(room_id) {
+            if over(cached.len()) {
+                Self::drop_oldest(cached, cap);
+            }
+        }
+        // Leave the open room alone while the user is scrolled back or has a selection,
+        // so nothing moves under them
+        if Some(room_id) != self.active_room.as_ref()
+            || self.scroll_offset > 0
+            || self.selected_message.is_some()
+            || !over(self.messages.len())
+        {
+            return;
+        }
+        let dropped = Self::drop_oldest(&mut self.messages, cap);
+        if dropped > 0 {
+            self.first_unread_index = self.first_unread_index.and_then(|i| i.checked_sub(dropped));
+            // The old token now points past a gap; paging back starts from the oldest
+            // kept message instead
+            self.stale_history_tokens.insert(room_id.clone());
+        }
+    }
+
+    /// Fetch a reply's original from the server when it isn't loaded locally.
+    /// The result arrives as `MatrixEvent::ReplyContext`.
+    fn request_reply_context(&mut self, room_id: &OwnedRoomId, event_id: &str) {
+        if !self.reply_fetches.insert(event_id.to_string()) {
+            return;
+        }
+        // Prefer the active account, but any account joined to the room will do
+        let client = self
+            .accounts
+            .iter()
+            .filter(|a| a.client.get_room(room_id).is_some())
+            .min_by_key(|a| self.active_account_id.as_deref() != Some(a.user_id.as_str()))
+            .map(|a| a.client.clone());
+        let Some(client) = client else {
+            return;
+        };
+        let tx = self.matrix_tx.clone();
+        let room_id = room_id.clone();
+        let event_id = event_id.to_string();
+        tokio::spawn(async move {
+            match crate::account::fetch_reply_original(&client, &room_id, &event_id).await {
+                Ok((sender, body)) => {
+                    let _ = tx.send(MatrixEvent::ReplyContext { room_id, event_id, sender, body });
+                }
+                Err(e) => info!("Reply original {} unavailable: {}", event_id, e),
+            }
+        });
+    }
+
+    /// Look up a user's status message without blocking the UI; it comes back as
+    /// `MatrixEvent::Presence`
+    fn request_status_message(&self, account_id: &str, user_id: String) {
+        let Some(client) = self
+            .accounts
+            .iter()
+            .find(|a| a.user_id == account_id)
+            .map(|a| a.client.clone())
+        else {
+            return;
+        };
+        let tx = self.matrix_tx.clone();
+        tokio::spawn(async move {
+            match crate::account::status_message(&client, &user_id).await {
+                Ok(status_msg) => {
+                    let _ = tx.send(MatrixEvent::Presence { user_id, status_msg });
+                }
+                Err(e) => info!("Status message unavailable for {}: {}", user_id, e),
+            }
+        });
+    }
+
+    /// Request every reply original in the active room that local history couldn't resolve
+    fn fetch_missing_replies(&mut self) {
+        let Some(room_id) = self.active_room.clone() else {
+            return;
+        };
+        let missing: Vec<String> = self
+            .messages
+            .iter()
+            .filter(|m| m.reply_to_sender.is_none())
+            .filter_map(|m| m.reply_to_event_id_raw.clone())
+            .collect();
+        for event_id in missing {
+            self.request_reply_context(&room_id, &event_id);
+        }
+    }
+
     /// Resolve reply context for all messages that have reply_to_event_id_raw set but no reply_to_sender
     fn resolve_all_replies(messages: &mut [DisplayMessage]) {
         // Build an index of event_id -> (sender, body) first
@@ -2420,13 +5999,7 @@ impl App {
             .iter()
             .filter_map(|m| {
                 let eid = m.event_id.as_ref()?;
-                let body = m.body_text();
-                let snippet = if body.len() > 50 {
-                    format!("{}...", &body[..50])
-                } else {
-                    body.to_string()
-                };
-                Some((eid.clone(), (m.sender.clone(), snippet)))
+                Some((eid.clone(), (m.sender.clone(), m.body_text().to_string())))
             })
             .collect();
         for msg in messages.iter_mut() {
@@ -2450,6 +6023,7 @@ impl App {
                 timestamp,
                 event_id,
                 reply_to_event_id,
+                notice,
             } => {
                 // Skip if this is our own message echoed back from sync
                 if let Some(pos) = self.pending_echoes.iter().position(|b| *b == body) {
@@ -2463,12 +6037,16 @@ impl App {
                 // Resolve reply context
                 let (reply_to_sender, reply_to_body) =
                     if let Some(ref reply_eid) = reply_to_event_id {
-                        self.resolve_reply_context(&room_id, reply_eid)
+                        let context = self.resolve_reply_context(&room_id, reply_eid);
+                        if context.0.is_none() {
+                            self.request_reply_context(&room_id, reply_eid);
+                        }
+                        context
                     } else {
                         (None, None)
                     };
 
-                let receipt_eid = event_id.clone();
+                let body_for_notify = body.clone();
                 let msg = DisplayMessage {
                     event_id: Some(event_id),
                     sender: sender.to_string(),
@@ -2478,7 +6056,12 @@ impl App {
                     reply_to_body,
                     reply_to_event_id_raw: reply_to_event_id,
                     reactions: Vec::new(),
+                    send_error: None,
+                    notice,
                 };
+                if !self.passes_room_filters(&room_id, &msg) {
+                    return;
+                }
 
                 // Always cache in per-room store
                 self.room_messages
@@ -2486,16 +6069,15 @@ impl App {
                     .or_default()
                     .push(msg.clone());
 
+                self.maybe_notify(&room_id, sender.as_str(), &body_for_notify);
+
                 // If this message is for the active room, add to display
                 if Some(&room_id) == self.active_room.as_ref() {
-                    self.messages.push(msg);
-                    // Send read receipt for the active room
-                    if let Some(ref aid) = self.active_account_id {
-                        if let Some(account) = self.accounts.iter().find(|a| &a.user_id == aid) {
-                            let _ = account.send_read_receipt(&room_id, &receipt_eid).await;
-                        }
-                    }
+                    self.messages.push(msg);
+                    // Read receipt, if the user can actually see it
+                    self.flush_read_receipt().await;
                 }
+                self.trim_messages(&room_id);
             }
             MatrixEvent::ImageMessage {
                 room_id,
@@ -2508,11 +6090,16 @@ impl App {
             } => {
                 let (reply_to_sender, reply_to_body) =
                     if let Some(ref reply_eid) = reply_to_event_id {
-                        self.resolve_reply_context(&room_id, reply_eid)
+                        let context = self.resolve_reply_context(&room_id, reply_eid);
+                        if context.0.is_none() {
+                            self.request_reply_context(&room_id, reply_eid);
+                        }
+                        context
                     } else {
                         (None, None)
                     };
 
+                let show_images = self.config.room_settings(room_id.as_str()).images;
                 let msg = DisplayMessage {
                     event_id: Some(event_id.clone()),
                     sender: sender.to_string(),
@@ -2520,14 +6107,20 @@ impl App {
                         body: body.clone(),
                         source: source.clone(),
                         protocol: None,
-                        loading: true,
+                        image: None,
+                        loading: show_images,
                     },
                     timestamp,
                     reply_to_sender,
                     reply_to_body,
                     reply_to_event_id_raw: reply_to_event_id,
                     reactions: Vec::new(),
+                    send_error: None,
+                    notice: false,
                 };
+                if !self.passes_room_filters(&room_id, &msg) {
+                    return;
+                }
 
                 self.room_messages
                     .entry(room_id.clone())
@@ -2537,9 +6130,12 @@ impl App {
                 if Some(&room_id) == self.active_room.as_ref() {
                     self.messages.push(msg);
                 }
+                self.trim_messages(&room_id);
 
                 // Spawn async image download
-                self.spawn_image_download(room_id, event_id, source);
+                if show_images {
+                    self.spawn_image_download(room_id, event_id, source);
+                }
             }
             MatrixEvent::FileMessage {
                 room_id,
@@ -2553,7 +6149,11 @@ impl App {
             } => {
                 let (reply_to_sender, reply_to_body) =
                     if let Some(ref reply_eid) = reply_to_event_id {
-                        self.resolve_reply_context(&room_id, reply_eid)
+                        let context = self.resolve_reply_context(&room_id, reply_eid);
+                        if context.0.is_none() {
+                            self.request_reply_context(&room_id, reply_eid);
+                        }
+                        context
                     } else {
                         (None, None)
                     };
@@ -2571,7 +6171,12 @@ impl App {
                     reply_to_body,
                     reply_to_event_id_raw: reply_to_event_id,
                     reactions: Vec::new(),
+                    send_error: None,
+                    notice: false,
                 };
+                if !self.passes_room_filters(&room_id, &msg) {
+                    return;
+                }
 
                 self.room_messages
                     .entry(room_id.clone())
@@ -2581,15 +6186,14 @@ impl App {
                 if Some(&room_id) == self.active_room.as_ref() {
                     self.messages.push(msg);
                 }
+                self.trim_messages(&room_id);
             }
-            MatrixEvent::Typing { room_id, user_ids } => {
+            MatrixEvent::Typing { room_id, users } => {
                 if Some(&room_id) == self.active_room.as_ref() {
-                    self.typing_users = user_ids
-                        .iter()
-                        .filter(|uid| !self.accounts.iter().any(|a| a.user_id == uid.as_str()))
-                        .map(|uid| {
-                            uid.localpart().to_string()
-                        })
+                    self.typing_users = users
+                        .into_iter()
+                        .filter(|(uid, _)| !self.accounts.iter().any(|a| a.user_id == uid.as_str()))
+                        .map(|(_, name)| name)
                         .collect();
                 }
             }
@@ -2627,6 +6231,11 @@ impl App {
                 if let Some(acct) = self.accounts.iter_mut().find(|a| a.user_id == account_id) {
                     acct.sync_complete = true;
                 }
+                self.clear_status_sticky(&format!("{}: sync error", account_id));
+                self.clear_status_sticky(&format!("{}: sync stalled", account_id));
+                if !self.account_avatars.contains_key(&account_id) {
+                    self.spawn_avatar_download(&account_id);
+                }
 
                 // Update status to reflect actual per-account sync state
                 let states: Vec<_> = self.accounts.iter()
@@ -2662,6 +6271,227 @@ impl App {
                     }
                 }
             }
+            MatrixEvent::BroadcastSent { room_name, error } => {
+                if let Some(ref mut b) = self.broadcast {
+                    if let Some(i) = b.in_flight.iter().position(|name| *name == room_name) {
+                        b.in_flight.remove(i);
+                    }
+                    match error {
+                        None => b.sent += 1,
+                        Some(e) => {
+                            info!("Broadcast to {} failed: {}", room_name, e);
+                            b.failed.push(room_name);
+                        }
+                    }
+                }
+                self.maybe_finish_broadcast();
+            }
+            MatrixEvent::ReplyContext { room_id, event_id, sender, body } => {
+                let active = Some(&room_id) == self.active_room.as_ref();
+                let cached = self.room_messages.get_mut(&room_id).into_iter().flatten();
+                let current = self.messages.iter_mut().filter(|_| active);
+                for msg in cached.chain(current) {
+                    if msg.reply_to_sender.is_none()
+                        && msg.reply_to_event_id_raw.as_deref() == Some(event_id.as_str())
+                    {
+                        msg.reply_to_sender = Some(sender.clone());
+                        msg.reply_to_body = Some(body.clone());
+                    }
+                }
+            }
+            MatrixEvent::Presence { user_id, status_msg } => match status_msg {
+                Some(msg) => {
+                    self.presence_status.insert(user_id, msg);
+                }
+                None => {
+                    self.presence_status.remove(&user_id);
+                }
+            },
+            MatrixEvent::OwnReadReceipt { room_id, event_id, thread } => match thread {
+                ReceiptThread::Thread(root) => {
+                    if let Some(threads) = self.thread_unread.get_mut(&room_id) {
+                        threads.remove(root.as_str());
+                    }
+                }
+                thread => {
+                    // An unthreaded receipt covers every thread in the room
+                    if matches!(thread, ReceiptThread::Unthreaded) {
+                        self.thread_unread.remove(&room_id);
+                    }
+                    if Some(&room_id) == self.active_room.as_ref() {
+                        self.own_read_receipt = Some(event_id);
+                    }
+                }
+            },
+            MatrixEvent::Knock { room_id, user_id, reason } => {
+                let is_active = self.active_room.as_ref() == Some(&room_id);
+                if is_active && self.overlay == Overlay::Knocks {
+                    if !self.knocks.iter().any(|k| k.user_id == user_id) {
+                        self.knocks.push(KnockRequest {
+                            user_id: user_id.clone(),
+                            display_name: None,
+                            reason: reason.clone(),
+                        });
+                    }
+                }
+                let room_name = self
+                    .all_rooms
+                    .iter()
+                    .find(|r| r.id == room_id)
+                    .map(|r| r.name.clone())
+                    .unwrap_or_else(|| room_id.to_string());
+                self.status_msg = match reason {
+                    Some(reason) => format!(
+                        "{} asked to join {}: \"{}\" (K in chat to review)",
+                        user_id, room_name, reason
+                    ),
+                    None => format!("{} asked to join {} (K in chat to review)", user_id, room_name),
+                };
+            }
+            MatrixEvent::RemovedFromRoom { account_id, room_id, by, reason, banned } => {
+                // The initial sync can replay removals we've already seen
+                let live = self
+                    .accounts
+                    .iter()
+                    .any(|a| a.user_id == account_id && a.sync_complete);
+                if !live {
+                    return;
+                }
+                let room_name = self
+                    .all_rooms
+                    .iter()
+                    .find(|r| r.id == room_id)
+                    .map(|r| r.name.clone())
+                    .unwrap_or_else(|| room_id.to_string());
+                let verb = if banned { "banned" } else { "removed" };
+                let notice = match reason.filter(|r| !r.is_empty()) {
+                    Some(reason) => format!("You were {} by {}: {}", verb, by, reason),
+                    None => format!("You were {} by {}", verb, by),
+                };
+                self.status_msg = format!("{} ({})", notice, room_name);
+                if self.active_room.as_ref() == Some(&room_id) {
+                    self.replying_to = None;
+                    self.composer_edit = None;
+                }
+                self.removed_from.insert(room_id, RemovedFrom { room_name, notice });
+                self.refresh_rooms().await;
+            }
+            MatrixEvent::ThreadReply { account_id, room_id, thread_root, event_id, sender } => {
+                // The initial sync replays thread history we may already have read
+                let live = self
+                    .accounts
+                    .iter()
+                    .any(|a| a.user_id == account_id && a.sync_complete);
+                if !live {
+                    return;
+                }
+                let threads = self.thread_unread.entry(room_id).or_default();
+                if self.accounts.iter().any(|a| a.user_id == sender.as_str()) {
+                    // Replying in a thread means we've read it
+                    threads.remove(&thread_root);
+                } else {
+                    let thread = threads.entry(thread_root).or_insert(ThreadUnread {
+                        latest_event_id: String::new(),
+                        count: 0,
+                    });
+                    thread.latest_event_id = event_id;
+                    thread.count += 1;
+                }
+            }
+            MatrixEvent::SenderRedaction {
+                room_id,
+                sender,
+                redacted,
+                deleted,
+                done,
+                total,
+                last_error,
+            } => {
+                if let Some(event_id) = redacted {
+                    self.remove_redacted(&room_id, &event_id);
+                }
+                self.status_msg = if done < total {
+                    format!("Deleting messages from {}... {}/{}", sender, done, total)
+                } else {
+                    match last_error {
+                        Some(e) => format!(
+                            "Deleted {} of {} messages from {} (last error: {})",
+                            deleted, total, sender, e
+                        ),
+                        None => format!("Deleted {} messages from {}", deleted, sender),
+                    }
+                };
+            }
+            MatrixEvent::DirectoryVisibility { room_id, published } => {
+                if let Some(details) = self
+                    .room_details
+                    .as_mut()
+                    .filter(|d| d.room_id == room_id.as_str())
+                {
+                    details.published = Some(published);
+                }
+                if self.editor_room_id.as_ref() == Some(&room_id) {
+                    self.editor_published = Some(published);
+                }
+            }
+            MatrixEvent::PowerLevelsChanged { account_id, room_id } => {
+                if self.active_room.as_ref() != Some(&room_id)
+                    || self.active_account_id.as_deref() != Some(account_id.as_str())
+                {
+                    return;
+                }
+                if let Some(account) = self.accounts.iter().find(|a| a.user_id == account_id) {
+                    self.room_power = account.room_power_levels(&room_id).await;
+                }
+            }
+            MatrixEvent::Diagnostics(checks) => {
+                self.diagnostics = checks;
+                self.status_msg = "Diagnostics complete".to_string();
+                // Don't cover whatever the user moved on to while the checks ran
+                if self.overlay == Overlay::Settings {
+                    self.overlay = Overlay::Diagnostics;
+                }
+            }
+            MatrixEvent::FavoritesJoined { joined, failed } => {
+                for id in &joined {
+                    if !self.config.favorites.contains(id) {
+                        self.config.favorites.push(id.clone());
+                    }
+                }
+                if let Err(e) = self.config.save() {
+                    error!("Failed to save config: {}", e);
+                }
+                self.favorite_joins.clear();
+                self.favorite_join_busy = false;
+                if self.overlay == Overlay::JoinFavorites {
+                    self.overlay = Overlay::None;
+                }
+                self.refresh_rooms().await;
+                self.status_msg = if failed.is_empty() {
+                    format!("Joined and favorited {} rooms", joined.len())
+                } else {
+                    format!(
+                        "Joined {} rooms; couldn't join {}",
+                        joined.len(),
+                        failed.join(", ")
+                    )
+                };
+            }
+            MatrixEvent::NewDevice { account_id, device_id, display_name } => {
+                let device = match display_name {
+                    Some(name) => format!("{} ({})", name, device_id),
+                    None => device_id,
+                };
+                warn!("New device on {}: {}", account_id, device);
+                self.set_status_sticky(format!(
+                    "\u{26a0} New session signed in to {}: {} \u{2014} verify or remove it if this wasn't you",
+                    account_id, device
+                ));
+                crate::notify::send(
+                    "New Matrix session",
+                    &format!("{} signed in on {}", account_id, device),
+                );
+            }
             MatrixEvent::KeysDownloaded { room_id, account_id } => {
                 self.downloading_keys = false;
                 // Re-fetch history if we're still viewing this room
@@ -2674,6 +6504,8 @@ impl App {
                                 let count = msgs.len();
                                 let decrypted = msgs.iter().filter(|m| !m.body_text().contains("[encrypted message")).count();
                                 self.messages = msgs;
+                                self.filter_active_messages();
+                                self.sort_active_by_time();
                                 self.trigger_image_downloads();
                                 self.status_msg = format!("Decrypted {}/{} messages", decrypted, count);
                             }
@@ -2687,7 +6519,7 @@ impl App {
                     acct.syncing = false;
                     acct.sync_complete = false;
                 }
-                self.status_msg = format!("{}: sync error — {}", account_id, error);
+                self.set_status_sticky(format!("{}: sync error — {}", account_id, error));
             }
             MatrixEvent::VerificationIncoming { account_id, user_id, flow_id } => {
                 // Show incoming verification request if no overlay is open
@@ -2740,6 +6572,7 @@ impl App {
     }
 
     pub async fn refresh_rooms(&mut self) {
+        let started = std::time::Instant::now();
         // Remember current selection by room ID
         let prev_id = self.all_rooms.get(self.selected_room).map(|r| r.id.clone());
 
@@ -2762,6 +6595,8 @@ impl App {
         self.favorites_count = favorites.len();
         self.all_rooms = favorites;
         self.all_rooms.append(&mut all);
+        let all_rooms = &self.all_rooms;
+        self.marked_rooms.retain(|id| all_rooms.iter().any(|r| &r.id == id));
 
         // Restore selection by room ID
         if let Some(prev) = prev_id {
@@ -2773,6 +6608,10 @@ impl App {
         if self.selected_room >= self.all_rooms.len() && !self.all_rooms.is_empty() {
             self.selected_room = self.all_rooms.len() - 1;
         }
+        if let Some(perf) = &mut self.perf {
+            perf.refresh_rooms = started.elapsed();
+            perf.record("refresh_rooms", perf.refresh_rooms);
+        }
     }
 
     fn sort_rooms(&self, rooms: &mut Vec<RoomInfo>) {
@@ -2808,28 +6647,161 @@ impl App {
         }
     }
 
+    /// Save the open room's messages before switching away (peeked rooms aren't kept)
+    fn stash_active_messages(&mut self) {
+        if self.peeking.is_some() || self.messages.is_empty() {
+            return;
+        }
+        if let Some(prev_room_id) = self.active_room.clone() {
+            self.room_messages
+                .insert(prev_room_id.clone(), self.messages.clone());
+            if let Some(cached) = self.room_messages.get_mut(&prev_room_id) {
+                let cap = self.config.max_messages_per_room;
+                Self::drop_oldest(cached, cap);
+            }
+        }
+    }
+
+    /// Show a world-readable room's history read-only, without joining it
+    async fn peek_room(&mut self, target: &str) {
+        let Some(account) = self.accounts.get(self.default_account_idx()) else {
+            self.status_msg = "No accounts".to_string();
+            return;
+        };
+        let account_id = account.user_id.clone();
+        let id_or_alias = target.split('?').next().unwrap_or(target);
+        let room_id = match account.resolve_peek_target(id_or_alias).await {
+            Ok(id) => id,
+            Err(e) => {
+                self.status_msg = format!("Can't find {}: {}", id_or_alias, e);
+                return;
+            }
+        };
+        if let Some(idx) = self.all_rooms.iter().position(|r| r.id == room_id) {
+            // Already joined — just open it
+            self.selected_room = idx;
+            self.open_selected_room().await;
+            return;
+        }
+        match account.peek_history(&room_id, None, 50).await {
+            Ok((msgs, end_token)) => {
+                self.stash_active_messages();
+                if self.composer_edit.take().is_some() {
+                    self.input.clear();
+                    self.cursor_pos = 0;
+                }
+                self.active_room = Some(room_id.clone());
+                self.active_account_id = Some(account_id);
+                self.peeking = Some(target.to_string());
+                self.members.clear();
+                self.room_power = RoomPower::default();
+                self.messages = msgs;
+                self.filter_active_messages();
+                self.stale_history_tokens.remove(&room_id);
+                self.room_history_tokens.insert(room_id, end_token);
+                self.scroll_offset = 0;
+                self.selected_message = None;
+                self.first_unread_index = None;
+                self.own_read_receipt = None;
+                self.typing_users.clear();
+                self.replying_to = None;
+                self.search_active = false;
+                self.search_query.clear();
+                self.sender_filter = None;
+                self.focus = Focus::Chat;
+                self.status_msg = format!(
+                    "Peeking at {} \u{2014} {} messages, /join to participate",
+                    id_or_alias,
+                    self.messages.len()
+                );
+            }
+            Err(e) => {
+                self.status_msg =
+                    format!("Can't peek at {} (not world-readable?): {}", id_or_alias, e);
+            }
+        }
+    }
+
+    /// Join a room by ID or alias (the peeked room if none given) and open it
+    async fn join_and_open(&mut self, target: Option<&str>) {
+        let target = match (target, &self.peeking) {
+            (Some(t), _) => t.to_string(),
+            // The alias or link we peeked with, so rooms on other servers can be reached
+            (None, Some(peeked)) => peeked.clone(),
+            _ => {
+                self.status_msg = "Usage: /join <#alias or !room_id>".to_string();
+                return;
+            }
+        };
+        let idx = match (&self.peeking, &self.active_account_id) {
+            (Some(_), Some(aid)) => self
+                .accounts
+                .iter()
+                .position(|a| &a.user_id == aid)
+                .unwrap_or_else(|| self.default_account_idx()),
+            _ => self.default_account_idx(),
+        };
+        let Some(account) = self.accounts.get(idx) else {
+            self.status_msg = "No accounts".to_string();
+            return;
+        };
+        self.status_msg = format!("Joining {}...", target);
+        match account.join_room(&target).await {
+            Ok(room_id) => {
+                if self.active_room.as_ref() == Some(&room_id) {
+                    self.peeking = None;
+                }
+                self.refresh_rooms().await;
+                if let Some(pos) = self.all_rooms.iter().position(|r| r.id == room_id) {
+                    self.selected_room = pos;
+                    self.open_selected_room().await;
+                }
+            }
+            Err(e) => {
+                self.status_msg = format!("Join failed: {}", e);
+            }
+        }
+    }
+
     async fn open_selected_room(&mut self) {
         if let Some(room) = self.all_rooms.get(self.selected_room) {
             let room_id = room.id.clone();
             let account_id = room.account_id.clone();
             let room_name = room.name.clone();
+            let dm_user = room.dm_user.clone();
 
-            // Save current room's messages before switching
-            if let Some(prev_room_id) = &self.active_room {
-                if !self.messages.is_empty() {
-                    self.room_messages
-                        .insert(prev_room_id.clone(), self.messages.clone());
-                }
-            }
+            self.stash_active_messages();
 
             let unread = room.unread;
+            // An edit started with Up belongs to the room we're leaving
+            if self.composer_edit.take().is_some() {
+                self.input.clear();
+                self.cursor_pos = 0;
+            }
+            // Bring back text left in the composer when we last quit
+            if self.input.is_empty() {
+                if let Some(draft) = self.config.drafts.remove(room_id.as_str()) {
+                    self.input = draft;
+                    self.cursor_pos = self.input.len();
+                    if let Err(e) = self.config.save() {
+                        error!("Failed to save config: {}", e);
+                    }
+                }
+            }
             self.active_room = Some(room_id.clone());
             self.active_account_id = Some(account_id.clone());
+            self.peeking = None;
+            // Listed again, so we're back in the room
+            self.removed_from.remove(&room_id);
             self.messages.clear();
+            self.members.clear();
             self.scroll_offset = 0;
             self.selected_message = None;
             self.typing_users.clear();
             self.replying_to = None;
+            self.search_active = false;
+            self.search_query.clear();
+            self.sender_filter = None;
             self.focus = Focus::Chat;
 
             let account_synced = self
@@ -2850,9 +6822,11 @@ impl App {
                 match account.fetch_history_paged(&room_id, None, 50).await {
                     Ok((msgs, end_token)) if !msgs.is_empty() => {
                         let count = msgs.len();
+                        self.stale_history_tokens.remove(&room_id);
                         self.room_history_tokens.insert(room_id.clone(), end_token);
                         let has_encrypted = msgs.iter().any(|m| m.body_text().contains("[encrypted message"));
                         self.messages = msgs;
+                        self.filter_active_messages();
                         self.trigger_image_downloads();
                         if has_encrypted {
                             // Encrypted messages found — SDK will auto-download keys
@@ -2923,32 +6897,283 @@ impl App {
                 );
             }
 
-            // Resolve reply context for loaded messages
+            // Cached messages may predate a filter change
+            self.filter_active_messages();
+            self.sort_active_by_time();
+            // Resolve reply context for loaded messages, fetching the rest from the server
             Self::resolve_all_replies(&mut self.messages);
+            self.fetch_missing_replies();
+
+            // Set unread separator after the fully-read marker synced from our other
+            // clients, falling back to the unread count if it isn't in loaded history
+            let (fully_read, own_receipt, power) =
+                match self.accounts.iter().find(|a| a.user_id == account_id) {
+                    Some(account) => (
+                        account.get_fully_read(&room_id).await,
+                        account.get_own_read_receipt(&room_id).await,
+                        account.room_power_levels(&room_id).await,
+                    ),
+                    None => (None, None, RoomPower::default()),
+                };
+            self.own_read_receipt = own_receipt;
+            self.room_power = power;
+            let marker_idx = fully_read.as_deref().and_then(|eid| {
+                self.messages
+                    .iter()
+                    .position(|m| m.event_id.as_deref() == Some(eid))
+            });
+            self.first_unread_index = match marker_idx {
+                Some(idx) if idx + 1 < self.messages.len() => Some(idx + 1),
+                Some(_) => None,
+                None if unread > 0 && !self.messages.is_empty() => {
+                    Some(self.messages.len().saturating_sub(unread as usize))
+                }
+                None => None,
+            };
 
-            // Set unread separator
-            if unread > 0 && !self.messages.is_empty() {
-                let idx = self.messages.len().saturating_sub(unread as usize);
-                self.first_unread_index = Some(idx);
-            } else {
-                self.first_unread_index = None;
+            // DM header shows the other person's status message
+            if let Some(user_id) = dm_user {
+                self.request_status_message(&account_id, user_id);
             }
 
             // Send read receipt on the latest message
-            if let Some(last) = self.messages.last() {
-                if let Some(ref eid) = last.event_id {
-                    if let Some(account) = self.accounts.iter().find(|a| a.user_id == account_id) {
-                        let _ = account.send_read_receipt(&room_id, eid).await;
+            self.last_receipt = None;
+            self.last_receipt_at = None;
+            self.flush_read_receipt().await;
+        }
+    }
+
+    /// Send a read receipt for the newest message in the active room, but only
+    /// while the terminal is focused and the chat panel is on screen
+    async fn flush_read_receipt(&mut self) {
+        if !self.terminal_focused || !self.chat_visible.get() || self.peeking.is_some() {
+            return;
+        }
+        let (room_id, account_id) = match (&self.active_room, &self.active_account_id) {
+            (Some(r), Some(a)) => (r.clone(), a.clone()),
+            _ => return,
+        };
+        let too_soon = self
+            .last_receipt_at
+            .as_ref()
+            .is_some_and(|(last_room, at)| {
+                *last_room == room_id && at.elapsed() < RECEIPT_INTERVAL
+            });
+        if too_soon {
+            return;
+        }
+        // Thread replies are shown inline, so having the room open reads its threads too
+        if let Some(threads) = self.thread_unread.remove(&room_id) {
+            self.last_receipt_at = Some((room_id.clone(), std::time::Instant::now()));
+            if let Some(account) = self.accounts.iter().find(|a| a.user_id == account_id) {
+                for (root, thread) in threads {
+                    if let Err(e) = account
+                        .send_thread_read_receipt(&room_id, &root, &thread.latest_event_id)
+                        .await
+                    {
+                        info!("Thread receipt failed for {} in {}: {}", root, room_id, e);
                     }
                 }
             }
         }
+        // Only what's been on screen counts as read: stop at the bottom of the
+        // viewport and skip messages the timeline filters hide
+        let end = self.messages.len().saturating_sub(self.scroll_offset);
+        let Some(eid) = self.messages[..end]
+            .iter()
+            .rev()
+            .filter(|m| self.is_message_visible(m))
+            .find_map(|m| m.event_id.clone())
+        else {
+            return;
+        };
+        if self.last_receipt.as_ref() == Some(&eid) {
+            return;
+        }
+        self.last_receipt_at = Some((room_id.clone(), std::time::Instant::now()));
+        if let Some(account) = self.accounts.iter().find(|a| a.user_id == account_id) {
+            // Only a receipt that went out counts; a failed one is retried next interval
+            match account.send_read_receipt(&room_id, &eid).await {
+                Ok(()) => self.last_receipt = Some(eid),
+                Err(e) => info!("Read receipt failed for {}: {}", room_id, e),
+            }
+        }
+    }
+
+    /// "X is typing", "X and Y are typing", "X, Y and 3 others are typing"
+    pub fn typing_summary(&self) -> Option<String> {
+        let names = &self.typing_users;
+        let who = match names.len() {
+            0 => return None,
+            1 => return Some(format!("{} is typing", names[0])),
+            2 => format!("{} and {}", names[0], names[1]),
+            3 => format!("{}, {} and {}", names[0], names[1], names[2]),
+            n => format!("{}, {} and {} others", names[0], names[1], n - 2),
+        };
+        Some(format!("{} are typing", who))
+    }
+
+    /// Threads in a room with replies we haven't read
+    pub fn unread_thread_count(&self, room_id: &OwnedRoomId) -> usize {
+        self.thread_unread.get(room_id).map_or(0, |threads| threads.len())
+    }
+
+    /// Desktop notification for a DM or mention, only while the terminal is unfocused
+    fn maybe_notify(&self, room_id: &OwnedRoomId, sender: &str, body: &str) {
+        if self.terminal_focused
+            || self.config.is_bot(sender)
+            || self.accounts.iter().any(|a| a.user_id == sender)
+        {
+            return;
+        }
+        let Some(room) = self.all_rooms.iter().find(|r| &r.id == room_id) else {
+            return;
+        };
+        // Skip the backlog delivered by the initial sync
+        let Some(account) = self.accounts.iter().find(|a| a.user_id == room.account_id) else {
+            return;
+        };
+        if !account.sync_complete || !self.config.room_settings(room_id.as_str()).notifications {
+            return;
+        }
+        let localpart = account
+            .user_id
+            .trim_start_matches('@')
+            .split(':')
+            .next()
+            .unwrap_or("");
+        // Whole words only, so "al" isn't mentioned by "also"
+        let mentioned = body.contains(&account.user_id)
+            || mentions_word(body, localpart)
+            || (account.display_name != account.user_id
+                && mentions_word(body, &account.display_name));
+        if !room.is_dm && !mentioned {
+            return;
+        }
+        let title = if room.is_dm {
+            sender.to_string()
+        } else {
+            format!("{} in {}", sender, room.name)
+        };
+        crate::notify::send(&title, body);
+
+        // The room's sound for this class wins over the global one
+        let class = if room.is_dm { "dm" } else { "mention" };
+        let settings = self.config.room_settings(room_id.as_str());
+        let sound = settings
+            .sounds
+            .get(class)
+            .or_else(|| self.config.sounds.get(class));
+        if let Some(sound) = sound {
+            crate::notify::play_sound(sound, &room.name, sender);
+        }
     }
 
     // --- Image download ---
 
+    /// Rebuild the picker if the cell size changed (e.g. font zoom) and re-encode
+    /// the avatars and images on screen, which were sized for the old cells
+    fn handle_resize(&mut self) {
+        let Some(font_size) = crate::terminal::window_font_size() else {
+            return;
+        };
+        if font_size == self.picker.font_size() {
+            return;
+        }
+        info!("Font size changed {:?} -> {:?}", self.picker.font_size(), font_size);
+        let protocol = self.picker.protocol_type();
+        let mut picker = Picker::from_fontsize(font_size);
+        picker.set_protocol_type(protocol);
+        self.picker = picker;
+
+        for (user_id, image) in &self.avatar_images {
+            let protocol = self.picker.new_resize_protocol((**image).clone());
+            self.account_avatars
+                .insert(user_id.clone(), Arc::new(Mutex::new(protocol)));
+        }
+
+        let show_images = self
+            .active_room
+            .as_ref()
+            .is_some_and(|id| self.config.room_settings(id.as_str()).images);
+        let Some(room_id) = self.active_room.clone().filter(|_| show_images) else {
+            return;
+        };
+        // Decoded images are re-encoded for the new cells; ones we don't have are
+        // downloaded again, and the old protocol keeps rendering until they arrive
+        let end = self.messages.len().saturating_sub(self.scroll_offset);
+        let start = end.saturating_sub(self.chat_viewport_msgs.get());
+        let mut reencoded = Vec::new();
+        for msg in &self.messages[start..end] {
+            let (MessageContent::Image { source, image, .. }, Some(eid)) =
+                (&msg.content, &msg.event_id)
+            else {
+                continue;
+            };
+            match image {
+                Some(image) => reencoded.push((eid.clone(), image.clone())),
+                None => self.spawn_image_download(room_id.clone(), eid.clone(), source.clone()),
+            }
+        }
+        for (event_id, image) in reencoded {
+            let protocol = self.picker.new_resize_protocol((*image).clone());
+            self.handle_image_ready(&room_id, &event_id, Arc::new(Mutex::new(protocol)), image);
+        }
+    }
+
+    /// Fetch an account's avatar thumbnail for the accounts panel; accounts
+    /// without one keep their initial badge
+    fn spawn_avatar_download(&self, user_id: &str) {
+        let Some(app_tx) = self.app_tx.clone() else {
+            return;
+        };
+        let Some(client) = self
+            .accounts
+            .iter()
+            .find(|a| a.user_id == user_id)
+            .map(|a| a.client.clone())
+        else {
+            return;
+        };
+        let user_id = user_id.to_string();
+        let picker = self.picker.clone();
+
+        tokio::spawn(async move {
+            let format = matrix_sdk::media::MediaFormat::Thumbnail(
+                matrix_sdk::media::MediaThumbnailSettings::new(
+                    matrix_sdk::ruma::UInt::from(64u32),
+                    matrix_sdk::ruma::UInt::from(64u32),
+                ),
+            );
+            let bytes = match client.account().get_avatar(format).await {
+                Ok(Some(b)) => b,
+                Ok(None) => return,
+                Err(e) => {
+                    info!("Avatar download failed for {}: {}", user_id, e);
+                    return;
+                }
+            };
+            if let Ok(dyn_img) = image::load_from_memory(&bytes) {
+                let proto = picker.new_resize_protocol(dyn_img.clone());
+                let _ = app_tx.send(AppEvent::AvatarReady {
+                    user_id,
+                    protocol: Arc::new(Mutex::new(proto)),
+                    image: Arc::new(dyn_img),
+                });
+            }
+        });
+    }
+
     /// Trigger downloads for any image messages that haven't been loaded yet
     fn trigger_image_downloads(&self) {
+        let show_images = self
+            .active_room
+            .as_ref()
+            .map(|id| self.config.room_settings(id.as_str()).images)
+            .unwrap_or(true);
+        if !show_images {
+            return;
+        }
         for msg in &self.messages {
             if let MessageContent::Image {
                 ref source,
@@ -2987,7 +7212,8 @@ impl App {
             .iter()
             .map(|a| (a.user_id.clone(), a.client.clone()))
             .collect();
-        let font_size = self.picker.font_size();
+        // Keep the detected protocol (and tmux passthrough) for the encoded image
+        let picker = self.picker.clone();
 
         tokio::spawn(async move {
             // Find the right client for this room
@@ -3028,12 +7254,12 @@ impl App {
             };
 
             if let Ok(dyn_img) = image::load_from_memory(&bytes) {
-                let picker = Picker::from_fontsize(font_size);
-                let proto = picker.new_resize_protocol(dyn_img);
+                let proto = picker.new_resize_protocol(dyn_img.clone());
                 let _ = app_tx.send(AppEvent::ImageReady {
                     room_id,
                     event_id,
                     protocol: Arc::new(Mutex::new(proto)),
+                    image: Arc::new(dyn_img),
                 });
             }
         });
@@ -3044,6 +7270,7 @@ impl App {
         room_id: &OwnedRoomId,
         event_id: &str,
         protocol: Arc<Mutex<StatefulProtocol>>,
+        image: Arc<image::DynamicImage>,
     ) {
         // Update in active messages
         if Some(room_id) == self.active_room.as_ref() {
@@ -3054,11 +7281,13 @@ impl App {
             {
                 if let MessageContent::Image {
                     protocol: ref mut p,
+                    image: ref mut i,
                     loading: ref mut l,
                     ..
                 } = msg.content
                 {
                     *p = Some(protocol.clone());
+                    *i = Some(image.clone());
                     *l = false;
                 }
             }
@@ -3071,11 +7300,13 @@ impl App {
             {
                 if let MessageContent::Image {
                     protocol: ref mut p,
+                    image: ref mut i,
                     loading: ref mut l,
                     ..
                 } = msg.content
                 {
                     *p = Some(protocol);
+                    *i = Some(image);
                     *l = false;
                 }
             }
@@ -3087,10 +7318,21 @@ impl App {
     async fn handle_paste(&mut self, data: String) {
         let path = data.trim().trim_matches('\'').trim_matches('"');
         let p = std::path::Path::new(path);
+        let will_send = (p.exists() && p.is_file()) || data.trim_end().contains('\n');
+        if will_send && self.active_room.is_some() && self.blocked_read_only() {
+            return;
+        }
         if p.exists() && p.is_file() {
             // File was dropped — confirm before sending
             self.pending_file_drop = Some(path.to_string());
             self.overlay = Overlay::FileConfirm;
+        } else if data.trim_end().contains('\n')
+            && self.active_room.is_some()
+            && self.composer_edit.is_none()
+        {
+            // Multi-line paste — offer to send it as a code block so indentation survives
+            self.pending_code_paste = Some(data);
+            self.overlay = Overlay::CodePaste;
         } else {
             // Regular paste — insert into input
             self.input.insert_str(self.cursor_pos, &data);
@@ -3098,6 +7340,60 @@ impl App {
         }
     }
 
+    async fn handle_code_paste_key(&mut self, key: KeyEvent) {
+        match key.code {
+            KeyCode::Enter => {
+                if let Some(code) = self.pending_code_paste.take() {
+                    self.overlay = Overlay::None;
+                    self.send_outgoing(Outgoing::Code(code), None).await;
+                }
+            }
+            KeyCode::Char('t') => {
+                if let Some(data) = self.pending_code_paste.take() {
+                    self.input.insert_str(self.cursor_pos, &data);
+                    self.cursor_pos += data.len();
+                }
+                self.overlay = Overlay::None;
+            }
+            KeyCode::Esc => {
+                self.pending_code_paste = None;
+                self.overlay = Overlay::None;
+            }
+            _ => {}
+        }
+    }
+
+    async fn send_code_block(&mut self, code: &str) {
+        let (room_id, account_id) = match (&self.active_room, &self.active_account_id) {
+            (Some(r), Some(a)) => (r.clone(), a.clone()),
+            _ => return,
+        };
+        if let Some(account) = self.accounts.iter().find(|a| a.user_id == account_id) {
+            let result = account.send_code_block(&room_id, code).await;
+            let body = crate::account::code_block_body(code);
+            let msg = DisplayMessage {
+                event_id: None,
+                sender: account.user_id.clone(),
+                content: MessageContent::Text(body.clone()),
+                timestamp: std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_secs(),
+                reply_to_sender: None,
+                reply_to_body: None,
+                reply_to_event_id_raw: None,
+                reactions: Vec::new(),
+                send_error: result.as_ref().err().map(|e| e.to_string()),
+                notice: false,
+            };
+            match result {
+                Ok(()) => self.pending_echoes.push(body),
+                Err(e) => self.status_msg = format!("Send failed: {}", e),
+            }
+            self.push_local_echo(room_id, msg);
+        }
+    }
+
     // --- File picker ---
 
     async fn open_file_picker(&mut self) {
@@ -3122,7 +7418,7 @@ impl App {
                     .trim()
                     .to_string();
                 if !path.is_empty() {
-                    self.send_file_attachment(&path).await;
+                    self.send_outgoing(Outgoing::File(path), None).await;
                 }
             }
         }
@@ -3167,7 +7463,7 @@ impl App {
             KeyCode::Enter => {
                 if let Some(path) = self.pending_file_drop.take() {
                     self.overlay = Overlay::None;
-                    self.send_file_attachment(&path).await;
+                    self.send_outgoing(Outgoing::File(path), None).await;
                 }
             }
             KeyCode::Esc => {
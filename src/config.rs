@@ -1,6 +1,7 @@
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 
 /// Where config lives: ~/.config/matrixtui/
 pub fn config_dir() -> PathBuf {
@@ -16,6 +17,14 @@ pub fn data_dir() -> PathBuf {
         .join("matrixtui")
 }
 
+/// Expand a leading `~/` to the home directory
+pub fn expand_home(path: &str) -> PathBuf {
+    match path.strip_prefix("~/") {
+        Some(rest) => dirs::home_dir().unwrap_or_default().join(rest),
+        None => PathBuf::from(path),
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SavedAccount {
     pub homeserver: String,
@@ -23,6 +32,194 @@ pub struct SavedAccount {
     /// Stored session token — avoids re-login
     pub access_token: String,
     pub device_id: String,
+    /// Kept configured but not restored or synced at startup
+    #[serde(default)]
+    pub disabled: bool,
+    /// Access token for the account's identity server, once its terms are accepted
+    #[serde(default)]
+    pub identity_server_token: Option<String>,
+}
+
+/// Match `text` against a pattern where `*` stands for any run of characters
+pub fn wildcard_match(pattern: &str, text: &str) -> bool {
+    let parts: Vec<&str> = pattern.split('*').collect();
+    let (first, last) = (parts[0], parts[parts.len() - 1]);
+    if parts.len() == 1 {
+        return pattern == text;
+    }
+    let Some(mut rest) = text.strip_prefix(first) else {
+        return false;
+    };
+    for part in &parts[1..parts.len() - 1] {
+        match rest.find(part) {
+            Some(i) => rest = &rest[i + part.len()..],
+            None => return false,
+        }
+    }
+    rest.ends_with(last)
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// Kinds of events hidden from a room's timeline
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TimelineFilters {
+    /// m.notice messages
+    #[serde(default)]
+    pub hide_notices: bool,
+    /// Senders matching `bot_patterns`
+    #[serde(default)]
+    pub hide_bots: bool,
+    /// Images, files, video and audio
+    #[serde(default)]
+    pub hide_media: bool,
+    /// Senders matching `bridge_patterns` (puppeted users from other networks)
+    #[serde(default)]
+    pub hide_bridges: bool,
+}
+
+/// Sound played with a notification, per class: a shell command, `bell` for the
+/// terminal bell, or `off`. Commands are executed, so they are never exported or
+/// imported with settings
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct NotificationSounds {
+    #[serde(default)]
+    pub dm: Option<String>,
+    #[serde(default)]
+    pub mention: Option<String>,
+}
+
+impl NotificationSounds {
+    pub fn get(&self, class: &str) -> Option<&String> {
+        match class {
+            "dm" => self.dm.as_ref(),
+            _ => self.mention.as_ref(),
+        }
+    }
+
+    pub fn get_mut(&mut self, class: &str) -> &mut Option<String> {
+        match class {
+            "dm" => &mut self.dm,
+            _ => &mut self.mention,
+        }
+    }
+}
+
+/// Composer and display preferences remembered per room
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RoomSettings {
+    /// Send messages as markdown (formatted body) instead of plain text
+    #[serde(default)]
+    pub markdown: bool,
+    #[serde(default = "default_true")]
+    pub notifications: bool,
+    /// Download and render images inline
+    #[serde(default = "default_true")]
+    pub images: bool,
+    #[serde(default)]
+    pub filters: TimelineFilters,
+    /// Order loaded history by origin_server_ts instead of the order the server
+    /// returned it, for bridged backfills with out-of-order timestamps
+    #[serde(default)]
+    pub sort_by_time: bool,
+    /// Overrides the global `sounds` for this room
+    #[serde(default)]
+    pub sounds: NotificationSounds,
+}
+
+impl Default for RoomSettings {
+    fn default() -> Self {
+        Self {
+            markdown: false,
+            notifications: true,
+            images: true,
+            filters: TimelineFilters::default(),
+            sort_by_time: false,
+            sounds: NotificationSounds::default(),
+        }
+    }
+}
+
+fn default_click() -> String {
+    "select".to_string()
+}
+
+fn default_double_click() -> String {
+    "open".to_string()
+}
+
+fn default_middle_click() -> String {
+    "reply".to_string()
+}
+
+/// Mouse button actions: "select", "open", "reply", "react" or "none"
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MouseConfig {
+    #[serde(default = "default_click")]
+    pub click: String,
+    #[serde(default = "default_double_click")]
+    pub double_click: String,
+    #[serde(default = "default_middle_click")]
+    pub middle_click: String,
+}
+
+impl Default for MouseConfig {
+    fn default() -> Self {
+        Self {
+            click: default_click(),
+            double_click: default_double_click(),
+            middle_click: default_middle_click(),
+        }
+    }
+}
+
+fn default_clock_format() -> String {
+    "%a %d %b %H:%M".to_string()
+}
+
+fn default_image_protocol() -> String {
+    "auto".to_string()
+}
+
+fn default_store() -> String {
+    "sqlite".to_string()
+}
+
+fn default_max_messages_per_room() -> usize {
+    500
+}
+
+fn default_status_ttl_secs() -> u64 {
+    8
+}
+
+fn default_reply_snippet_len() -> usize {
+    50
+}
+
+fn default_sync_watchdog_mins() -> u64 {
+    3
+}
+
+fn default_three_column_width() -> u16 {
+    120
+}
+
+fn default_two_column_width() -> u16 {
+    60
+}
+
+fn default_bot_patterns() -> Vec<String> {
+    vec!["@*bot:*".to_string(), "@*-bot*:*".to_string()]
+}
+
+fn default_bridge_patterns() -> Vec<String> {
+    ["@_*", "@telegram_*", "@whatsapp_*", "@signal_*", "@discord_*", "@slack_*"]
+        .iter()
+        .map(|p| p.to_string())
+        .collect()
 }
 
 fn default_room_sort() -> String {
@@ -39,6 +236,102 @@ pub struct Config {
     pub favorites: Vec<String>,
     #[serde(default = "default_room_sort")]
     pub room_sort: String,
+    /// user_id of the account used for new rooms, DMs and joins
+    #[serde(default)]
+    pub default_account: Option<String>,
+    /// Keyed by room ID; rooms without an entry use `RoomSettings::default()`
+    #[serde(default)]
+    pub room_settings: HashMap<String, RoomSettings>,
+    /// Unsent composer text saved on quit, keyed by room ID
+    #[serde(default)]
+    pub drafts: HashMap<String, String>,
+    /// Most recently used reaction, applied with `E`
+    #[serde(default)]
+    pub last_reaction: Option<String>,
+    #[serde(default)]
+    pub mouse: MouseConfig,
+    /// Show a clock on the right of the status bar
+    #[serde(default)]
+    pub show_clock: bool,
+    /// strftime-style format for the status bar clock
+    #[serde(default = "default_clock_format")]
+    pub clock_format: String,
+    /// "auto", "kitty", "sixel", "iterm2" or "halfblocks"
+    #[serde(default = "default_image_protocol")]
+    pub image_protocol: String,
+    /// Store for new logins: "sqlite" (kept under `sessions/`) or "memory", for guest
+    /// sessions that are never written to disk and are signed out on quit
+    #[serde(default = "default_store")]
+    pub store: String,
+    /// `--store` given on the command line, for this run only
+    #[serde(skip)]
+    pub store_override: Option<String>,
+    /// Characters of the original message quoted above a reply (expand with `z`)
+    #[serde(default = "default_reply_snippet_len")]
+    pub reply_snippet_len: usize,
+    /// Seconds before a transient status bar message clears (0 keeps it)
+    #[serde(default = "default_status_ttl_secs")]
+    pub status_ttl_secs: u64,
+    /// Messages kept in memory per room before the oldest are dropped (0 = no limit)
+    #[serde(default = "default_max_messages_per_room")]
+    pub max_messages_per_room: usize,
+    /// Minutes without a sync response before an account's sync is restarted (0 = never)
+    #[serde(default = "default_sync_watchdog_mins")]
+    pub sync_watchdog_mins: u64,
+    /// Terminal widths at which the layout grows to three and two columns
+    #[serde(default = "default_three_column_width")]
+    pub three_column_width: u16,
+    #[serde(default = "default_two_column_width")]
+    pub two_column_width: u16,
+    /// Force 1, 2 or 3 columns regardless of width; 0 follows the widths above
+    #[serde(default)]
+    pub layout_columns: u8,
+    /// Sounds for desktop notifications; rooms can override them with `/sound`
+    #[serde(default)]
+    pub sounds: NotificationSounds,
+    /// Show admin / mod badges next to sender names
+    #[serde(default = "default_true")]
+    pub power_badges: bool,
+    /// User IDs tagged as bots: their messages are dimmed and compact, and never notify
+    #[serde(default)]
+    pub bots: Vec<String>,
+    /// User ID patterns (`*` wildcard) hidden by a room's "hide bots" filter
+    #[serde(default = "default_bot_patterns")]
+    pub bot_patterns: Vec<String>,
+    /// User ID patterns (`*` wildcard) hidden by a room's "hide bridge users" filter
+    #[serde(default = "default_bridge_patterns")]
+    pub bridge_patterns: Vec<String>,
+}
+
+/// One room in a shared favorites list
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FavoriteEntry {
+    pub room_id: String,
+    /// Canonical alias, preferred for joining since it doesn't need a via server
+    #[serde(default)]
+    pub alias: Option<String>,
+    #[serde(default)]
+    pub name: Option<String>,
+}
+
+/// Favorites in order, as written by `/export-favorites`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FavoritesList {
+    #[serde(default)]
+    pub rooms: Vec<FavoriteEntry>,
+}
+
+impl FavoritesList {
+    pub fn load(path: &Path) -> Result<Self> {
+        let data = std::fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&data)?)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let data = serde_json::to_string_pretty(self)?;
+        std::fs::write(path, data)?;
+        Ok(())
+    }
 }
 
 impl Config {
@@ -48,7 +341,8 @@ impl Config {
             let data = std::fs::read_to_string(&path)?;
             Ok(serde_json::from_str(&data)?)
         } else {
-            Ok(Self::default())
+            // Go through serde so field-level defaults apply
+            Ok(serde_json::from_str("{}")?)
         }
     }
 
@@ -61,6 +355,52 @@ impl Config {
         Ok(())
     }
 
+    /// Write all settings to `path`, except saved accounts (which hold access tokens)
+    /// and what only makes sense on this machine: drafts, the default account and
+    /// notification sound commands
+    pub fn export_settings(&self, path: &Path) -> Result<()> {
+        let mut bundle = self.clone();
+        bundle.accounts.clear();
+        bundle.drafts.clear();
+        bundle.default_account = None;
+        bundle.sounds = NotificationSounds::default();
+        for settings in bundle.room_settings.values_mut() {
+            settings.sounds = NotificationSounds::default();
+        }
+        let data = serde_json::to_string_pretty(&bundle)?;
+        std::fs::write(path, data)?;
+        Ok(())
+    }
+
+    /// Replace settings with an exported bundle, keeping this machine's accounts,
+    /// drafts, default account and sounds. Sound commands in the bundle are ignored,
+    /// since they would run through the shell.
+    pub fn import_settings(&mut self, path: &Path) -> Result<()> {
+        let data = std::fs::read_to_string(path)?;
+        let mut imported: Config = serde_json::from_str(&data)?;
+        imported.accounts = std::mem::take(&mut self.accounts);
+        imported.drafts = std::mem::take(&mut self.drafts);
+        imported.default_account = self.default_account.take();
+        imported.sounds = std::mem::take(&mut self.sounds);
+        for settings in imported.room_settings.values_mut() {
+            settings.sounds = NotificationSounds::default();
+        }
+        for (room, local) in self.room_settings.drain() {
+            if local.sounds.dm.is_none() && local.sounds.mention.is_none() {
+                continue;
+            }
+            imported.room_settings.entry(room).or_default().sounds = local.sounds;
+        }
+        imported.store_override = self.store_override.take();
+        *self = imported;
+        Ok(())
+    }
+
+    /// Whether new logins get an in-memory store and stay out of config.json
+    pub fn memory_store(&self) -> bool {
+        self.store_override.as_deref().unwrap_or(&self.store) == "memory"
+    }
+
     pub fn add_account(&mut self, account: SavedAccount) {
         // Replace existing entry for same user_id, or add new
         if let Some(existing) = self
@@ -76,5 +416,27 @@ impl Config {
 
     pub fn remove_account(&mut self, user_id: &str) {
         self.accounts.retain(|a| a.user_id != user_id);
+        if self.default_account.as_deref() == Some(user_id) {
+            self.default_account = None;
+        }
+    }
+
+    pub fn is_bot(&self, user_id: &str) -> bool {
+        self.bots.iter().any(|b| b == user_id)
+    }
+
+    pub fn room_settings(&self, room_id: &str) -> RoomSettings {
+        self.room_settings.get(room_id).cloned().unwrap_or_default()
+    }
+
+    pub fn set_account_disabled(&mut self, user_id: &str, disabled: bool) {
+        if let Some(account) = self.accounts.iter_mut().find(|a| a.user_id == user_id) {
+            account.disabled = disabled;
+        }
+    }
+
+    /// Saved accounts that are skipped at startup
+    pub fn disabled_accounts(&self) -> Vec<&SavedAccount> {
+        self.accounts.iter().filter(|a| a.disabled).collect()
     }
 }
@@ -0,0 +1,135 @@
+//! `mtui --daemon`: keep syncing and sending desktop notifications without a
+//! terminal. Starting the UI asks the daemon over a Unix socket to hand its
+//! sessions over, and the UI starts a new daemon when it quits.
+
+use anyhow::{Result, bail};
+use std::fs::{File, TryLockError};
+use std::path::PathBuf;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::unix::OwnedWriteHalf;
+use tokio::net::{UnixListener, UnixStream};
+use tokio::signal::unix::{SignalKind, signal};
+use tokio::sync::mpsc;
+use tracing::{info, warn};
+
+use crate::app::App;
+
+/// How long the UI waits for the daemon to stop its syncs
+const HAND_OVER_TIMEOUT: Duration = Duration::from_secs(15);
+
+pub fn socket_path() -> PathBuf {
+    crate::config::data_dir().join("daemon.sock")
+}
+
+fn open_lock_file() -> std::io::Result<File> {
+    File::options()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(crate::config::data_dir().join("sessions.lock"))
+}
+
+/// Share the sessions lock for as long as the returned file is kept, so
+/// `mtui doctor` can tell the session stores are open in another process
+pub fn lock_sessions() -> std::io::Result<File> {
+    let file = open_lock_file()?;
+    file.lock_shared()?;
+    Ok(file)
+}
+
+/// Whether a running UI or daemon has the session stores open
+pub fn sessions_in_use() -> bool {
+    open_lock_file().is_ok_and(|file| matches!(file.try_lock(), Err(TryLockError::WouldBlock)))
+}
+
+/// Run headless until a UI attaches or we get SIGTERM / Ctrl+C
+pub async fn run(mut app: App) -> Result<()> {
+    let path = socket_path();
+    if UnixStream::connect(&path).await.is_ok() {
+        bail!("a daemon is already running ({})", path.display());
+    }
+    // Left behind by a daemon that didn't exit cleanly
+    let _ = std::fs::remove_file(&path);
+    let listener = UnixListener::bind(&path)?;
+    info!("Daemon listening on {}", path.display());
+
+    // An attaching UI sends its connection along so we can answer once stopped
+    let (stop_tx, stop_rx) = mpsc::unbounded_channel::<Option<OwnedWriteHalf>>();
+    let signal_tx = stop_tx.clone();
+    tokio::spawn(async move {
+        let Ok(mut term) = signal(SignalKind::terminate()) else {
+            return;
+        };
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => {}
+            _ = term.recv() => {}
+        }
+        let _ = signal_tx.send(None);
+    });
+    tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            let (reader, mut writer) = stream.into_split();
+            let mut line = String::new();
+            if BufReader::new(reader).read_line(&mut line).await.is_err() {
+                continue;
+            }
+            match line.trim() {
+                "attach" => {
+                    let _ = stop_tx.send(Some(writer));
+                }
+                "status" => {
+                    let _ = writer.write_all(b"running\n").await;
+                }
+                other => {
+                    let reply = format!("unknown command: {}\n", other);
+                    let _ = writer.write_all(reply.as_bytes()).await;
+                }
+            }
+        }
+    });
+
+    app.restore_sessions().await;
+    let attached = app.run_headless(stop_rx).await;
+    let _ = std::fs::remove_file(&path);
+    for warning in &app.shutdown_warnings {
+        warn!("{}", warning);
+    }
+    if let Some(mut writer) = attached {
+        info!("Handing sessions over to the UI");
+        let _ = writer.write_all(b"detached\n").await;
+    }
+    Ok(())
+}
+
+/// Ask a running daemon to stop syncing so the UI can open the sessions.
+/// Returns whether there was one, so it can be started again on quit.
+pub async fn hand_over() -> Result<bool> {
+    let path = socket_path();
+    let Ok(mut stream) = UnixStream::connect(&path).await else {
+        let _ = std::fs::remove_file(&path);
+        return Ok(false);
+    };
+    stream.write_all(b"attach\n").await?;
+    let mut reply = String::new();
+    tokio::time::timeout(HAND_OVER_TIMEOUT, BufReader::new(stream).read_line(&mut reply)).await??;
+    if reply.trim() != "detached" {
+        bail!("the background daemon didn't hand over its sessions ({})", reply.trim());
+    }
+    Ok(true)
+}
+
+/// Start `mtui --daemon` again in its own process group, detached from the terminal
+pub fn respawn() -> Result<()> {
+    use std::os::unix::process::CommandExt;
+    use std::process::{Command, Stdio};
+
+    Command::new(std::env::current_exe()?)
+        .arg("--daemon")
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .process_group(0)
+        .spawn()?;
+    Ok(())
+}
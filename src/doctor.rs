@@ -0,0 +1,216 @@
+use ratatui_image::picker::{Picker, ProtocolType};
+use std::time::Duration;
+use tokio::net::TcpStream;
+
+use crate::account::check_session_store;
+use crate::config::{Config, config_dir};
+use crate::terminal;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Level {
+    Ok,
+    Warn,
+    Fail,
+}
+
+impl Level {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Ok => "ok",
+            Self::Warn => "warn",
+            Self::Fail => "FAIL",
+        }
+    }
+}
+
+/// One diagnostic result, with what to do about it when it isn't ok
+#[derive(Debug, Clone)]
+pub struct Check {
+    pub name: String,
+    pub level: Level,
+    pub detail: String,
+}
+
+impl Check {
+    fn new(name: impl Into<String>, level: Level, detail: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            level,
+            detail: detail.into(),
+        }
+    }
+}
+
+/// Run all health checks. `open_accounts` are user IDs whose session store is
+/// already open in this process; with `stores_in_use` another mtui has them all
+/// open. Either way their store check is skipped.
+pub async fn run_checks(
+    picker: &Picker,
+    open_accounts: &[String],
+    stores_in_use: bool,
+) -> Vec<Check> {
+    let mut checks = vec![check_truecolor(), check_graphics(picker), check_clipboard()];
+
+    let config = match Config::load() {
+        Ok(cfg) => {
+            checks.push(Check::new(
+                "Config",
+                Level::Ok,
+                format!("{} is valid", config_dir().join("config.json").display()),
+            ));
+            cfg
+        }
+        Err(e) => {
+            checks.push(Check::new(
+                "Config",
+                Level::Fail,
+                format!("config.json could not be parsed ({}) \u{2014} fix or remove it", e),
+            ));
+            return checks;
+        }
+    };
+    if let Some(check) = check_config_permissions() {
+        checks.push(check);
+    }
+
+    for saved in &config.accounts {
+        if saved.disabled {
+            checks.push(Check::new(&saved.user_id, Level::Ok, "disabled, skipped"));
+            continue;
+        }
+        checks.push(check_homeserver(&saved.user_id, &saved.homeserver).await);
+        if open_accounts.contains(&saved.user_id) {
+            checks.push(Check::new(
+                format!("{} store", saved.user_id),
+                Level::Ok,
+                "open in this session",
+            ));
+        } else if stores_in_use {
+            checks.push(Check::new(
+                format!("{} store", saved.user_id),
+                Level::Ok,
+                "held by a running mtui, skipped",
+            ));
+        } else {
+            checks.push(match check_session_store(saved).await {
+                Ok(()) => Check::new(format!("{} store", saved.user_id), Level::Ok, "opens cleanly"),
+                Err(e) => Check::new(
+                    format!("{} store", saved.user_id),
+                    Level::Fail,
+                    format!("{} \u{2014} remove the account and log in again", e),
+                ),
+            });
+        }
+    }
+    checks
+}
+
+fn check_truecolor() -> Check {
+    let colorterm = std::env::var("COLORTERM").unwrap_or_default();
+    if colorterm == "truecolor" || colorterm == "24bit" {
+        Check::new("Truecolor", Level::Ok, format!("COLORTERM={}", colorterm))
+    } else {
+        Check::new(
+            "Truecolor",
+            Level::Warn,
+            "COLORTERM is not truecolor/24bit \u{2014} themes may look off; set it if your terminal supports 24-bit colour",
+        )
+    }
+}
+
+fn check_graphics(picker: &Picker) -> Check {
+    let protocol = picker.protocol_type();
+    if protocol != ProtocolType::Halfblocks {
+        let via = if terminal::in_tmux() { " via tmux passthrough" } else { "" };
+        return Check::new("Graphics", Level::Ok, format!("{:?}{}", protocol, via));
+    }
+    let hint = if terminal::in_tmux() {
+        "halfblocks only \u{2014} tmux 3.3+ with allow-passthrough is needed for sharp images"
+    } else {
+        "halfblocks only \u{2014} use a kitty/sixel/iTerm2 capable terminal, or set image_protocol in config.json"
+    };
+    Check::new("Graphics", Level::Warn, hint)
+}
+
+fn check_clipboard() -> Check {
+    const TOOLS: &[&str] = &["wl-copy", "xclip", "xsel", "pbcopy", "termux-clipboard-set"];
+    match TOOLS.iter().find(|tool| on_path(tool)) {
+        Some(tool) => Check::new("Clipboard", Level::Ok, format!("{} found", tool)),
+        None => Check::new(
+            "Clipboard",
+            Level::Warn,
+            "no clipboard tool found \u{2014} install wl-clipboard or xclip for copy support",
+        ),
+    }
+}
+
+fn on_path(program: &str) -> bool {
+    std::env::var_os("PATH")
+        .map(|paths| std::env::split_paths(&paths).any(|dir| dir.join(program).is_file()))
+        .unwrap_or(false)
+}
+
+/// config.json holds access tokens, so it shouldn't be readable by others
+#[cfg(unix)]
+fn check_config_permissions() -> Option<Check> {
+    use std::os::unix::fs::PermissionsExt;
+    let path = config_dir().join("config.json");
+    let mode = std::fs::metadata(&path).ok()?.permissions().mode();
+    Some(if mode & 0o077 == 0 {
+        Check::new("Config permissions", Level::Ok, format!("{:o}", mode & 0o777))
+    } else {
+        Check::new(
+            "Config permissions",
+            Level::Warn,
+            format!(
+                "{:o} \u{2014} readable by other users; run chmod 600 {}",
+                mode & 0o777,
+                path.display()
+            ),
+        )
+    })
+}
+
+#[cfg(not(unix))]
+fn check_config_permissions() -> Option<Check> {
+    None
+}
+
+/// TCP reachability of the homeserver's host and port
+async fn check_homeserver(user_id: &str, homeserver: &str) -> Check {
+    let name = format!("{} server", user_id);
+    let (secure, rest) = match homeserver.split_once("://") {
+        Some((scheme, rest)) => (scheme != "http", rest),
+        None => (true, homeserver),
+    };
+    let host_port = rest.split('/').next().unwrap_or(rest);
+    let addr = if host_port.contains(':') {
+        host_port.to_string()
+    } else {
+        format!("{}:{}", host_port, if secure { 443 } else { 80 })
+    };
+    match tokio::time::timeout(Duration::from_secs(3), TcpStream::connect(&addr)).await {
+        Ok(Ok(_)) => Check::new(name, Level::Ok, format!("{} reachable", addr)),
+        Ok(Err(e)) => Check::new(
+            name,
+            Level::Fail,
+            format!("{} unreachable ({}) \u{2014} check your network or the homeserver URL", addr, e),
+        ),
+        Err(_) => Check::new(
+            name,
+            Level::Fail,
+            format!("{} timed out \u{2014} check your network or firewall", addr),
+        ),
+    }
+}
+
+/// Print results for the `doctor` subcommand
+pub fn print(checks: &[Check]) {
+    println!("MatrixTUI doctor");
+    for check in checks {
+        println!("  [{:>4}] {}: {}", check.level.label(), check.name, check.detail);
+    }
+    let failed = checks.iter().filter(|c| c.level == Level::Fail).count();
+    let warned = checks.iter().filter(|c| c.level == Level::Warn).count();
+    println!("{} failed, {} warnings", failed, warned);
+}
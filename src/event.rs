@@ -1,4 +1,4 @@
-use crossterm::event::{self, Event, KeyEvent};
+use crossterm::event::{self, Event, KeyEvent, MouseEvent};
 use matrix_sdk::ruma::OwnedRoomId;
 use ratatui_image::protocol::StatefulProtocol;
 use std::sync::{Arc, Mutex};
@@ -11,6 +11,10 @@ use crate::account::MatrixEvent;
 pub enum AppEvent {
     /// A key was pressed
     Key(KeyEvent),
+    /// Mouse click, scroll or movement
+    Mouse(MouseEvent),
+    /// Terminal window gained (true) or lost (false) focus
+    FocusChanged(bool),
     /// Terminal was resized
     Resize,
     /// A Matrix event from any account
@@ -22,11 +26,35 @@ pub enum AppEvent {
         room_id: OwnedRoomId,
         event_id: String,
         protocol: Arc<Mutex<StatefulProtocol>>,
+        image: Arc<image::DynamicImage>,
+    },
+    /// An account's avatar has been downloaded and decoded for the accounts panel
+    AvatarReady {
+        user_id: String,
+        protocol: Arc<Mutex<StatefulProtocol>>,
+        image: Arc<image::DynamicImage>,
     },
     /// Tick for periodic UI refresh
     Tick,
 }
 
+impl AppEvent {
+    /// Short label for the `--perf` overlay
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Key(_) => "key",
+            Self::Mouse(_) => "mouse",
+            Self::FocusChanged(_) => "focus",
+            Self::Resize => "resize",
+            Self::Matrix(_) => "matrix",
+            Self::Paste(_) => "paste",
+            Self::ImageReady { .. } => "image",
+            Self::AvatarReady { .. } => "avatar",
+            Self::Tick => "tick",
+        }
+    }
+}
+
 /// Spawns a task that reads terminal events and forwards them
 pub fn spawn_input_reader(tx: mpsc::UnboundedSender<AppEvent>) {
     tokio::spawn(async move {
@@ -37,9 +65,18 @@ pub fn spawn_input_reader(tx: mpsc::UnboundedSender<AppEvent>) {
                     Ok(Event::Key(key)) => {
                         let _ = tx.send(AppEvent::Key(key));
                     }
+                    Ok(Event::Mouse(mouse)) => {
+                        let _ = tx.send(AppEvent::Mouse(mouse));
+                    }
                     Ok(Event::Resize(_, _)) => {
                         let _ = tx.send(AppEvent::Resize);
                     }
+                    Ok(Event::FocusGained) => {
+                        let _ = tx.send(AppEvent::FocusChanged(true));
+                    }
+                    Ok(Event::FocusLost) => {
+                        let _ = tx.send(AppEvent::FocusChanged(false));
+                    }
                     Ok(Event::Paste(data)) => {
                         let _ = tx.send(AppEvent::Paste(data));
                     }
@@ -1,47 +1,95 @@
 mod account;
 mod app;
 mod config;
+mod daemon;
+mod doctor;
 mod event;
+mod notify;
+mod perf;
+mod terminal;
 mod ui;
 
 use anyhow::Result;
 use app::App;
 use crossterm::{
     cursor::SetCursorStyle,
-    event::{DisableMouseCapture, EnableMouseCapture, EnableBracketedPaste, DisableBracketedPaste},
+    event::{
+        DisableBracketedPaste, DisableFocusChange, DisableMouseCapture, EnableBracketedPaste,
+        EnableFocusChange, EnableMouseCapture,
+    },
     execute,
     terminal::{EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode},
 };
 use ratatui::prelude::*;
-use ratatui_image::picker::Picker;
 use std::io;
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    // Set up logging to file (don't pollute the TUI)
+    let daemon_mode = std::env::args().skip(1).any(|a| a == "--daemon");
+
+    // Set up logging to file (don't pollute the TUI); the daemon keeps its own
+    // so a UI taking over doesn't truncate it
     let log_dir = config::data_dir();
     std::fs::create_dir_all(&log_dir)?;
-    let log_file = std::fs::File::create(log_dir.join("matrixtui.log"))?;
+    let log_name = if daemon_mode { "matrixtui-daemon.log" } else { "matrixtui.log" };
+    let log_file = std::fs::File::create(log_dir.join(log_name))?;
     tracing_subscriber::fmt()
         .with_writer(log_file)
         .with_ansi(false)
         .init();
 
+    // `mtui doctor`: print health checks and exit without starting the UI.
+    // Runs before loading config so a broken config.json is reported, not fatal.
+    if std::env::args().nth(1).as_deref() == Some("doctor") {
+        let protocol = config::Config::load()
+            .map(|c| c.image_protocol)
+            .unwrap_or_else(|_| "auto".to_string());
+        let picker = terminal::image_picker(&protocol);
+        // Opening a store a running UI or daemon is syncing could disturb it
+        let checks = doctor::run_checks(&picker, &[], daemon::sessions_in_use()).await;
+        doctor::print(&checks);
+        return Ok(());
+    }
+
     // Load config and saved accounts
-    let cfg = config::Config::load()?;
+    let mut cfg = config::Config::load()?;
+    // `--store memory`: new logins this run are guest sessions, whatever config.json says
+    cfg.store_override = store_arg()?;
+
+    // Held until exit so `mtui doctor` leaves our session stores alone
+    let _sessions_lock = daemon::lock_sessions()?;
+
+    // `--daemon`: sync and notify without a terminal until the UI takes over
+    if daemon_mode {
+        let picker = ratatui_image::picker::Picker::from_fontsize((8, 16));
+        return daemon::run(App::new(cfg, picker)).await;
+    }
+    // Sessions can only be synced by one process at a time
+    let resume_daemon = daemon::hand_over().await?;
 
     // Detect terminal graphics protocol BEFORE raw mode (query needs normal terminal)
-    let picker = Picker::from_query_stdio().unwrap_or_else(|_| Picker::from_fontsize((8, 16)));
+    let picker = terminal::image_picker(&cfg.image_protocol);
 
     // Terminal setup
     enable_raw_mode()?;
     let mut stdout = io::stdout();
-    execute!(stdout, EnterAlternateScreen, EnableMouseCapture, SetCursorStyle::SteadyBar, EnableBracketedPaste)?;
+    execute!(
+        stdout,
+        EnterAlternateScreen,
+        EnableMouseCapture,
+        SetCursorStyle::SteadyBar,
+        EnableBracketedPaste,
+        EnableFocusChange
+    )?;
     let backend = CrosstermBackend::new(stdout);
     let mut terminal = Terminal::new(backend)?;
 
     // Run the app
     let mut app = App::new(cfg, picker);
+    // `--perf`: overlay frame, event and slowest-operation timings
+    if std::env::args().skip(1).any(|a| a == "--perf") {
+        app.perf = Some(perf::PerfStats::default());
+    }
     app.restore_sessions().await;
     let result = app.run(&mut terminal).await;
 
@@ -52,9 +100,33 @@ async fn main() -> Result<()> {
         LeaveAlternateScreen,
         DisableMouseCapture,
         SetCursorStyle::DefaultUserShape,
-        DisableBracketedPaste
+        DisableBracketedPaste,
+        DisableFocusChange
     )?;
     terminal.show_cursor()?;
 
+    for warning in &app.shutdown_warnings {
+        eprintln!("mtui: {}", warning);
+    }
+    if resume_daemon {
+        if let Err(e) = daemon::respawn() {
+            eprintln!("mtui: failed to restart the background daemon: {}", e);
+        }
+    }
+
     result
 }
+
+/// Value of `--store sqlite|memory`, if given
+fn store_arg() -> Result<Option<String>> {
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--store" {
+            return match args.next().as_deref() {
+                Some(store @ ("sqlite" | "memory")) => Ok(Some(store.to_string())),
+                _ => anyhow::bail!("--store takes sqlite or memory"),
+            };
+        }
+    }
+    Ok(None)
+}
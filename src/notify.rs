@@ -0,0 +1,48 @@
+use std::io::Write;
+use tokio::process::Command;
+
+/// Show a desktop notification without blocking the UI.
+/// Uses termux-notification on Android (Termux) and notify-send elsewhere;
+/// silently does nothing if neither is installed.
+pub fn send(title: &str, body: &str) {
+    let mut cmd = if std::env::var_os("TERMUX_VERSION").is_some() {
+        let mut cmd = Command::new("termux-notification");
+        cmd.args(["--title", title, "--content", body]);
+        cmd
+    } else {
+        let mut cmd = Command::new("notify-send");
+        cmd.args(["--app-name", "MatrixTUI", title, body]);
+        cmd
+    };
+    cmd.stdin(std::process::Stdio::null())
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null());
+    tokio::spawn(async move {
+        let _ = cmd.status().await;
+    });
+}
+
+/// Play a notification sound: `bell` rings the terminal bell, anything else runs
+/// through `sh -c` with MTUI_ROOM and MTUI_SENDER set, without blocking the UI
+pub fn play_sound(sound: &str, room: &str, sender: &str) {
+    match sound.trim() {
+        "" | "off" => {}
+        "bell" => {
+            let mut stdout = std::io::stdout();
+            let _ = stdout.write_all(b"\x07");
+            let _ = stdout.flush();
+        }
+        command => {
+            let mut cmd = Command::new("sh");
+            cmd.args(["-c", command])
+                .env("MTUI_ROOM", room)
+                .env("MTUI_SENDER", sender)
+                .stdin(std::process::Stdio::null())
+                .stdout(std::process::Stdio::null())
+                .stderr(std::process::Stdio::null());
+            tokio::spawn(async move {
+                let _ = cmd.status().await;
+            });
+        }
+    }
+}
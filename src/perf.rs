@@ -0,0 +1,66 @@
+use std::cell::Cell;
+use std::time::{Duration, Instant};
+use tracing::info;
+
+/// How long the slowest operation stays on the overlay before a faster one can replace it
+const SLOWEST_WINDOW: Duration = Duration::from_secs(10);
+
+/// Operations slower than this are also written to the log
+const LOG_THRESHOLD: Duration = Duration::from_millis(50);
+
+/// Timings shown by the `--perf` overlay
+#[derive(Debug, Default)]
+pub struct PerfStats {
+    /// Last full `terminal.draw`
+    pub frame: Duration,
+    /// Last event handled by the main loop, and what kind it was
+    pub event: Duration,
+    pub event_kind: &'static str,
+    /// Last `draw_chat_panel`; written from the draw pass, which only has `&App`
+    pub chat_panel: Cell<Duration>,
+    /// Last `refresh_rooms`
+    pub refresh_rooms: Duration,
+    /// Slowest operation within the last `SLOWEST_WINDOW`
+    pub slowest: Option<(&'static str, Duration, Instant)>,
+}
+
+impl PerfStats {
+    /// Note an operation's duration, keeping it if it's the slowest recently seen
+    pub fn record(&mut self, name: &'static str, elapsed: Duration) {
+        if elapsed >= LOG_THRESHOLD {
+            info!("perf: {} took {:.1}ms", name, ms(elapsed));
+        }
+        let replace = match self.slowest {
+            Some((_, slowest, at)) => elapsed >= slowest || at.elapsed() > SLOWEST_WINDOW,
+            None => true,
+        };
+        if replace {
+            self.slowest = Some((name, elapsed, Instant::now()));
+        }
+    }
+
+    /// Start timing `draw_chat_panel`; the duration is stored when the guard drops
+    pub fn time_chat_panel(&self) -> ScopeTimer<'_> {
+        ScopeTimer {
+            cell: &self.chat_panel,
+            started: Instant::now(),
+        }
+    }
+}
+
+/// Writes the time since it was created into a cell when dropped
+pub struct ScopeTimer<'a> {
+    cell: &'a Cell<Duration>,
+    started: Instant,
+}
+
+impl Drop for ScopeTimer<'_> {
+    fn drop(&mut self) {
+        self.cell.set(self.started.elapsed());
+    }
+}
+
+/// Duration as fractional milliseconds
+pub fn ms(d: Duration) -> f64 {
+    d.as_secs_f64() * 1000.0
+}
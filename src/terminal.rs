@@ -0,0 +1,60 @@
+use ratatui_image::picker::{Picker, ProtocolType};
+use std::process::Command;
+use tracing::{info, warn};
+
+/// Running inside tmux — graphics sequences must be passed through to the outer terminal
+pub fn in_tmux() -> bool {
+    std::env::var_os("TMUX").is_some()
+}
+
+/// Ask tmux to forward escape sequences to the outer terminal (needs tmux 3.3+)
+pub fn enable_tmux_passthrough() -> bool {
+    Command::new("tmux")
+        .args(["set", "-p", "allow-passthrough", "on"])
+        .output()
+        .map(|o| o.status.success())
+        .unwrap_or(false)
+}
+
+/// Current cell size in pixels, from the window size the terminal reports.
+/// None when the terminal doesn't report pixel dimensions.
+pub fn window_font_size() -> Option<(u16, u16)> {
+    let size = crossterm::terminal::window_size().ok()?;
+    if size.columns == 0 || size.rows == 0 || size.width == 0 || size.height == 0 {
+        return None;
+    }
+    Some((size.width / size.columns, size.height / size.rows))
+}
+
+/// Detect the image protocol. Must run BEFORE raw mode (the query needs a normal terminal).
+///
+/// `preference` is the config `image_protocol`: "auto" queries the terminal, anything
+/// else forces that protocol. Inside tmux without passthrough, auto degrades to halfblocks
+/// since kitty/sixel/iterm2 sequences would otherwise be printed as garbage.
+pub fn image_picker(preference: &str) -> Picker {
+    let passthrough = if in_tmux() {
+        let ok = enable_tmux_passthrough();
+        info!("tmux detected, passthrough {}", if ok { "enabled" } else { "unavailable" });
+        ok
+    } else {
+        true
+    };
+
+    let mut picker = Picker::from_query_stdio().unwrap_or_else(|_| Picker::from_fontsize((8, 16)));
+
+    let forced = match preference {
+        "halfblocks" => Some(ProtocolType::Halfblocks),
+        "sixel" => Some(ProtocolType::Sixel),
+        "kitty" => Some(ProtocolType::Kitty),
+        "iterm2" => Some(ProtocolType::Iterm2),
+        _ => None,
+    };
+    if let Some(protocol) = forced {
+        picker.set_protocol_type(protocol);
+    } else if !passthrough && picker.protocol_type() != ProtocolType::Halfblocks {
+        warn!("tmux passthrough unavailable, falling back to halfblocks");
+        picker.set_protocol_type(ProtocolType::Halfblocks);
+    }
+    info!("Image protocol: {:?}", picker.protocol_type());
+    picker
+}
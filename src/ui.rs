@@ -1,10 +1,15 @@
 use ratatui::{
     prelude::*,
-    widgets::{Block, Borders, Clear, List, ListItem, Paragraph, Wrap},
+    widgets::{Block, Borders, Clear, List, ListItem, ListState, Paragraph, Wrap},
 };
 use ratatui_image::StatefulImage;
+use std::fmt::Write;
 
-use crate::app::{App, FileKind, Focus, MessageContent, Overlay, RoomSortMode, SasOverlayState};
+use crate::app::{
+    App, Completion, FileKind, Focus, MessageContent, Overlay, RoomSortMode, SasOverlayState, ThreepidStep,
+};
+use crate::doctor::Level;
+use crate::perf::{PerfStats, ms};
 use matrix_sdk::ruma::events::room::MediaSource;
 
 // --- Theme system ---
@@ -115,13 +120,23 @@ pub fn draw(f: &mut Frame, app: &App) {
     let size = f.area();
     let width = size.width;
 
-    // Decide layout based on terminal width
-    if width >= 120 {
-        draw_three_column(f, app, size);
-    } else if width >= 60 {
-        draw_two_column(f, app, size);
-    } else {
-        draw_single_column(f, app, size);
+    // Hit-test rows are re-recorded by whichever panels are drawn this frame
+    app.room_hits.borrow_mut().clear();
+    app.message_hits.borrow_mut().clear();
+    app.message_area.set(Rect::default());
+    app.chat_visible.set(false);
+
+    // Decide layout based on terminal width, unless forced with `L`
+    let columns = match app.config.layout_columns {
+        n @ 1..=3 => n,
+        _ if width >= app.config.three_column_width => 3,
+        _ if width >= app.config.two_column_width => 2,
+        _ => 1,
+    };
+    match columns {
+        3 => draw_three_column(f, app, size),
+        2 => draw_two_column(f, app, size),
+        _ => draw_single_column(f, app, size),
     }
 
     // Draw overlays on top
@@ -131,6 +146,10 @@ pub fn draw(f: &mut Frame, app: &App) {
         Overlay::RoomSwitcher => draw_switcher_overlay(f, app),
         Overlay::Settings => draw_settings_overlay(f, app),
         Overlay::ProfileEditor => draw_profile_overlay(f, app),
+        Overlay::IdentityServer => draw_identity_overlay(f, app),
+        Overlay::Threepids => draw_threepid_overlay(f, app),
+        Overlay::RoomBatch => draw_room_batch_overlay(f, app),
+        Overlay::CodePaste => draw_code_paste_overlay(f, app),
         Overlay::RoomCreator => draw_creator_overlay(f, app),
         Overlay::RoomEditor => draw_editor_overlay(f, app),
         Overlay::Recovery => draw_recovery_overlay(f, app),
@@ -139,8 +158,50 @@ pub fn draw(f: &mut Frame, app: &App) {
         Overlay::EmojiPicker => draw_emoji_picker_overlay(f, app),
         Overlay::RoomInfo => draw_room_info_overlay(f, app),
         Overlay::FileConfirm => draw_file_confirm_overlay(f, app),
+        Overlay::JoinFavorites => draw_join_favorites_overlay(f, app),
+        Overlay::MemberList => draw_member_list_overlay(f, app),
+        Overlay::Knocks => draw_knocks_overlay(f, app),
+        Overlay::TimelineFilters => draw_timeline_filters_overlay(f, app),
+        Overlay::Confirm => draw_confirm_overlay(f, app),
+        Overlay::DeviceReview => draw_device_review_overlay(f, app),
+        Overlay::Broadcast => draw_broadcast_overlay(f, app),
+        Overlay::StatusHistory => draw_status_history_overlay(f, app),
+        Overlay::Diagnostics => draw_diagnostics_overlay(f, app),
         Overlay::None => {}
     }
+
+    if let Some(perf) = &app.perf {
+        draw_perf_overlay(f, app, perf);
+    }
+}
+
+/// Timings from the last frame in the top-right corner (`--perf`)
+fn draw_perf_overlay(f: &mut Frame, app: &App, perf: &PerfStats) {
+    let theme = &app.theme;
+    let mut lines = vec![
+        format!(" frame   {:>7.1}ms ", ms(perf.frame)),
+        format!(" chat    {:>7.1}ms ", ms(perf.chat_panel.get())),
+        format!(" rooms   {:>7.1}ms ", ms(perf.refresh_rooms)),
+        format!(" {:<7} {:>7.1}ms ", perf.event_kind, ms(perf.event)),
+    ];
+    if let Some((name, elapsed, _)) = perf.slowest {
+        lines.push(format!(" slowest {:>7.1}ms ", ms(elapsed)));
+        lines.push(format!("  {} ", name));
+    }
+    let width = lines.iter().map(|l| l.chars().count()).max().unwrap_or(0) as u16;
+    let size = f.area();
+    let area = Rect::new(
+        size.right().saturating_sub(width + 1),
+        size.y + 1,
+        width.min(size.width),
+        (lines.len() as u16).min(size.height),
+    );
+    f.render_widget(Clear, area);
+    let text: Vec<Line> = lines.into_iter().map(Line::from).collect();
+    f.render_widget(
+        Paragraph::new(text).style(Style::default().fg(theme.text).bg(theme.status_bg)),
+        area,
+    );
 }
 
 fn draw_three_column(f: &mut Frame, app: &App, area: Rect) {
@@ -226,7 +287,29 @@ fn draw_accounts_panel(f: &mut Frame, app: &App, area: Rect) {
             } else {
                 Style::default()
             };
-            ListItem::new(format!(" {} {}", marker, label)).style(style)
+            // Two cells for the avatar, drawn in after the list; until it loads
+            // (or if there isn't one) show the localpart's initial
+            let badge = if app.account_avatars.contains_key(&acct.user_id) {
+                Span::raw("  ")
+            } else {
+                let initial = acct
+                    .user_id
+                    .trim_start_matches('@')
+                    .chars()
+                    .next()
+                    .map(|c| c.to_uppercase().to_string())
+                    .unwrap_or_default();
+                Span::styled(
+                    format!("{:^2}", initial),
+                    Style::default().fg(theme.status_bg).bg(theme.accent),
+                )
+            };
+            ListItem::new(Line::from(vec![
+                Span::raw(format!(" {} ", marker)),
+                badge,
+                Span::raw(format!(" {}", label)),
+            ]))
+            .style(style)
         })
         .collect();
 
@@ -236,8 +319,26 @@ fn draw_accounts_panel(f: &mut Frame, app: &App, area: Rect) {
             .style(Style::default().fg(theme.dimmed)),
     );
 
+    // Scrolls to keep the selected account in view
     let list = List::new(items).block(block);
-    f.render_widget(list, area);
+    let mut state = ListState::default().with_selected(Some(app.selected_account));
+    f.render_stateful_widget(list, area, &mut state);
+    let offset = state.offset();
+
+    // Avatars over their badge cells: border + " ● " puts them at column 4
+    for (i, acct) in app.accounts.iter().enumerate().skip(offset) {
+        let Some(protocol) = app.account_avatars.get(&acct.user_id) else {
+            continue;
+        };
+        let y = area.y + 1 + (i - offset) as u16;
+        if y + 1 >= area.bottom() || area.width < 8 {
+            break;
+        }
+        if let Ok(mut guard) = protocol.lock() {
+            let avatar_area = Rect::new(area.x + 4, y, 2, 1);
+            f.render_stateful_widget(StatefulImage::default(), avatar_area, &mut *guard);
+        }
+    }
 }
 
 fn draw_rooms_panel(f: &mut Frame, app: &App, area: Rect) {
@@ -248,8 +349,13 @@ fn draw_rooms_panel(f: &mut Frame, app: &App, area: Rect) {
     } else {
         Style::default().fg(theme.dimmed)
     };
+    let title = if app.marked_rooms.is_empty() {
+        " Rooms ".to_string()
+    } else {
+        format!(" Rooms ({} marked, b: batch) ", app.marked_rooms.len())
+    };
     let block = Block::default()
-        .title(" Rooms ")
+        .title(title)
         .borders(Borders::ALL)
         .border_style(border_style);
 
@@ -264,6 +370,12 @@ fn draw_rooms_panel(f: &mut Frame, app: &App, area: Rect) {
     let has_separator = app.favorites_count > 0
         && app.favorites_count < app.all_rooms.len();
 
+    // Row under the mouse pointer, relative to the first list row
+    let hover_row = app.mouse_pos.and_then(|(x, y)| {
+        let inside = x > area.x && x + 1 < area.right() && y > area.y && y + 1 < area.bottom();
+        inside.then(|| (y - area.y - 1) as usize)
+    });
+
     let mut items: Vec<ListItem> = Vec::new();
     // Track mapping from visual index -> all_rooms index
     // The separator is visual-only and not in all_rooms
@@ -288,14 +400,21 @@ fn draw_rooms_panel(f: &mut Frame, app: &App, area: Rect) {
         } else {
             "#"
         };
-        let unread = if room.unread > 0 {
+        let mut unread = if room.unread > 0 {
             format!(" ({})", room.unread)
         } else {
             String::new()
         };
+        let threads = app.unread_thread_count(&room.id);
+        if threads > 0 {
+            let _ = write!(unread, " \u{21b3}{}", threads);
+        }
+
+        let mark = if app.marked_rooms.contains(&room.id) { "\u{25cf}" } else { " " };
 
         let is_active = Some(&room.id) == app.active_room.as_ref();
         let is_selected = i == app.selected_room;
+        let is_hovered = hover_row == Some(visual_to_room.len());
 
         let style = if is_active {
             Style::default()
@@ -303,6 +422,8 @@ fn draw_rooms_panel(f: &mut Frame, app: &App, area: Rect) {
                 .add_modifier(Modifier::BOLD)
         } else if is_selected && focused {
             Style::default().fg(theme.text).bg(theme.highlight_bg)
+        } else if is_hovered {
+            Style::default().fg(theme.text).bg(theme.status_bg)
         } else if room.unread > 0 {
             Style::default()
                 .fg(theme.text)
@@ -319,10 +440,20 @@ fn draw_rooms_panel(f: &mut Frame, app: &App, area: Rect) {
             room.name.clone()
         };
 
-        items.push(ListItem::new(format!(" {}{}{}", prefix, name, unread)).style(style));
+        items.push(ListItem::new(format!("{}{}{}{}", mark, prefix, name, unread)).style(style));
         visual_to_room.push(Some(i));
     }
 
+    // Record list rows for mouse hit-testing
+    let inner = block.inner(area);
+    let mut hits = app.room_hits.borrow_mut();
+    for (row, room_idx) in visual_to_room.iter().enumerate().take(inner.height as usize) {
+        if let Some(room_idx) = room_idx {
+            let rect = Rect::new(inner.x, inner.y + row as u16, inner.width, 1);
+            hits.push((rect, *room_idx));
+        }
+    }
+
     let list = List::new(items).block(block);
     f.render_widget(list, area);
 }
@@ -391,7 +522,14 @@ fn inject_osc8_link(buf: &mut Buffer, x: u16, y: u16, len: u16, url: &str) {
 }
 
 fn draw_chat_panel(f: &mut Frame, app: &App, area: Rect) {
+    let _timer = app.perf.as_ref().map(|p| p.time_chat_panel());
     let theme = &app.theme;
+    app.chat_visible.set(true);
+    let room_settings = app
+        .active_room
+        .as_ref()
+        .map(|id| app.config.room_settings(id.as_str()))
+        .unwrap_or_default();
     let focused = app.focus == Focus::Chat || app.focus == Focus::Input;
     let border_style = if focused {
         Style::default().fg(theme.accent)
@@ -401,9 +539,21 @@ fn draw_chat_panel(f: &mut Frame, app: &App, area: Rect) {
 
     let title = if app.downloading_keys {
         " Downloading room keys... ".to_string()
+    } else if let Some(ref target) = app.peeking {
+        let id_or_alias = target.split('?').next().unwrap_or(target);
+        format!(" {} · read-only preview ", id_or_alias)
     } else if let Some(room_id) = &app.active_room {
         if let Some(room) = app.all_rooms.iter().find(|r| &r.id == room_id) {
-            format!(" {} · {} ", room.name, room.account_id)
+            let status = room
+                .dm_user
+                .as_ref()
+                .and_then(|u| app.presence_status.get(u));
+            match status {
+                Some(status) => format!(" {} ({}) · {} ", room.name, status, room.account_id),
+                None => format!(" {} · {} ", room.name, room.account_id),
+            }
+        } else if let Some(removed) = app.active_removed() {
+            format!(" {} · no longer a member ", removed.room_name)
         } else {
             " Chat ".to_string()
         }
@@ -430,7 +580,8 @@ fn draw_chat_panel(f: &mut Frame, app: &App, area: Rect) {
     let input_height = (clamped_lines as u16) + 2; // +2 for borders
 
     // Typing indicator height
-    let typing_height: u16 = if !app.typing_users.is_empty() { 1 } else { 0 };
+    let banner = app.chat_banner();
+    let typing_height: u16 = if !app.typing_users.is_empty() || banner.is_some() { 1 } else { 0 };
 
     // Split chat area: messages + typing + input
     let chat_layout = Layout::default()
@@ -445,13 +596,33 @@ fn draw_chat_panel(f: &mut Frame, app: &App, area: Rect) {
     let msg_area = chat_layout[0];
     let typing_area = chat_layout[1];
     let input_area = chat_layout[2];
+    app.message_area.set(msg_area);
 
     // Messages
-    let msg_block = Block::default()
+    let mut msg_block = Block::default()
         .title(Span::styled(title, title_style))
         .borders(Borders::ALL)
         .border_style(if app.downloading_keys { Style::default().fg(theme.status_ok) } else { border_style });
 
+    // Search query + match count along the bottom border
+    let search_matches = app.search_matches();
+    if app.search_active || !app.search_query.is_empty() {
+        let cursor = if app.search_active { "\u{2588}" } else { "" };
+        msg_block = msg_block.title_bottom(Line::from(Span::styled(
+            format!(" /{}{} ({} matches) ", app.search_query, cursor, search_matches.len()),
+            Style::default().fg(theme.status_warn),
+        )));
+    }
+    if let Some(ref sender) = app.sender_filter {
+        msg_block = msg_block.title_bottom(
+            Line::from(Span::styled(
+                format!(" only {} (o to clear) ", sender),
+                Style::default().fg(theme.status_warn),
+            ))
+            .right_aligned(),
+        );
+    }
+
     if app.messages.is_empty() && app.active_room.is_none() {
         let welcome = Paragraph::new("\n  Select a room to start chatting\n\n  Ctrl+K  quick room switcher\n  a       add account\n  n       new room\n  e       edit active room\n  s       settings\n  ?       help")
             .style(Style::default().fg(theme.dimmed))
@@ -473,10 +644,19 @@ fn draw_chat_panel(f: &mut Frame, app: &App, area: Rect) {
         let mut start = end;
         for i in (0..end).rev() {
             let msg = &app.messages[i];
+            if !app.is_message_visible(msg) {
+                continue;
+            }
             let is_reply = msg.reply_to_sender.is_some();
             let indent = if is_reply { "    " } else { "  " };
             let indent_w = indent.chars().count();
-            let mut msg_h = wrapped_height_indented(msg.sender.chars().count(), indent_w, inner_width);
+            // Tagged bots put sender and text on one line
+            let compact = app.is_compact_bot(msg);
+            let mut msg_h = if compact {
+                0
+            } else {
+                wrapped_height_indented(app.sender_label(msg).chars().count(), indent_w, inner_width)
+            };
             match &msg.content {
                 MessageContent::Image { protocol, .. } => {
                     if protocol.is_some() {
@@ -496,20 +676,40 @@ fn draw_chat_panel(f: &mut Frame, app: &App, area: Rect) {
                 }
                 MessageContent::Text(_) => {
                     let body_str = msg.body_text();
-                    msg_h += wrapped_height_indented(body_str.chars().count(), indent_w, inner_width);
+                    let mut len = body_str.chars().count();
+                    if compact {
+                        len += app.sender_label(msg).chars().count() + 2;
+                    }
+                    msg_h += wrapped_height_indented(len, indent_w, inner_width);
                 }
             }
             // Reply context line (may wrap)
             if is_reply {
                 let reply_content = format!("\u{2514} {}: {}",
                     msg.reply_to_sender.as_deref().unwrap_or(""),
-                    msg.reply_to_body.as_deref().unwrap_or(""));
+                    app.reply_quote(msg));
                 msg_h += wrapped_height_indented(reply_content.chars().count(), 2, inner_width);
             }
+            // Full original under the selected reply
+            if let Some(original) = app.reply_preview(i) {
+                msg_h += original
+                    .lines()
+                    .map(|l| wrapped_height_indented(l.chars().count(), 4, inner_width))
+                    .sum::<usize>();
+            }
             // Reaction line
             if !msg.reactions.is_empty() {
                 msg_h += 1;
             }
+            // Failed send detail (may wrap)
+            if let Some(ref err) = msg.send_error {
+                let err_content = format!("\u{26a0} not sent: {}", err);
+                msg_h += wrapped_height_indented(err_content.chars().count(), 2, inner_width);
+            }
+            // Read-on-another-device marker
+            if app.is_read_elsewhere(msg) {
+                msg_h += 1;
+            }
             // Unread separator
             if app.first_unread_index == Some(i) {
                 msg_h += 1;
@@ -535,18 +735,32 @@ fn draw_chat_panel(f: &mut Frame, app: &App, area: Rect) {
         // Track link positions for OSC 8: (line_offset, source, text_len)
         let mut link_positions: Vec<(usize, MediaSource, usize)> = Vec::new();
         let mut visible: Vec<Line> = Vec::new();
+        // Lines each message occupies: (first_line, end_line, msg_index)
+        let mut message_spans: Vec<(usize, usize, usize)> = Vec::new();
 
         for (i, msg) in visible_msgs.iter().enumerate() {
             let msg_idx = start + i;
+            if !app.is_message_visible(msg) {
+                continue;
+            }
             let is_selected = app.selected_message == Some(msg_idx);
+            let is_bot = app.config.is_bot(&msg.sender);
+            let compact = app.is_compact_bot(msg);
+            let sender_fg = if msg.send_error.is_some() {
+                theme.status_err
+            } else if is_bot {
+                theme.text_dim
+            } else {
+                theme.accent
+            };
             let sender_style = if is_selected {
                 Style::default()
-                    .fg(theme.accent)
+                    .fg(sender_fg)
                     .bg(theme.highlight_bg)
                     .add_modifier(Modifier::BOLD)
             } else {
                 Style::default()
-                    .fg(theme.accent)
+                    .fg(sender_fg)
                     .add_modifier(Modifier::BOLD)
             };
             let body_style = if is_selected {
@@ -568,20 +782,29 @@ fn draw_chat_panel(f: &mut Frame, app: &App, area: Rect) {
                 )));
             }
 
+            let first_line = visible.len();
+
             // Reply context line + indented sender/body for replies
             let is_reply = msg.reply_to_sender.is_some();
-            if let (Some(reply_sender), Some(reply_body)) =
-                (&msg.reply_to_sender, &msg.reply_to_body)
-            {
-                let reply_content = format!("\u{2514} {}: {}", reply_sender, reply_body);
+            if let Some(ref reply_sender) = msg.reply_to_sender {
+                let reply_content =
+                    format!("\u{2514} {}: {}", reply_sender, app.reply_quote(msg));
                 let reply_style = Style::default()
                     .fg(theme.text_dim)
                     .add_modifier(Modifier::ITALIC);
                 visible.extend(wrap_with_indent(&reply_content, "  ", inner_width, reply_style));
             }
+            if let Some(original) = app.reply_preview(msg_idx) {
+                let preview_style = Style::default().fg(theme.text_dim);
+                for line in original.lines() {
+                    visible.extend(wrap_with_indent(line, "  \u{2502} ", inner_width, preview_style));
+                }
+            }
 
             let indent = if is_reply { "    " } else { "  " };
-            visible.extend(wrap_with_indent(&msg.sender, indent, inner_width, sender_style));
+            if !compact {
+                visible.extend(wrap_with_indent(&app.sender_label(msg), indent, inner_width, sender_style));
+            }
 
             match &msg.content {
                 MessageContent::Image { body, loading, protocol, source, .. } => {
@@ -592,7 +815,7 @@ fn draw_chat_panel(f: &mut Frame, app: &App, area: Rect) {
                         for _ in 0..8 {
                             visible.push(Line::from(""));
                         }
-                    } else if *loading {
+                    } else if *loading && room_settings.images {
                         let load_text = format!("[loading {}...]", body);
                         let load_style = Style::default()
                             .fg(theme.text_dim)
@@ -620,6 +843,11 @@ fn draw_chat_panel(f: &mut Frame, app: &App, area: Rect) {
                         .add_modifier(Modifier::UNDERLINED);
                     visible.extend(wrap_with_indent(&display_content, indent, inner_width, link_style));
                 }
+                MessageContent::Text(_) if compact => {
+                    let line = format!("{}: {}", app.sender_label(msg), msg.body_text());
+                    let bot_style = body_style.fg(theme.text_dim);
+                    visible.extend(wrap_with_indent(&line, indent, inner_width, bot_style));
+                }
                 MessageContent::Text(_) => {
                     let body_str = msg.body_text();
                     visible.extend(wrap_with_indent(body_str, indent, inner_width, body_style));
@@ -645,6 +873,23 @@ fn draw_chat_panel(f: &mut Frame, app: &App, area: Rect) {
                 )));
             }
 
+            // Failed send detail — Resend / Discard live in the action menu
+            if let Some(ref err) = msg.send_error {
+                let err_content = format!("\u{26a0} not sent: {}", err);
+                let err_style = Style::default().fg(theme.status_err);
+                visible.extend(wrap_with_indent(&err_content, "  ", inner_width, err_style));
+            }
+
+            // Read-on-another-device marker
+            if app.is_read_elsewhere(msg) {
+                visible.push(Line::from(Span::styled(
+                    format!("{:>width$}", "\u{2713} read elsewhere", width = inner_width),
+                    Style::default().fg(theme.text_dim).add_modifier(Modifier::DIM),
+                )));
+            }
+
+            message_spans.push((first_line, visible.len(), msg_idx));
+
             // Add separator after every message except the last
             if i + 1 < msg_count {
                 visible.push(Line::from(""));
@@ -666,13 +911,38 @@ fn draw_chat_panel(f: &mut Frame, app: &App, area: Rect) {
         let messages = Paragraph::new(visible).block(msg_block).wrap(Wrap { trim: false });
         f.render_widget(messages, msg_area);
 
-        // Render StatefulImage widgets as overlays at tracked positions
+        // Record message rows for mouse hit-testing, and highlight the one under the pointer
         let inner_area = Rect::new(
             msg_area.x + 1,
             msg_area.y + 1,
             msg_area.width.saturating_sub(2),
             msg_area.height.saturating_sub(2),
         );
+        {
+            let mut hits = app.message_hits.borrow_mut();
+            for (first, end, msg_idx) in &message_spans {
+                let top = inner_area.y + (first + top_padding) as u16;
+                let bottom = (inner_area.y + (end + top_padding) as u16).min(inner_area.bottom());
+                if top < bottom {
+                    let rect = Rect::new(inner_area.x, top, inner_area.width, bottom - top);
+                    hits.push((rect, *msg_idx));
+                }
+            }
+            if let Some(pos) = app.mouse_pos {
+                if let Some(idx) = App::hit(&hits, pos) {
+                    if app.selected_message != Some(idx) {
+                        if let Some((rect, _)) = hits.iter().find(|(_, i)| *i == idx) {
+                            f.buffer_mut().set_style(*rect, Style::default().bg(theme.status_bg));
+                        }
+                    }
+                }
+            }
+        }
+
+        // Search minimap: mark where matches sit in the loaded history on the right border
+        draw_search_markers(f, app, &search_matches, msg_area);
+
+        // Render StatefulImage widgets as overlays at tracked positions
         for (line_offset, msg_idx) in &image_positions {
             if let Some(msg) = app.messages.get(*msg_idx) {
                 if let MessageContent::Image { protocol: Some(ref proto), .. } = msg.content {
@@ -716,13 +986,19 @@ fn draw_chat_panel(f: &mut Frame, app: &App, area: Rect) {
         }
     }
 
-    // Typing indicator
-    if !app.typing_users.is_empty() {
-        let typing_text = if app.typing_users.len() == 1 {
-            format!("  {} is typing...", app.typing_users[0])
-        } else {
-            format!("  {} are typing...", app.typing_users.join(", "))
-        };
+    // Typing indicator, or a banner while the composer can't be used
+    if let Some(text) = banner {
+        let banner = Paragraph::new(Span::styled(
+            format!("  {}", text),
+            Style::default()
+                .fg(theme.status_warn)
+                .add_modifier(Modifier::BOLD),
+        ));
+        f.render_widget(banner, typing_area);
+    } else if let Some(summary) = app.typing_summary() {
+        // One more dot every 4 ticks (~400ms), padded so the line doesn't jitter
+        let dots = ".".repeat(app.typing_tick / 4 % 4);
+        let typing_text = format!("  {}{:<3}", summary, dots);
         let typing = Paragraph::new(Span::styled(
             typing_text,
             Style::default()
@@ -739,7 +1015,9 @@ fn draw_chat_panel(f: &mut Frame, app: &App, area: Rect) {
     } else {
         Style::default().fg(theme.dimmed)
     };
-    let input_title = if let Some((_, ref sender, _)) = app.replying_to {
+    let input_title = if app.composer_edit.is_some() {
+        " Edit message (Enter save, Esc cancel) ".to_string()
+    } else if let Some((_, ref sender, _)) = app.replying_to {
         let short_name = sender.split(':').next().unwrap_or(sender);
         format!(" Reply to {} (Esc cancel) ", short_name)
     } else if input_focused {
@@ -747,11 +1025,39 @@ fn draw_chat_panel(f: &mut Frame, app: &App, area: Rect) {
     } else {
         String::new()
     };
-    let input_block = Block::default()
+    let mut input_block = Block::default()
         .borders(Borders::ALL)
         .border_style(input_style)
         .title(input_title);
 
+    // Per-room composer settings, toggled with /markdown, /notify, /images
+    if app.active_room.is_some() {
+        let flag = |label: &'static str, on: bool| {
+            if on {
+                Span::styled(label, Style::default().fg(theme.accent))
+            } else {
+                Span::styled(
+                    label,
+                    Style::default()
+                        .fg(theme.dimmed)
+                        .add_modifier(Modifier::CROSSED_OUT),
+                )
+            }
+        };
+        input_block = input_block.title_top(
+            Line::from(vec![
+                Span::raw(" "),
+                flag("md", room_settings.markdown),
+                Span::raw(" "),
+                flag("notify", room_settings.notifications),
+                Span::raw(" "),
+                flag("img", room_settings.images),
+                Span::raw(" "),
+            ])
+            .right_aligned(),
+        );
+    }
+
     let input_text = Paragraph::new(app.input.as_str())
         .block(input_block)
         .wrap(Wrap { trim: false });
@@ -766,6 +1072,69 @@ fn draw_chat_panel(f: &mut Frame, app: &App, area: Rect) {
             input_area.x + 1 + cursor_col as u16,
             input_area.y + 1 + cursor_row as u16,
         ));
+        if let Some(ref completion) = app.completion {
+            draw_completion_popup(f, app, completion, input_area);
+        }
+    }
+}
+
+/// Candidates for the word under the cursor, drawn just above the input box
+fn draw_completion_popup(f: &mut Frame, app: &App, completion: &Completion, input_area: Rect) {
+    let theme = &app.theme;
+    let height = (completion.items.len() as u16 + 2).min(input_area.y);
+    if height < 3 {
+        return;
+    }
+    let area = Rect::new(input_area.x, input_area.y - height, input_area.width, height);
+    f.render_widget(Clear, area);
+
+    let block = Block::default()
+        .title_bottom(Line::from(" Tab/Enter complete  Esc dismiss ").right_aligned())
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(theme.dimmed));
+
+    let items: Vec<ListItem> = completion
+        .items
+        .iter()
+        .enumerate()
+        .map(|(i, (text, hint))| {
+            let style = if i == completion.selected {
+                Style::default().fg(theme.accent).bg(theme.highlight_bg)
+            } else {
+                Style::default()
+            };
+            ListItem::new(Line::from(vec![
+                Span::styled(format!(" {} ", text), style),
+                Span::styled(hint.clone(), style.fg(theme.text_dim)),
+            ]))
+        })
+        .collect();
+
+    f.render_widget(List::new(items).block(block), area);
+}
+
+/// Draw one marker per search match on the right border of the message area,
+/// positioned proportionally to the match's place in the loaded history.
+fn draw_search_markers(f: &mut Frame, app: &App, matches: &[usize], msg_area: Rect) {
+    let track_h = msg_area.height.saturating_sub(2) as usize;
+    if matches.is_empty() || track_h == 0 || msg_area.width < 2 {
+        return;
+    }
+    let theme = &app.theme;
+    let last = app.messages.len().saturating_sub(1).max(1);
+    let x = msg_area.right() - 1;
+    for &idx in matches {
+        let row = idx * (track_h - 1) / last;
+        let y = msg_area.y + 1 + row as u16;
+        let is_current = app.selected_message == Some(idx);
+        let style = if is_current {
+            Style::default().fg(theme.accent).add_modifier(Modifier::BOLD)
+        } else {
+            Style::default().fg(theme.status_warn)
+        };
+        if let Some(cell) = f.buffer_mut().cell_mut(ratatui::layout::Position { x, y }) {
+            cell.set_symbol("\u{25c6}").set_style(style);
+        }
     }
 }
 
@@ -799,6 +1168,22 @@ fn draw_status_bar(f: &mut Frame, app: &App, area: Rect) {
     let status = Paragraph::new(Line::from(spans))
         .style(Style::default().bg(theme.status_bg));
     f.render_widget(status, area);
+
+    // Clock, right-aligned over the end of the bar (redrawn every tick)
+    if app.config.show_clock {
+        let now = chrono::Local::now();
+        let mut clock = String::new();
+        if write!(clock, " {} ", now.format(&app.config.clock_format)).is_err() {
+            clock = format!(" {} ", now.format("%H:%M"));
+        }
+        let width = (clock.chars().count() as u16).min(area.width);
+        let clock_area = Rect::new(area.right() - width, area.y, width, 1);
+        f.render_widget(
+            Paragraph::new(Span::styled(clock, Style::default().fg(theme.text_dim)))
+                .style(Style::default().bg(theme.status_bg)),
+            clock_area,
+        );
+    }
 }
 
 fn draw_login_overlay(f: &mut Frame, app: &App) {
@@ -877,11 +1262,9 @@ fn draw_login_overlay(f: &mut Frame, app: &App) {
     f.render_widget(hint, fields[9]);
 }
 
-fn draw_help_overlay(f: &mut Frame, app: &App) {
-    let theme = &app.theme;
-    let term = f.area();
-
-    let help_text = vec![
+/// Help overlay contents: section headers end in ':', entries are "key  description"
+pub fn help_lines() -> Vec<&'static str> {
+    vec![
         "",
         "  Navigation:",
         "    Tab/Shift+Tab    Cycle panels",
@@ -896,31 +1279,123 @@ fn draw_help_overlay(f: &mut Frame, app: &App) {
         "    s                Settings / themes",
         "    n                New room",
         "    e                Edit active room",
-        "    ?                Toggle this help",
+        "    L                Layout: auto / 1 / 2 / 3 columns",
+        "    H                Status history (last 100 messages)",
+        "    ?                Toggle this help (/ to search it)",
         "",
         "  Rooms:",
         "    f                Toggle favorite",
         "    Shift+Up/Down    Reorder favorites",
+        "    Space            Mark room for batch actions",
+        "    b                Favorite / mute / mark read / leave marked",
+        "    Esc              Clear marks",
         "",
         "  Chat:",
         "    Up/Down          Select / scroll messages",
-        "    Enter            Message actions (edit/delete)",
+        "    Enter            Message actions (edit/delete/resend)",
         "    r                Reply to selected message",
+        "    z                Expand / collapse a reply's quote",
         "    e                React to selected message",
+        "    E                React with last-used emoji",
+        "    m                Member list (Enter jumps to last message)",
+        "    K                Join requests (let in / turn away knocks)",
+        "    o                Only show the selected sender (toggle)",
+        "    F                Timeline filters (notices/bots/media/bridges)",
+        "    /                Search loaded messages",
+        "    n / N            Older / newer search match",
         "    Ctrl+I           Room info panel",
         "    Tab              Focus input box",
+        "    Up (empty input) Edit your last message",
         "    Esc              Deselect / back to rooms",
         "    Home/End         Jump to oldest / newest",
-    ];
+        "",
+        "  Commands (type in the input box):",
+        "    /markdown [on|off]   Send this room's messages as markdown",
+        "    /notify [on|off]     Notifications for this room",
+        "    /images [on|off]     Inline images for this room",
+        "    /timesort [on|off]   Order this room's history by timestamp",
+        "    /sound <dm|mention> [cmd|bell|off]  Alert sound for this room (cmd runs in sh)",
+        "    /topic [text]        Edit the room topic inline",
+        "    /status [text]       Set or clear this account's status message",
+        "    /export-keymap [path]  Save this help as a Markdown cheat sheet",
+        "    /export-settings [path]  Save settings (no tokens) to a file",
+        "    /import-settings <path>  Load settings from an exported file",
+        "    /peek <#alias|link>  Read a world-readable room without joining",
+        "    /join [#alias|!id]   Join a room (the peeked one by default)",
+        "    /forget              Forget a room you were kicked or banned from",
+        "    /broadcast <message> Send to every marked room, confirming each",
+        "    /export-favorites [path] Share your favorites (IDs + aliases)",
+        "    /import-favorites <path> Favorite a shared list, joining missing rooms",
+        "    //text               Send a message starting with /",
+        "    @ # /  then Tab      Complete users, room aliases and commands",
+    ]
+}
+
+/// The help overlay as a Markdown cheat sheet, one table per section
+pub fn help_markdown() -> String {
+    let mut out = String::from("# MatrixTUI keys\n");
+    for line in help_lines() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        if let Some(section) = line.strip_suffix(':') {
+            let _ = write!(out, "\n## {}\n\n| Key | Action |\n|-----|--------|\n", section);
+            continue;
+        }
+        let (key, action) = line.split_once("  ").unwrap_or((line, ""));
+        let _ = writeln!(
+            out,
+            "| `{}` | {} |",
+            key.trim().replace('|', "\\|"),
+            action.trim().replace('|', "\\|")
+        );
+    }
+    out
+}
 
-    let content_height = help_text.len() as u16;
+/// Help lines containing `query`, each under its section header
+pub fn filtered_help_lines(query: &str) -> Vec<&'static str> {
+    let lines = help_lines();
+    if query.is_empty() {
+        return lines;
+    }
+    let query = query.to_lowercase();
+    let mut out = Vec::new();
+    let mut header = None;
+    for line in lines {
+        if line.trim_end().ends_with(':') {
+            header = Some(line);
+        } else if line.to_lowercase().contains(&query) {
+            if let Some(h) = header.take() {
+                out.push(h);
+            }
+            out.push(line);
+        }
+    }
+    out
+}
+
+fn draw_help_overlay(f: &mut Frame, app: &App) {
+    let theme = &app.theme;
+    let term = f.area();
+
+    let help_text = filtered_help_lines(&app.help_search);
+    // Keep the box the full size while searching so it doesn't jump around
+    let content_height = help_lines().len() as u16;
     let height = (content_height + 2).min(term.height); // +2 for borders
 
     let area = centered_rect(60, height, term);
     f.render_widget(Clear, area);
 
+    let search_title = if app.help_searching || !app.help_search.is_empty() {
+        format!(" /{} ", app.help_search)
+    } else {
+        " / search ".to_string()
+    };
     let block = Block::default()
         .title(" Help (\u{2191}/\u{2193} scroll) ")
+        .title_bottom(Line::from(search_title).right_aligned())
         .borders(Borders::ALL)
         .border_style(Style::default().fg(theme.accent));
 
@@ -1001,15 +1476,423 @@ fn draw_switcher_overlay(f: &mut Frame, app: &App) {
     f.render_widget(list, layout[2]);
 }
 
+fn draw_diagnostics_overlay(f: &mut Frame, app: &App) {
+    let theme = &app.theme;
+    let height = (app.diagnostics.len() as u16 * 2 + 3).min(f.area().height);
+    let area = centered_rect(70, height, f.area());
+    f.render_widget(Clear, area);
+
+    let block = Block::default()
+        .title(" Diagnostics ")
+        .title_bottom(Line::from(" Esc back ").right_aligned())
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(theme.accent));
+
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+
+    let mut lines: Vec<Line> = vec![Line::from("")];
+    for check in &app.diagnostics {
+        let (mark, color) = match check.level {
+            Level::Ok => ("\u{2713}", theme.status_ok),
+            Level::Warn => ("!", theme.status_warn),
+            Level::Fail => ("\u{2717}", theme.status_err),
+        };
+        lines.push(Line::from(vec![
+            Span::styled(format!("  {} ", mark), Style::default().fg(color).add_modifier(Modifier::BOLD)),
+            Span::styled(check.name.clone(), Style::default().fg(theme.text).add_modifier(Modifier::BOLD)),
+        ]));
+        lines.push(Line::from(Span::styled(
+            format!("    {}", check.detail),
+            Style::default().fg(theme.text_dim),
+        )));
+    }
+
+    f.render_widget(Paragraph::new(lines).wrap(Wrap { trim: false }), inner);
+}
+
+fn draw_member_list_overlay(f: &mut Frame, app: &App) {
+    let theme = &app.theme;
+    let filtered = app.filtered_members();
+    let result_rows = (filtered.len() as u16).clamp(1, 12);
+    let height = (result_rows + 4).min(f.area().height); // +2 filter+separator, +2 borders
+    let area = centered_rect(50, height, f.area());
+    f.render_widget(Clear, area);
+
+    let block = Block::default()
+        .title(format!(" Members ({}) ", app.members.len()))
+        .title_bottom(Line::from(" Enter jump to last message  Tab only their messages ").right_aligned())
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(theme.accent));
+
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+
+    let layout = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(1), Constraint::Length(1), Constraint::Min(1)])
+        .split(inner);
+
+    render_input_field(f, &format!("> {}", app.member_filter), layout[0], Style::default(), true);
+
+    f.render_widget(
+        Paragraph::new("─".repeat(layout[1].width as usize))
+            .style(Style::default().fg(theme.dimmed)),
+        layout[1],
+    );
+
+    // Keep the selection in view
+    let rows = layout[2].height as usize;
+    let skip = app.member_selected.saturating_sub(rows.saturating_sub(1));
+    let items: Vec<ListItem> = filtered
+        .iter()
+        .enumerate()
+        .skip(skip)
+        .take(rows)
+        .map(|(i, member)| {
+            let style = if i == app.member_selected {
+                Style::default().fg(theme.accent).bg(theme.highlight_bg)
+            } else {
+                Style::default()
+            };
+            let mut line = match member.display_name {
+                Some(ref name) => Line::from(vec![
+                    Span::styled(format!(" {} ", name), style),
+                    Span::styled(member.user_id.clone(), style.fg(theme.text_dim)),
+                ]),
+                None => Line::from(Span::styled(format!(" {}", member.user_id), style)),
+            };
+            if let Some(status) = app.presence_status.get(&member.user_id) {
+                line.push_span(Span::styled(
+                    format!("  {}", status),
+                    style.fg(theme.dimmed).add_modifier(Modifier::ITALIC),
+                ));
+            }
+            ListItem::new(line)
+        })
+        .collect();
+
+    f.render_widget(List::new(items), layout[2]);
+}
+
+fn draw_knocks_overlay(f: &mut Frame, app: &App) {
+    let theme = &app.theme;
+    let rows = (app.knocks.len() as u16 * 2).clamp(1, 16);
+    let height = (rows + 2).min(f.area().height);
+    let area = centered_rect(60, height, f.area());
+    f.render_widget(Clear, area);
+
+    let block = Block::default()
+        .title(format!(" Join Requests ({}) ", app.knocks.len()))
+        .title_bottom(Line::from(" Enter/a let in  d turn away  Esc close ").right_aligned())
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(theme.accent));
+
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+
+    if app.knocks.is_empty() {
+        f.render_widget(
+            Paragraph::new(" No pending requests").style(Style::default().fg(theme.dimmed)),
+            inner,
+        );
+        return;
+    }
+
+    // Two rows per request: who, then their reason; keep the selection in view
+    let visible = (inner.height as usize / 2).max(1);
+    let skip = app.knock_selected.saturating_sub(visible.saturating_sub(1));
+    let items: Vec<ListItem> = app
+        .knocks
+        .iter()
+        .enumerate()
+        .skip(skip)
+        .take(visible)
+        .map(|(i, knock)| {
+            let style = if i == app.knock_selected {
+                Style::default().fg(theme.accent).bg(theme.highlight_bg)
+            } else {
+                Style::default()
+            };
+            let who = match knock.display_name {
+                Some(ref name) => Line::from(vec![
+                    Span::styled(format!(" {} ", name), style),
+                    Span::styled(knock.user_id.clone(), style.fg(theme.text_dim)),
+                ]),
+                None => Line::from(Span::styled(format!(" {}", knock.user_id), style)),
+            };
+            let reason = Line::from(Span::styled(
+                format!("   {}", knock.reason.as_deref().unwrap_or("(no reason given)")),
+                Style::default().fg(theme.text_dim).add_modifier(Modifier::ITALIC),
+            ));
+            ListItem::new(vec![who, reason])
+        })
+        .collect();
+
+    f.render_widget(List::new(items), inner);
+}
+
+/// Past status messages, newest first
+fn draw_status_history_overlay(f: &mut Frame, app: &App) {
+    let theme = &app.theme;
+    let height = (app.status_history.len() as u16 + 2).clamp(3, 22).min(f.area().height);
+    let area = centered_rect(80, height, f.area());
+    f.render_widget(Clear, area);
+
+    let block = Block::default()
+        .title(format!(" Status History ({}) ", app.status_history.len()))
+        .title_bottom(Line::from(" \u{2191}/\u{2193} scroll  Esc close ").right_aligned())
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(theme.accent));
+
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+
+    if app.status_history.is_empty() {
+        f.render_widget(
+            Paragraph::new(" Nothing yet").style(Style::default().fg(theme.dimmed)),
+            inner,
+        );
+        return;
+    }
+
+    let items: Vec<ListItem> = app
+        .status_history
+        .iter()
+        .rev()
+        .skip(app.status_history_scroll)
+        .take(inner.height as usize)
+        .map(|(at, msg)| {
+            let lower = msg.to_lowercase();
+            let is_problem = ["fail", "error", "\u{26a0}"].iter().any(|w| lower.contains(w));
+            let style = if is_problem {
+                Style::default().fg(theme.status_err)
+            } else {
+                Style::default()
+            };
+            ListItem::new(Line::from(vec![
+                Span::styled(format!(" {} ", at.format("%H:%M:%S")), Style::default().fg(theme.dimmed)),
+                Span::styled(msg.clone(), style),
+            ]))
+        })
+        .collect();
+
+    f.render_widget(List::new(items), inner);
+}
+
+/// Summary of the devices a held first message would be encrypted for; `r` lists
+/// the unverified ones
+fn draw_device_review_overlay(f: &mut Frame, app: &App) {
+    let theme = &app.theme;
+    let Some(ref review) = app.device_review else {
+        return;
+    };
+    let unverified: Vec<_> = review.unverified().collect();
+    let list_rows = if review.listing {
+        unverified.len().min(12) as u16 + 1
+    } else {
+        0
+    };
+    let height = (list_rows + 6).min(f.area().height);
+    let area = centered_rect(60, height, f.area());
+    f.render_widget(Clear, area);
+
+    let block = Block::default()
+        .title(" Unverified Devices ")
+        .title_bottom(
+            Line::from(" r review  t trust all & send  Enter send anyway  Esc cancel ").right_aligned(),
+        )
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(theme.status_warn));
+
+    let mut lines = vec![
+        Line::from(""),
+        Line::from(Span::styled(
+            format!(
+                " {} devices, {} unverified",
+                review.devices.len(),
+                unverified.len()
+            ),
+            Style::default().fg(theme.text).add_modifier(Modifier::BOLD),
+        )),
+        Line::from(Span::styled(
+            " Your message will be readable by all of them.",
+            Style::default().fg(theme.text_dim),
+        )),
+    ];
+    if review.listing {
+        lines.push(Line::from(""));
+        for device in unverified.iter().skip(review.scroll).take(12) {
+            let name = device.display_name.as_deref().unwrap_or("unnamed");
+            lines.push(Line::from(vec![
+                Span::styled(format!(" {} ", device.user_id), Style::default().fg(theme.text)),
+                Span::styled(
+                    format!("{} ({})", name, device.device_id),
+                    Style::default().fg(theme.text_dim),
+                ),
+            ]));
+        }
+    }
+
+    f.render_widget(Paragraph::new(lines).block(block), area);
+}
+
+/// The broadcast target awaiting confirmation, with the message going out, then
+/// progress while the last sends finish
+fn draw_broadcast_overlay(f: &mut Frame, app: &App) {
+    let theme = &app.theme;
+    let Some(ref b) = app.broadcast else {
+        return;
+    };
+    let Some(target) = b.targets.get(b.next) else {
+        let area = centered_rect(60, 3, f.area());
+        f.render_widget(Clear, area);
+        let block = Block::default()
+            .title(" Broadcast ")
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(theme.accent));
+        f.render_widget(
+            Paragraph::new(format!(" Sending\u{2026} {} left", b.in_flight.len()))
+                .style(Style::default().fg(theme.text_dim))
+                .block(block),
+            area,
+        );
+        return;
+    };
+    let warning = b.devices.as_ref().map(|devices| {
+        let unverified = devices.iter().filter(|d| !d.verified).count();
+        format!(
+            " \u{26a0} {} devices, {} unverified \u{2014} t trusts them and sends",
+            devices.len(),
+            unverified
+        )
+    });
+    let inner_width = centered_rect(60, 1, f.area()).width.saturating_sub(2) as usize;
+    let body_rows: usize = b
+        .body
+        .lines()
+        .map(|l| wrapped_height_indented(l.chars().count() + 1, 0, inner_width))
+        .sum();
+    let warning_rows = if warning.is_some() { 1 } else { 0 };
+    let height = (body_rows.min(10) as u16 + 6 + warning_rows).min(f.area().height);
+    let area = centered_rect(60, height, f.area());
+    f.render_widget(Clear, area);
+
+    let block = Block::default()
+        .title(format!(" Broadcast ({} of {}) ", b.next + 1, b.targets.len()))
+        .title_bottom(
+            Line::from(" Enter/y send  n skip  a send to all remaining  Esc stop ").right_aligned(),
+        )
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(theme.accent));
+
+    let mut lines = vec![
+        Line::from(""),
+        Line::from(vec![
+            Span::styled(" Send to ", Style::default().fg(theme.text_dim)),
+            Span::styled(
+                target.name.clone(),
+                Style::default().fg(theme.text).add_modifier(Modifier::BOLD),
+            ),
+            Span::styled(format!(" as {}", target.account_id), Style::default().fg(theme.text_dim)),
+        ]),
+    ];
+    if let Some(warning) = warning {
+        lines.push(Line::from(Span::styled(warning, Style::default().fg(theme.status_warn))));
+    }
+    lines.push(Line::from(""));
+    for line in b.body.lines() {
+        lines.push(Line::from(Span::styled(
+            format!(" {}", line),
+            Style::default().fg(theme.text),
+        )));
+    }
+
+    f.render_widget(
+        Paragraph::new(lines).block(block).wrap(Wrap { trim: false }),
+        area,
+    );
+}
+
+fn draw_confirm_overlay(f: &mut Frame, app: &App) {
+    let theme = &app.theme;
+    let Some(ref action) = app.pending_confirm else {
+        return;
+    };
+    let (title, consequences) = action.describe();
+    // Consequences wrap in narrow terminals; size the box to fit them
+    let inner_width = centered_rect(60, 1, f.area()).width.saturating_sub(2) as usize;
+    let text_rows: usize = consequences
+        .iter()
+        .map(|c| wrapped_height_indented(c.chars().count() + 3, 0, inner_width))
+        .sum();
+    let height = (text_rows as u16 + 4).min(f.area().height);
+    let area = centered_rect(60, height, f.area());
+    f.render_widget(Clear, area);
+
+    let block = Block::default()
+        .title(format!(" {} ", title))
+        .title_bottom(Line::from(" Enter/y confirm  Esc cancel ").right_aligned())
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(theme.status_err));
+
+    let mut lines = vec![Line::from("")];
+    for consequence in consequences {
+        lines.push(Line::from(Span::styled(
+            format!(" \u{2022} {}", consequence),
+            Style::default().fg(theme.text),
+        )));
+    }
+
+    f.render_widget(
+        Paragraph::new(lines).block(block).wrap(Wrap { trim: false }),
+        area,
+    );
+}
+
+fn draw_timeline_filters_overlay(f: &mut Frame, app: &App) {
+    let theme = &app.theme;
+    let filters = app.timeline_filter_items();
+    let height = (filters.len() as u16 + 2).min(f.area().height);
+    let area = centered_rect(40, height, f.area());
+    f.render_widget(Clear, area);
+
+    let block = Block::default()
+        .title(" Timeline Filters ")
+        .title_bottom(Line::from(" Space toggle  Esc apply ").right_aligned())
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(theme.accent));
+
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+
+    let items: Vec<ListItem> = filters
+        .iter()
+        .enumerate()
+        .map(|(i, (label, on))| {
+            let style = if i == app.filter_selected {
+                Style::default().fg(theme.accent).bg(theme.highlight_bg)
+            } else {
+                Style::default()
+            };
+            let mark = if *on { "[x]" } else { "[ ]" };
+            ListItem::new(Line::from(Span::styled(format!(" {} {}", mark, label), style)))
+        })
+        .collect();
+
+    f.render_widget(List::new(items), inner);
+}
+
 fn draw_settings_overlay(f: &mut Frame, app: &App) {
     let theme = &app.theme;
 
     // Dynamic height based on expanded sub-menus
-    let mut content_lines: u16 = 7; // top_pad + Accounts + Theme + Sort + Clear Cache + bottom_pad + hint
+    let mut content_lines: u16 = 9; // top_pad + Accounts + Theme + Sort + Clock + Diagnostics + Clear Cache + bottom_pad + hint
     if app.settings_accounts_open {
-        content_lines += 1 + app.accounts.len() as u16; // Add Account + each account
+        // Add Account + each active account + each disabled account
+        content_lines += 1 + (app.accounts.len() + app.config.disabled_accounts().len()) as u16;
         if app.settings_account_action_open {
-            content_lines += 4; // Reconnect + Remove + Edit Profile + Verify Session
+            content_lines += 9; // Reconnect + Remove + Edit Profile + Verify Session + Disable + Set as Default + Identity Server + Emails & Phones + Log Out
             if app.settings_verify_open {
                 content_lines += 2; // Recovery Key + Another Device
             }
@@ -1094,6 +1977,11 @@ fn draw_settings_overlay(f: &mut Frame, app: &App) {
                 && app.settings_accounts_selected == acct_sel_idx;
             let dot = if acct.syncing { "\u{25cf}" } else { "\u{25cb}" };
             let prefix = if is_sel { "      > " } else { "        " };
+            let default_tag = if app.config.default_account.as_deref() == Some(&acct.user_id) {
+                " (default)"
+            } else {
+                ""
+            };
             let style = if is_sel || is_action_target {
                 Style::default()
                     .fg(theme.text)
@@ -1103,13 +1991,23 @@ fn draw_settings_overlay(f: &mut Frame, app: &App) {
                 Style::default().fg(theme.text_dim)
             };
             lines.push(Line::from(Span::styled(
-                format!("{}{} {}", prefix, dot, acct.user_id),
+                format!("{}{} {}{}", prefix, dot, acct.user_id, default_tag),
                 style,
             )));
 
             // Action menu for this account
             if is_action_target {
-                let actions = ["Reconnect", "Remove Account", "Edit Profile", "Verify Session"];
+                let actions = [
+                    "Reconnect",
+                    "Remove Account",
+                    "Edit Profile",
+                    "Verify Session",
+                    "Disable",
+                    "Set as Default",
+                    "Identity Server",
+                    "Emails & Phones",
+                    "Log Out",
+                ];
                 for (j, action) in actions.iter().enumerate() {
                     let is_action_sel = !app.settings_verify_open
                         && app.settings_account_action_selected == j;
@@ -1163,6 +2061,28 @@ fn draw_settings_overlay(f: &mut Frame, app: &App) {
                 }
             }
         }
+
+        // Disabled accounts — Enter re-enables
+        for (i, saved) in app.config.disabled_accounts().iter().enumerate() {
+            let acct_sel_idx = 1 + app.accounts.len() + i;
+            let is_sel = !app.settings_account_action_open
+                && app.settings_accounts_selected == acct_sel_idx;
+            let prefix = if is_sel { "      > " } else { "        " };
+            let style = if is_sel {
+                Style::default()
+                    .fg(theme.text)
+                    .bg(theme.highlight_bg)
+                    .add_modifier(Modifier::BOLD)
+            } else {
+                Style::default()
+                    .fg(theme.text_dim)
+                    .add_modifier(Modifier::DIM)
+            };
+            lines.push(Line::from(Span::styled(
+                format!("{}\u{2298} {} (disabled)", prefix, saved.user_id),
+                style,
+            )));
+        }
     }
 
     // --- Theme item ---
@@ -1264,9 +2184,49 @@ fn draw_settings_overlay(f: &mut Frame, app: &App) {
         }
     }
 
+    // --- Clock item ---
+    let sel3 = at_top && app.settings_selected == 3;
+    let (prefix3, style3) = if sel3 {
+        (
+            "  > ",
+            Style::default()
+                .fg(theme.text)
+                .bg(theme.highlight_bg)
+                .add_modifier(Modifier::BOLD),
+        )
+    } else {
+        ("    ", Style::default().fg(theme.text_dim))
+    };
+    lines.push(Line::from(Span::styled(
+        format!(
+            "{}Clock: {}",
+            prefix3,
+            if app.config.show_clock { "On" } else { "Off" }
+        ),
+        style3,
+    )));
+
+    // --- Diagnostics item ---
+    let sel4 = at_top && app.settings_selected == 4;
+    let (prefix4, style4) = if sel4 {
+        (
+            "  > ",
+            Style::default()
+                .fg(theme.text)
+                .bg(theme.highlight_bg)
+                .add_modifier(Modifier::BOLD),
+        )
+    } else {
+        ("    ", Style::default().fg(theme.text_dim))
+    };
+    lines.push(Line::from(Span::styled(
+        format!("{}Diagnostics", prefix4),
+        style4,
+    )));
+
     // --- Clear Cache item ---
-    let sel3 = at_top && app.settings_selected == 3;
-    let (prefix3, style3) = if sel3 {
+    let sel5 = at_top && app.settings_selected == 5;
+    let (prefix5, style5) = if sel5 {
         (
             "  > ",
             Style::default()
@@ -1278,8 +2238,8 @@ fn draw_settings_overlay(f: &mut Frame, app: &App) {
         ("    ", Style::default().fg(theme.status_err))
     };
     lines.push(Line::from(Span::styled(
-        format!("{}Clear Cache", prefix3),
-        style3,
+        format!("{}Clear Cache", prefix5),
+        style5,
     )));
 
     // Bottom padding
@@ -1288,6 +2248,8 @@ fn draw_settings_overlay(f: &mut Frame, app: &App) {
     // Hint
     let hint_text = if app.settings_account_action_open || app.settings_theme_open || app.settings_sort_open {
         "  \u{2191}/\u{2193} select   Enter apply   Esc back"
+    } else if app.settings_accounts_open {
+        "  \u{2191}/\u{2193} select   Shift+\u{2191}/\u{2193} reorder   Enter open   Esc back"
     } else {
         "  \u{2191}/\u{2193} select   Enter open   Esc back"
     };
@@ -1404,6 +2366,224 @@ fn draw_profile_overlay(f: &mut Frame, app: &App) {
     f.render_widget(hint, fields[13]);
 }
 
+fn draw_identity_overlay(f: &mut Frame, app: &App) {
+    let theme = &app.theme;
+    let base_width = (f.area().width * 50 / 100).min(f.area().width);
+    let inner_w = base_width.saturating_sub(2);
+    let url_lines = input_field_lines(&app.identity_url, inner_w);
+    let terms_lines = match &app.identity_terms {
+        Some(terms) => 1 + terms.len().max(1) as u16,
+        None => 0,
+    };
+    let height = (9 + url_lines + terms_lines).min(f.area().height);
+    let area = centered_rect(50, height, f.area());
+    f.render_widget(Clear, area);
+
+    let block = Block::default()
+        .title(" Identity Server ")
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(theme.accent));
+
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+
+    let fields = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(1),           // user id
+            Constraint::Length(1),           // current server
+            Constraint::Length(1),           // spacer
+            Constraint::Length(1),           // label
+            Constraint::Length(url_lines),   // url field
+            Constraint::Length(terms_lines), // terms
+            Constraint::Length(1),           // spacer
+            Constraint::Min(1),              // error/hint
+        ])
+        .split(inner);
+
+    let acct_label = app
+        .accounts
+        .get(app.identity_account_idx)
+        .map(|a| a.user_id.as_str())
+        .unwrap_or("");
+    f.render_widget(
+        Paragraph::new(format!("  {}", acct_label)).style(Style::default().fg(theme.accent)),
+        fields[0],
+    );
+    f.render_widget(
+        Paragraph::new(format!(
+            "  Current: {}",
+            app.identity_current.as_deref().unwrap_or("(none)")
+        ))
+        .style(Style::default().fg(theme.text_dim)),
+        fields[1],
+    );
+
+    f.render_widget(
+        Paragraph::new("  Server URL:").style(Style::default().fg(theme.text_dim)),
+        fields[3],
+    );
+    render_input_field(
+        f,
+        &app.identity_url,
+        fields[4],
+        field_style(true, theme),
+        !app.identity_busy,
+    );
+
+    if let Some(terms) = &app.identity_terms {
+        let mut lines = vec![Line::from(Span::styled(
+            "  Accepting these terms:",
+            Style::default().fg(theme.text_dim),
+        ))];
+        if terms.is_empty() {
+            lines.push(Line::from(Span::styled(
+                "    (this server has no terms)",
+                Style::default().fg(theme.dimmed),
+            )));
+        }
+        for (name, url) in terms {
+            lines.push(Line::from(vec![
+                Span::styled(format!("    {} ", name), Style::default().fg(theme.text)),
+                Span::styled(url.clone(), Style::default().fg(theme.dimmed)),
+            ]));
+        }
+        f.render_widget(Paragraph::new(lines), fields[5]);
+    }
+
+    let hint = if let Some(err) = &app.identity_error {
+        Paragraph::new(format!("  {}", err))
+            .style(Style::default().fg(theme.status_err))
+            .wrap(Wrap { trim: false })
+    } else if app.identity_busy {
+        Paragraph::new("  Working...")
+            .style(Style::default().fg(theme.status_warn))
+            .wrap(Wrap { trim: false })
+    } else if app.identity_terms.is_some() {
+        Paragraph::new("  Enter: accept and connect  Esc: back")
+            .style(Style::default().fg(theme.dimmed))
+            .wrap(Wrap { trim: false })
+    } else {
+        Paragraph::new("  Enter: review terms  Ctrl+D: disconnect  Esc: back")
+            .style(Style::default().fg(theme.dimmed))
+            .wrap(Wrap { trim: false })
+    };
+    f.render_widget(hint, fields[7]);
+}
+
+fn draw_threepid_overlay(f: &mut Frame, app: &App) {
+    let theme = &app.theme;
+    let base_width = (f.area().width * 50 / 100).min(f.area().width);
+    let inner_w = base_width.saturating_sub(2);
+    let list_lines = app.threepid_list.len().max(1) as u16;
+    let entering = matches!(
+        app.threepid_step,
+        ThreepidStep::EnterAddress | ThreepidStep::Validate | ThreepidStep::Password
+    );
+    let field_lines = if entering {
+        2 + input_field_lines(&app.threepid_input, inner_w)
+    } else {
+        0
+    };
+    let height = (6 + list_lines + field_lines).min(f.area().height);
+    let area = centered_rect(50, height, f.area());
+    f.render_widget(Clear, area);
+
+    let block = Block::default()
+        .title(" Emails & Phones ")
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(theme.accent));
+
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+
+    let fields = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(1),            // user id
+            Constraint::Length(list_lines),   // addresses
+            Constraint::Length(field_lines),  // step prompt + input
+            Constraint::Length(1),            // spacer
+            Constraint::Min(1),               // error/hint
+        ])
+        .split(inner);
+
+    let acct_label = app
+        .accounts
+        .get(app.threepid_account_idx)
+        .map(|a| a.user_id.as_str())
+        .unwrap_or("");
+    f.render_widget(
+        Paragraph::new(format!("  {}", acct_label)).style(Style::default().fg(theme.accent)),
+        fields[0],
+    );
+
+    let mut lines: Vec<Line> = Vec::new();
+    if app.threepid_list.is_empty() {
+        lines.push(Line::from(Span::styled(
+            "  (no emails or phone numbers)",
+            Style::default().fg(theme.dimmed),
+        )));
+    }
+    for (i, (medium, address)) in app.threepid_list.iter().enumerate() {
+        let selected = !entering && i == app.threepid_selected;
+        let style = if selected {
+            Style::default().fg(theme.text).bg(theme.highlight_bg)
+        } else {
+            Style::default().fg(theme.text_dim)
+        };
+        let prefix = if selected { "> " } else { "  " };
+        lines.push(Line::from(Span::styled(
+            format!("{}{:<7} {}", prefix, medium.as_str(), address),
+            style,
+        )));
+    }
+    f.render_widget(Paragraph::new(lines), fields[1]);
+
+    if entering {
+        let (label, masked) = match (app.threepid_step, app.threepid_phone) {
+            (ThreepidStep::EnterAddress, false) => ("  New email address:", false),
+            (ThreepidStep::EnterAddress, true) => ("  Country code and number (e.g. GB 07700900123):", false),
+            (ThreepidStep::Validate, false) => ("  Click the link in the email, then press Enter", false),
+            (ThreepidStep::Validate, true) => ("  Code from the text message:", false),
+            _ => ("  Account password:", true),
+        };
+        let prompt = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Length(1), Constraint::Length(1), Constraint::Min(1)])
+            .split(fields[2]);
+        f.render_widget(
+            Paragraph::new(label).style(Style::default().fg(theme.text_dim)),
+            prompt[1],
+        );
+        let shown = if masked {
+            "*".repeat(app.threepid_input.chars().count())
+        } else {
+            app.threepid_input.clone()
+        };
+        render_input_field(f, &shown, prompt[2], field_style(true, theme), !app.threepid_busy);
+    }
+
+    let hint = if let Some(err) = &app.threepid_error {
+        Paragraph::new(format!("  {}", err))
+            .style(Style::default().fg(theme.status_err))
+            .wrap(Wrap { trim: false })
+    } else if app.threepid_busy {
+        Paragraph::new("  Working...")
+            .style(Style::default().fg(theme.status_warn))
+            .wrap(Wrap { trim: false })
+    } else {
+        let text = match app.threepid_step {
+            ThreepidStep::List => "  a: add email  p: add phone  d: remove  Esc: back",
+            _ => "  Enter: continue  Esc: cancel",
+        };
+        Paragraph::new(text)
+            .style(Style::default().fg(theme.dimmed))
+            .wrap(Wrap { trim: false })
+    };
+    f.render_widget(hint, fields[4]);
+}
+
 fn draw_creator_overlay(f: &mut Frame, app: &App) {
     let theme = &app.theme;
     let base_width = (f.area().width * 50 / 100).min(f.area().width);
@@ -1542,7 +2722,7 @@ fn draw_editor_overlay(f: &mut Frame, app: &App) {
     let nm_lines = input_field_lines(&app.editor_name, inner_w);
     let tp_lines = input_field_lines(&app.editor_topic, inner_w);
     let inv_lines = input_field_lines(&app.editor_invite_user, inner_w);
-    let height = (15 + nm_lines + tp_lines + inv_lines).min(f.area().height);
+    let height = (16 + nm_lines + tp_lines + inv_lines).min(f.area().height);
     let area = centered_rect(50, height, f.area());
     f.render_widget(Clear, area);
 
@@ -1568,6 +2748,7 @@ fn draw_editor_overlay(f: &mut Frame, app: &App) {
             Constraint::Length(1),         // label
             Constraint::Length(inv_lines), // invite field
             Constraint::Length(1),         // spacer
+            Constraint::Length(1),         // directory toggle
             Constraint::Length(1),         // leave button
             Constraint::Length(1),         // delete button
             Constraint::Length(1),         // spacer
@@ -1612,49 +2793,38 @@ fn draw_editor_overlay(f: &mut Frame, app: &App) {
     );
     render_input_field(f, &app.editor_invite_user, fields[9], s2, cursor_ok && app.editor_focus == 2);
 
-    // Leave button
-    let leave_style = if app.editor_focus == 3 {
-        if app.editor_confirm_leave {
-            Style::default()
-                .fg(theme.status_err)
-                .bg(theme.highlight_bg)
-                .add_modifier(Modifier::BOLD)
-        } else {
-            Style::default()
-                .fg(theme.status_err)
-                .bg(theme.highlight_bg)
-        }
+    // Directory toggle
+    let directory_style = if app.editor_focus == 3 {
+        Style::default().fg(theme.text).bg(theme.highlight_bg)
     } else {
-        Style::default().fg(theme.status_err)
+        Style::default().fg(theme.text_dim)
     };
-    let leave_text = if app.editor_confirm_leave {
-        "  [ Press Enter again to leave ]"
-    } else {
-        "  [ Leave Room ]"
+    let directory_text = match app.editor_published {
+        Some(true) => "  [x] Published in room directory",
+        Some(false) => "  [ ] Published in room directory",
+        None => "  [?] Published in room directory",
     };
-    f.render_widget(Paragraph::new(leave_text).style(leave_style), fields[11]);
+    f.render_widget(Paragraph::new(directory_text).style(directory_style), fields[11]);
 
-    // Delete button
-    let delete_style = if app.editor_focus == 4 {
-        if app.editor_confirm_delete {
-            Style::default()
-                .fg(theme.status_err)
-                .bg(theme.highlight_bg)
-                .add_modifier(Modifier::BOLD)
-        } else {
-            Style::default()
-                .fg(theme.status_err)
-                .bg(theme.highlight_bg)
-        }
+    // Leave button
+    let leave_style = if app.editor_focus == 4 {
+        Style::default()
+            .fg(theme.status_err)
+            .bg(theme.highlight_bg)
     } else {
         Style::default().fg(theme.status_err)
     };
-    let delete_text = if app.editor_confirm_delete {
-        "  [ Press Enter again to DELETE ]"
+    f.render_widget(Paragraph::new("  [ Leave Room ]").style(leave_style), fields[12]);
+
+    // Delete button
+    let delete_style = if app.editor_focus == 5 {
+        Style::default()
+            .fg(theme.status_err)
+            .bg(theme.highlight_bg)
     } else {
-        "  [ Delete Room ]"
+        Style::default().fg(theme.status_err)
     };
-    f.render_widget(Paragraph::new(delete_text).style(delete_style), fields[12]);
+    f.render_widget(Paragraph::new("  [ Delete Room ]").style(delete_style), fields[13]);
 
     let hint = if let Some(err) = &app.editor_error {
         Paragraph::new(format!("  {}", err))
@@ -1669,7 +2839,7 @@ fn draw_editor_overlay(f: &mut Frame, app: &App) {
             .style(Style::default().fg(theme.dimmed))
             .wrap(Wrap { trim: false })
     };
-    f.render_widget(hint, fields[14]);
+    f.render_widget(hint, fields[15]);
 }
 
 fn draw_recovery_overlay(f: &mut Frame, app: &App) {
@@ -2324,7 +3494,7 @@ fn draw_room_info_overlay(f: &mut Frame, app: &App) {
     } else {
         0
     };
-    let height = (9 + topic_lines).min(term.height);
+    let height = (10 + topic_lines).min(term.height);
     let area = centered_rect(60, height, term);
 
     f.render_widget(ratatui::widgets::Clear, area);
@@ -2368,6 +3538,15 @@ fn draw_room_info_overlay(f: &mut Frame, app: &App) {
         format!("  Encryption: {}", details.encryption),
         Style::default().fg(theme.text),
     )));
+    let directory = match details.published {
+        Some(true) => "Published",
+        Some(false) => "Not published",
+        None => "Unknown",
+    };
+    lines.push(Line::from(Span::styled(
+        format!("  Room directory: {}", directory),
+        Style::default().fg(theme.text),
+    )));
 
     let content = Paragraph::new(lines).wrap(Wrap { trim: false });
     f.render_widget(content, inner);
@@ -2435,6 +3614,164 @@ fn draw_file_confirm_overlay(f: &mut Frame, app: &App) {
     );
 }
 
+fn draw_room_batch_overlay(f: &mut Frame, app: &App) {
+    let theme = &app.theme;
+    let actions = app.room_batch_labels();
+    let names: Vec<&str> = app
+        .all_rooms
+        .iter()
+        .filter(|r| app.marked_rooms.contains(&r.id))
+        .map(|r| r.name.as_str())
+        .collect();
+
+    let height = (actions.len() as u16 + 7).min(f.area().height);
+    let area = centered_rect(50, height, f.area());
+    f.render_widget(Clear, area);
+
+    let block = Block::default()
+        .title(format!(" {} Rooms ", names.len()))
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(theme.accent));
+
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+
+    let mut lines = vec![
+        Line::from(""),
+        Line::from(Span::styled(
+            format!("  {}", names.join(", ")),
+            Style::default().fg(theme.text_dim),
+        )),
+        Line::from(""),
+    ];
+    for (i, action) in actions.iter().enumerate() {
+        let is_sel = app.room_batch_selected == i;
+        let is_leave = *action == "Leave Rooms";
+        let prefix = if is_sel { "  > " } else { "    " };
+        let fg = if is_leave {
+            theme.status_err
+        } else if is_sel {
+            theme.text
+        } else {
+            theme.text_dim
+        };
+        let style = if is_sel {
+            Style::default()
+                .fg(fg)
+                .bg(theme.highlight_bg)
+                .add_modifier(Modifier::BOLD)
+        } else {
+            Style::default().fg(fg)
+        };
+        lines.push(Line::from(Span::styled(format!("{}{}", prefix, action), style)));
+    }
+    lines.push(Line::from(""));
+    lines.push(Line::from(Span::styled(
+        "  Enter: apply to all marked    Esc: cancel",
+        Style::default().fg(theme.dimmed),
+    )));
+    f.render_widget(Paragraph::new(lines).wrap(Wrap { trim: false }), inner);
+}
+
+fn draw_join_favorites_overlay(f: &mut Frame, app: &App) {
+    let theme = &app.theme;
+    let shown: Vec<&str> = app
+        .favorite_joins
+        .iter()
+        .take(8)
+        .map(|e| e.alias.as_deref().or(e.name.as_deref()).unwrap_or(&e.room_id))
+        .collect();
+    let total = app.favorite_joins.len();
+
+    let height = (shown.len() as u16 + 8).min(f.area().height);
+    let area = centered_rect(60, height, f.area());
+    f.render_widget(Clear, area);
+
+    let block = Block::default()
+        .title(format!(" Join {} rooms? ", total))
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(theme.accent));
+
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+
+    let account = app
+        .accounts
+        .get(app.favorite_join_account)
+        .map(|a| a.user_id.as_str())
+        .unwrap_or("");
+    let mut lines = vec![
+        Line::from(""),
+        Line::from(Span::styled(
+            "  These favorites aren't joined on any account:",
+            Style::default().fg(theme.text_dim),
+        )),
+    ];
+    for name in &shown {
+        lines.push(Line::from(format!("    {}", name)));
+    }
+    if total > shown.len() {
+        lines.push(Line::from(Span::styled(
+            format!("    ... {} more", total - shown.len()),
+            Style::default().fg(theme.dimmed),
+        )));
+    }
+    lines.push(Line::from(""));
+    lines.push(Line::from(vec![
+        Span::styled("  Join as ", Style::default().fg(theme.text_dim)),
+        Span::styled(account.to_string(), Style::default().fg(theme.accent)),
+    ]));
+    lines.push(Line::from(""));
+    let hint = if app.favorite_join_busy {
+        "  Joining..."
+    } else if app.accounts.len() > 1 {
+        "  Enter: join all    Tab: account    Esc: skip"
+    } else {
+        "  Enter: join all    Esc: skip"
+    };
+    lines.push(Line::from(Span::styled(hint, Style::default().fg(theme.dimmed))));
+    f.render_widget(Paragraph::new(lines), inner);
+}
+
+fn draw_code_paste_overlay(f: &mut Frame, app: &App) {
+    let theme = &app.theme;
+    let code = app.pending_code_paste.as_deref().unwrap_or("");
+    let preview: Vec<&str> = code.trim_end_matches('\n').lines().take(6).collect();
+    let total = code.trim_end_matches('\n').lines().count();
+
+    let height = (preview.len() as u16 + 6).min(f.area().height);
+    let area = centered_rect(60, height, f.area());
+    f.render_widget(Clear, area);
+
+    let block = Block::default()
+        .title(format!(" Paste {} lines ", total))
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(theme.accent));
+
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+
+    let mut lines = vec![Line::from("")];
+    for line in &preview {
+        lines.push(Line::from(Span::styled(
+            format!("  {}", line),
+            Style::default().fg(theme.text_dim),
+        )));
+    }
+    if total > preview.len() {
+        lines.push(Line::from(Span::styled(
+            format!("  ... {} more", total - preview.len()),
+            Style::default().fg(theme.dimmed),
+        )));
+    }
+    lines.push(Line::from(""));
+    lines.push(Line::from(Span::styled(
+        "  Enter: send as code block    t: paste as text    Esc: cancel",
+        Style::default().fg(theme.dimmed),
+    )));
+    f.render_widget(Paragraph::new(lines), inner);
+}
+
 fn centered_rect(percent_x: u16, height: u16, area: Rect) -> Rect {
     let popup_width = (area.width * percent_x / 100).min(area.width);
     let popup_height = height.min(area.height);